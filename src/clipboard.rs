@@ -0,0 +1,1469 @@
+//! the clipboard handle
+use crate::{
+    options, providers, Backend, ClearGuard, ClipboardGuard, Error, Options, Provider, Result,
+    SecretGuard,
+};
+use std::{
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, PoisonError},
+    time::Duration,
+};
+
+/// A clipboard, to hold onto.
+///
+/// The free functions ([`copy`](crate::copy), [`paste`](crate::paste), ...) use one of these,
+/// detected on first use. Make your own to use a particular provider,
+/// or several different ones at once.
+///
+/// ```no_run
+/// use clipp::{Clipboard, Provider};
+/// let local = Clipboard::with_provider(Provider::Wayland)?;
+/// let x = Clipboard::with_provider(Provider::XClip)?;
+/// x.copy(&local.paste()?)?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Clipboard {
+    provider: Provider,
+    pub(crate) backend: Arc<dyn Backend>,
+}
+
+impl Clipboard {
+    /// Detect the clipboard, with the [default options](crate::Options::set_default).
+    ///
+    /// # Errors
+    ///
+    /// If no clipboard is available.
+    pub fn detect() -> Result<Self> {
+        options::DEFAULT
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .detect()
+    }
+
+    /// Use this provider, skipping detection.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::with_provider(clipp::Provider::XClip)?;
+    /// clip.copy("only xclip")?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If a program the provider needs is not installed.
+    pub fn with_provider(provider: Provider) -> Result<Self> {
+        provider
+            .check(&providers::System)
+            .and_then(|p| Self::of(p, &Options::new()))
+    }
+
+    /// Use your own [`Backend`]. Its [provider](Self::provider) is [`Provider::Custom`].
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self::from(Arc::new(backend) as Arc<dyn Backend>)
+    }
+
+    /// A clipboard for this process alone, for when there is no other.
+    pub(crate) fn memory() -> Self {
+        Self {
+            provider: Provider::Memory,
+            backend: Arc::new(Scratch::default()),
+        }
+    }
+
+    /// `provider`, on the selection `o` asks for, if it has that one.
+    pub(crate) fn of(provider: Provider, o: &Options) -> Result<Self> {
+        if !provider.has_selection(o.selection) {
+            return Err(Error::Unsupported {
+                what: match o.selection {
+                    crate::Selection::Secondary => "the secondary selection outside of X11",
+                    _ => "the primary selection outside of X11 and Wayland",
+                },
+            });
+        }
+        Ok(Self {
+            provider,
+            backend: provider.backend(o)?,
+        })
+    }
+
+    /// Which provider this is.
+    #[must_use]
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    /// Copy text to this clipboard.
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    pub fn copy(&self, text: &str) -> Result<()> {
+        self.backend.copy(text)
+    }
+
+    /// Copy what `r` reads to this clipboard, streaming it to the provider where it can
+    /// take it that way. Through a helper program, it needn't be UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// If reading fails, with [`Error::Read`], or copying fails.
+    pub fn copy_from(&self, r: &mut dyn io::Read) -> Result<()> {
+        self.backend.copy_from(r)
+    }
+
+    /// Copy bytes to this clipboard, which needn't be text where it goes through a helper program
+    /// or a file. See [`copy_from`](Self::copy_from).
+    ///
+    /// # Errors
+    ///
+    /// If they aren't UTF-8 and this clipboard only takes text, e.g. klipper's or OSC 52,
+    /// with [`Error::Unsupported`], or copying fails.
+    pub fn copy_bytes(&self, bytes: &[u8]) -> Result<()> {
+        self.backend.copy_from(&mut &*bytes)
+    }
+
+    /// Copy what `args` format to, handed to the provider a chunk at a time as it is formatted,
+    /// where it can take it that way, so that something big is never all held at once. See
+    /// [`copy_from`](Self::copy_from).
+    ///
+    /// Nothing is copied if formatting fails, with [`Error::Read`].
+    ///
+    /// # Errors
+    ///
+    /// If formatting or copying fails.
+    pub fn copy_fmt(&self, args: fmt::Arguments<'_>) -> Result<()> {
+        const CHUNK: usize = 64 * 1024;
+        /// What was formatted, as it is sent from the formatting thread.
+        struct Chunks {
+            rx: mpsc::Receiver<io::Result<Vec<u8>>>,
+            chunk: io::Cursor<Vec<u8>>,
+        }
+        impl io::Read for Chunks {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                loop {
+                    let n = self.chunk.read(buf)?;
+                    if n > 0 || buf.is_empty() {
+                        return Ok(n);
+                    }
+                    match self.rx.recv() {
+                        Ok(chunk) => self.chunk = io::Cursor::new(chunk?),
+                        // that was all of it
+                        Err(_) => return Ok(0),
+                    }
+                }
+            }
+        }
+        /// Sends on what is formatted once there is a chunk of it.
+        struct Sender(mpsc::SyncSender<io::Result<Vec<u8>>>, Vec<u8>);
+        impl fmt::Write for Sender {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let mut s = s.as_bytes();
+                while !s.is_empty() {
+                    let n = (CHUNK - self.1.len()).min(s.len());
+                    self.1.extend_from_slice(&s[..n]);
+                    s = &s[n..];
+                    if self.1.len() == CHUNK {
+                        let chunk = std::mem::replace(&mut self.1, Vec::with_capacity(CHUNK));
+                        // copying gave up
+                        self.0.send(Ok(chunk)).map_err(|_| fmt::Error)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+        // nothing to format
+        if let Some(text) = args.as_str() {
+            return self.copy(text);
+        }
+        let (tx, rx) = mpsc::sync_channel(4);
+        let mut chunks = Chunks {
+            rx,
+            chunk: io::Cursor::default(),
+        };
+        std::thread::scope(|s| {
+            let copying = s.spawn(move || self.copy_from(&mut chunks));
+            let mut sender = Sender(tx, Vec::with_capacity(CHUNK));
+            let last = match fmt::write(&mut sender, args) {
+                Ok(()) => Ok(std::mem::take(&mut sender.1)),
+                // for it to be read as a failure, rather than the end of it
+                Err(_) => Err(io::Error::other("formatting what was to be copied failed")),
+            };
+            _ = sender.0.send(last);
+            drop(sender);
+            copying
+                .join()
+                .unwrap_or_else(|p| std::panic::resume_unwind(p))
+        })
+    }
+
+    /// Copy `data` to this clipboard as `mime`, e.g. `text/html` or `image/png`,
+    /// for whatever pastes it to ask for.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.copy_mime("text/html", b"<b>bold</b> move")?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `mime` isn't a MIME type, with an [`io::ErrorKind::InvalidInput`] error,
+    /// the provider can't do this, with [`Error::Unsupported`], or copying fails.
+    pub fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.backend.copy_mime(checked(mime)?, data)
+    }
+
+    /// Paste what is on this clipboard as `mime`, e.g. the `text/html` a browser copies
+    /// alongside the text.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// match clip.paste_mime("text/html") {
+    ///     Ok(html) => println!("{}", String::from_utf8_lossy(&html)),
+    ///     Err(clipp::Error::FormatUnavailable { .. }) => println!("{}", clip.paste()?),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If it isn't offered as `mime`, with [`Error::FormatUnavailable`], `mime` isn't a MIME
+    /// type, the provider can't do this, with [`Error::Unsupported`], or pasting fails.
+    pub fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        self.backend.paste_mime(checked(mime)?)
+    }
+
+    /// Copy each of `items` to this clipboard as its MIME type, at once, the first being
+    /// the one preferred.
+    ///
+    /// Where they can all be offered, by the native Windows and X11 clipboards and on macOS,
+    /// they are listed in this order. Elsewhere, only one is: the first `text/*` item,
+    /// or failing that the first. [`copies_multi`](Self::copies_multi) tells which it is.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.copy_multi(&[
+    ///     ("text/markdown", "**bold** move".as_bytes()),
+    ///     ("text/plain", "bold move".as_bytes()),
+    /// ])?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If there are no items, or one's type isn't a MIME type, with an
+    /// [`io::ErrorKind::InvalidInput`] error, the provider can't take the one it copies,
+    /// with [`Error::Unsupported`], or copying fails.
+    pub fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        if items.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to copy").into());
+        }
+        for (mime, _) in items {
+            checked(mime)?;
+        }
+        self.backend.copy_multi(items)
+    }
+
+    /// Whether [`copy_multi`](Self::copy_multi) offers all of what it is given here,
+    /// rather than one of them.
+    #[must_use]
+    pub fn copies_multi(&self) -> bool {
+        self.backend.copies_multi()
+    }
+
+    /// Copy `html` to this clipboard, with `alt_text` for whatever pastes it as text,
+    /// so that pasting in a mail client or word processor keeps its formatting, and pasting
+    /// in a terminal gives the text.
+    ///
+    /// Only the native Windows and X11 clipboards offer both. Through a helper program, which
+    /// can only offer one, `alt_text` is copied, see [`Backend::copy_html`].
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.copy_html("<b>bold</b> move", "bold move")?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    pub fn copy_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        self.backend.copy_html(html, alt_text)
+    }
+
+    /// Paste the HTML on this clipboard, e.g. what a browser copies, links and all,
+    /// or [`None`] if there isn't any. See [`paste_mime`](Self::paste_mime).
+    ///
+    /// HTML in UTF-16, as some X11 programs give it, or in latin-1 or windows-1252,
+    /// as its `<meta charset>` says, is converted.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// let pasted = match clip.paste_html()? {
+    ///     Some(html) => html,
+    ///     None => clip.paste()?,
+    /// };
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't do this, with [`Error::Unsupported`], pasting fails,
+    /// or it is in a charset that can't be made sense of, with [`Error::InvalidUtf8`].
+    pub fn paste_html(&self) -> Result<Option<String>> {
+        match self.backend.paste_mime("text/html") {
+            Ok(html) => decoded(&html).map(Some),
+            Err(Error::FormatUnavailable { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copy `rtf` to this clipboard, with `alt_text` for whatever pastes it as text,
+    /// as word processors do. It is offered as `text/rtf`, or as the Windows clipboard's
+    /// `Rich Text Format`.
+    ///
+    /// Only the native Windows and X11 clipboards, and macOS through `osascript`, offer both.
+    /// The rest can't, and fail rather than leave RTF markup for what pastes text,
+    /// see [`Backend::copy_rtf`].
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.copy_rtf(r"{\rtf1\ansi {\b bold} move}", "bold move")?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't do this, with [`Error::Unsupported`], or copying fails.
+    pub fn copy_rtf(&self, rtf: &str, alt_text: &str) -> Result<()> {
+        self.backend.copy_rtf(rtf, alt_text)
+    }
+
+    /// Paste the RTF on this clipboard, e.g. what a word processor copies,
+    /// or [`None`] if there isn't any.
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't do this, with [`Error::Unsupported`], pasting fails,
+    /// or it isn't text, with [`Error::InvalidUtf8`].
+    pub fn paste_rtf(&self) -> Result<Option<String>> {
+        match self.backend.paste_mime("text/rtf") {
+            // windows keeps the nul it ends with
+            Ok(mut rtf) => {
+                while rtf.last() == Some(&0) {
+                    rtf.pop();
+                }
+                String::from_utf8(rtf)
+                    .map(Some)
+                    .map_err(|_| Error::InvalidUtf8)
+            }
+            Err(Error::FormatUnavailable { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copy a secret, e.g. a password, to this clipboard, marked for clipboard managers and
+    /// the system's clipboard history to leave it out.
+    ///
+    /// Where it can't be marked, e.g. through `clip.exe` or OSC 52, it is copied like anything
+    /// else. [`conceals`](Self::conceals) tells beforehand, for a warning to be given.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// if !clip.conceals() {
+    ///     eprintln!("warning: your clipboard history will remember this password");
+    /// }
+    /// clip.copy_concealed("hunter2")?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    pub fn copy_concealed(&self, text: &str) -> Result<()> {
+        self.backend.copy_concealed(text)
+    }
+
+    /// Whether [`copy_concealed`](Self::copy_concealed) marks what it copies as a secret here.
+    #[must_use]
+    pub fn conceals(&self) -> bool {
+        self.backend.conceals()
+    }
+
+    /// Copy a secret, [concealed](Self::copy_concealed), and clear it after `ttl`
+    /// unless something else has been copied by then, as password managers do.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// let clip = clipp::Clipboard::detect()?;
+    /// let guard = clip.copy_secret("hunter2", Duration::from_secs(30))?;
+    /// // logged in, so it can go now
+    /// guard.clear()?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    pub fn copy_secret(&self, text: &str, ttl: Duration) -> Result<SecretGuard> {
+        self.copy_concealed(text)?;
+        Ok(SecretGuard::new(self.clone(), text, ttl))
+    }
+
+    /// Save what is on this clipboard, for it to be put back when the returned guard is dropped.
+    /// See [`ClipboardGuard`].
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    pub fn save(&self) -> Result<ClipboardGuard> {
+        ClipboardGuard::of(self)
+    }
+
+    /// Copy `text` to this clipboard while `f` runs, e.g. to have another program paste it,
+    /// and put back what was there before afterwards, even if `f` panics.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.with_temporary("typed for you", || {
+    ///     std::process::Command::new("xdotool").args(["key", "ctrl+v"]).status()
+    /// })??;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If saving what was there, copying, or putting it back fails.
+    pub fn with_temporary<R>(&self, text: &str, f: impl FnOnce() -> R) -> Result<R> {
+        let saved = self.save()?;
+        self.copy(text)?;
+        let r = f();
+        saved.restore().map(|()| r)
+    }
+
+    /// Copy text to this clipboard for as long as the returned guard lives: dropping it clears
+    /// the clipboard, unless something else has been copied since.
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    pub fn copy_scoped(&self, text: &str) -> Result<ClearGuard> {
+        self.copy(text)?;
+        Ok(ClearGuard::new(self.clone(), text))
+    }
+
+    /// Paste a secret, e.g. a password, for it to be wiped from memory once it is dropped.
+    ///
+    /// Wiping is best-effort, see [`Wipe`](crate::Wipe), and only what is pasted into this
+    /// process is wiped: a helper program it was pasted through
+    /// keeps its own copy for as long as it runs, and the clipboard keeps its own.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    #[cfg(feature = "zeroize")]
+    pub fn paste_secret(&self) -> Result<crate::WipeOnDrop<String>> {
+        self.paste().map(crate::WipeOnDrop::from)
+    }
+
+    /// Copy a PNG image to this clipboard, as `image/png`.
+    ///
+    /// On Windows, it goes as the `PNG` format most programs take,
+    /// but not as a bitmap, which some older ones only take.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.copy_image_png(&std::fs::read("screenshot.png")?)?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `png` isn't a PNG, with an [`io::ErrorKind::InvalidInput`] error, the provider
+    /// can't take images, with [`Error::Unsupported`], or copying fails.
+    pub fn copy_image_png(&self, png: &[u8]) -> Result<()> {
+        if !png.starts_with(PNG) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a PNG image").into());
+        }
+        self.backend.copy_mime("image/png", png)
+    }
+
+    /// Paste the PNG image on this clipboard, or [`None`] if there isn't one.
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't give images, with [`Error::Unsupported`], or pasting fails.
+    pub fn paste_image_png(&self) -> Result<Option<Vec<u8>>> {
+        match self.backend.paste_mime("image/png") {
+            Ok(png) => Ok(Some(png)),
+            Err(Error::FormatUnavailable { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copy files to this clipboard, for a file manager to paste, as `text/uri-list`.
+    ///
+    /// Relative paths are taken from the current directory. The native X11 clipboard also
+    /// offers them the way nautilus wants them, and the native Windows one as explorer does.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// clipp::Clipboard::detect()?.copy_files(&[Path::new("/home/me/notes.txt")])?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't take files, with [`Error::Unsupported`], or copying fails.
+    pub fn copy_files(&self, paths: &[&Path]) -> Result<()> {
+        let paths = paths
+            .iter()
+            .map(std::path::absolute)
+            .collect::<io::Result<Vec<_>>>()?;
+        self.backend
+            .copy_files(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>())
+    }
+
+    /// Paste the files on this clipboard, e.g. what a file manager copied,
+    /// or [`None`] if there aren't any.
+    ///
+    /// Only local files are given, links and the like are left out.
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't give files, with [`Error::Unsupported`], or pasting fails.
+    pub fn paste_files(&self) -> Result<Option<Vec<PathBuf>>> {
+        match self.backend.paste_files() {
+            Ok(paths) if paths.is_empty() => Ok(None),
+            Ok(paths) => Ok(Some(paths)),
+            Err(Error::FormatUnavailable { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The MIME types what is on this clipboard is offered as, for [`paste_mime`](Self::paste_mime).
+    /// An empty clipboard offers none.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// if clip.targets()?.iter().any(|t| t == "image/png") {
+    ///     std::fs::write("pasted.png", clip.paste_mime("image/png")?)?;
+    /// }
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't tell, with [`Error::Unsupported`], or asking fails.
+    pub fn targets(&self) -> Result<Vec<String>> {
+        self.backend.targets()
+    }
+
+    /// Something that changes whenever what is on this clipboard does: ask again later,
+    /// and if the two differ, something has been copied.
+    ///
+    /// This is cheap where the system counts what is copied, and costs a whole paste
+    /// elsewhere, see [`ChangeToken`](crate::ChangeToken).
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// let seen = clip.change_token()?;
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// if clip.change_token()? != seen {
+    ///     println!("copied: {}", clip.paste()?);
+    /// }
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If pasting, or asking for it, fails.
+    pub fn change_token(&self) -> Result<crate::ChangeToken> {
+        self.backend.change_token()
+    }
+
+    /// Wait for something to be copied to this clipboard, or for it to be cleared, and
+    /// return what it has then, or [`None`] if `timeout` is up first.
+    /// See [`Wait`](crate::Wait) for how it waits.
+    ///
+    /// # Errors
+    ///
+    /// If watching it, or pasting, fails.
+    pub fn wait_for_change(&self, timeout: Option<Duration>) -> Result<Option<String>> {
+        let wait = crate::Wait::new();
+        match timeout {
+            Some(timeout) => wait.timeout(timeout).changed(self),
+            None => wait.changed(self),
+        }
+    }
+
+    /// [`wait_for_change`](Self::wait_for_change), waiting as `wait` says, and returning
+    /// [`None`] if it is given up on first.
+    ///
+    /// # Errors
+    ///
+    /// If watching it, or pasting, fails.
+    pub fn wait_for_change_with(&self, wait: &crate::Wait) -> Result<Option<String>> {
+        wait.changed(self)
+    }
+
+    /// Each new thing copied to this clipboard from now on, looking every `interval` where
+    /// it can't say when it changes. See [`Watch`](crate::Watch).
+    #[must_use]
+    pub fn watch(&self, interval: Duration) -> crate::Watch {
+        crate::Watch::new(self.clone(), interval)
+    }
+
+    /// Call `on_change` with each new thing copied to this clipboard from now on, on a
+    /// thread of its own, until the handle is stopped or dropped. Copies in quick succession
+    /// are passed on as one, see [`Watch::spawn`](crate::Watch::spawn) for more say.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// let watcher = clip.spawn_watcher(|copied| println!("copied: {copied}"));
+    /// // ... until the app quits
+    /// watcher.stop();
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    pub fn spawn_watcher(
+        &self,
+        on_change: impl Fn(String) + Send + 'static,
+    ) -> crate::WatcherHandle {
+        self.watch(crate::Wait::INTERVAL)
+            .coalesce(crate::Watch::COALESCE)
+            .spawn(on_change)
+    }
+
+    /// Paste whatever is on this clipboard, as the richest of what it is offered as
+    /// that can be made sense of. In order, that is:
+    ///
+    /// 1. [files](Self::paste_files), as a file manager copies them
+    /// 2. a [PNG image](Self::paste_image_png)
+    /// 3. [HTML](Self::paste_html), with the text it came with
+    /// 4. text
+    /// 5. the first of whatever else it is offered as
+    ///
+    /// Where the provider can't tell what is offered, and with nothing on it at all,
+    /// it is pasted as text. Text that isn't UTF-8 is given as it is, as `text/plain`.
+    ///
+    /// ```no_run
+    /// use clipp::ClipboardContent;
+    /// match clipp::Clipboard::detect()?.paste_any()? {
+    ///     ClipboardContent::Text(text) => println!("{text}"),
+    ///     ClipboardContent::Files(files) => println!("{} files", files.len()),
+    ///     other => println!("{other:?}"),
+    /// }
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    pub fn paste_any(&self) -> Result<ClipboardContent> {
+        let targets = match self.targets() {
+            Err(Error::Unsupported { .. }) => vec![],
+            r => r?,
+        };
+        let has = |mime: &str| targets.iter().any(|t| t == mime);
+        if has("text/uri-list") {
+            if let Some(files) = self.paste_files()? {
+                return Ok(ClipboardContent::Files(files));
+            }
+        }
+        if has("image/png") {
+            if let Some(png) = self.paste_image_png()? {
+                return Ok(ClipboardContent::ImagePng(png));
+            }
+        }
+        let text = targets.is_empty() || targets.iter().any(|t| providers::is_text(t));
+        if has("text/html") {
+            if let Some(html) = self.paste_html()? {
+                let alt = match text.then(|| self.paste()) {
+                    Some(Err(Error::InvalidUtf8)) | None => None,
+                    alt => alt.transpose()?,
+                };
+                return Ok(ClipboardContent::Html { html, alt });
+            }
+        }
+        match targets.first().filter(|_| !text) {
+            Some(mime) => Ok(ClipboardContent::Other {
+                data: self.paste_mime(mime)?,
+                mime: mime.clone(),
+            }),
+            None => match self.paste() {
+                Err(Error::InvalidUtf8) => Ok(ClipboardContent::Other {
+                    mime: "text/plain".into(),
+                    data: self.paste_bytes()?,
+                }),
+                r => r.map(ClipboardContent::Text),
+            },
+        }
+    }
+
+    /// Paste text from this clipboard.
+    ///
+    /// An empty clipboard pastes as an empty string.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    pub fn paste(&self) -> Result<String> {
+        self.backend.paste()
+    }
+
+    /// Write what is on this clipboard to `w`, as it comes where the provider can give it that
+    /// way, returning how many bytes were written. Through a helper program, it needn't be UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails, or writing does, with [`Error::Write`].
+    pub fn paste_to(&self, w: &mut dyn Write) -> Result<u64> {
+        /// Counts what it writes, and keeps hold of the error it failed to write with.
+        struct Sink<'a>(&'a mut dyn Write, u64, Option<io::Error>);
+        impl Write for Sink<'_> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                match self.0.write(buf) {
+                    Ok(n) => {
+                        self.1 += n as u64;
+                        Ok(n)
+                    }
+                    Err(e) => {
+                        let kind = e.kind();
+                        if kind != io::ErrorKind::Interrupted {
+                            self.2 = Some(e);
+                        }
+                        Err(kind.into())
+                    }
+                }
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+        let mut sink = Sink(w, 0, None);
+        let pasted = self.backend.paste_to(&mut sink);
+        match sink.2 {
+            Some(e) => Err(Error::Write(e)),
+            None => pasted.map(|()| sink.1),
+        }
+    }
+
+    /// Paste the bytes on this clipboard, as they are where it goes through a helper program
+    /// or a file. Otherwise, they are the text [`paste`](Self::paste) gives.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    pub fn paste_bytes(&self) -> Result<Vec<u8>> {
+        let mut v = vec![];
+        self.paste_to(&mut v)?;
+        Ok(v)
+    }
+
+    /// Copy text to this clipboard, giving up with [`Error::TimedOut`] after `timeout`.
+    ///
+    /// A helper program still running then is killed, and reaped before this returns, so
+    /// nothing is copied once it has given up. A native provider can't be stopped midway:
+    /// what it does is let finish in the background, and thrown away.
+    ///
+    /// # Errors
+    ///
+    /// If copying fails, or takes too long.
+    pub fn copy_with_timeout(&self, text: &str, timeout: Duration) -> Result<()> {
+        let (backend, text) = (Arc::clone(&self.backend), text.to_owned());
+        within(timeout, "copying", move || backend.copy(&text))
+    }
+
+    /// Paste text from this clipboard, giving up with [`Error::TimedOut`] after `timeout`.
+    /// See [`copy_with_timeout`](Self::copy_with_timeout).
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails, or takes too long.
+    pub fn paste_with_timeout(&self, timeout: Duration) -> Result<String> {
+        let backend = Arc::clone(&self.backend);
+        within(timeout, "pasting", move || backend.paste())
+    }
+
+    /// Paste text from this clipboard, or [`None`] if it is empty.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails. An empty clipboard is not an error.
+    pub fn paste_opt(&self) -> Result<Option<String>> {
+        self.paste().map(|s| (!s.is_empty()).then_some(s))
+    }
+
+    /// Copy `text` to this clipboard, returning the text it replaced, which is empty
+    /// if there was none.
+    ///
+    /// This pastes and then copies, as quickly as it can, but whatever is copied in between
+    /// is lost: no clipboard can be swapped in one go.
+    ///
+    /// # Errors
+    ///
+    /// If pasting or copying fails. If pasting does, nothing is copied.
+    pub fn swap(&self, text: &str) -> Result<String> {
+        let was = self.paste()?;
+        self.copy(text).map(|()| was)
+    }
+
+    /// Run what is on this clipboard through `f`, copying what it returns in its place,
+    /// and returning that.
+    ///
+    /// An empty clipboard is passed as an empty string. If `f` gives back what it was
+    /// given, nothing is copied, for a clipboard manager not to see a copy that changed
+    /// nothing.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.transform(|s| s.trim().to_owned())?;
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If pasting or copying fails.
+    pub fn transform(&self, f: impl FnOnce(String) -> String) -> Result<String> {
+        self.try_transform(|s| Ok::<_, Error>(f(s)))
+    }
+
+    /// [`transform`](Self::transform), with an `f` that can fail, copying nothing if it does.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// clip.try_transform(|s| {
+    ///     let n: i64 = s.trim().parse()?;
+    ///     Ok::<_, Box<dyn std::error::Error>>((n * 2).to_string())
+    /// })?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If pasting or copying fails, or `f` does.
+    pub fn try_transform<E: From<Error>>(
+        &self,
+        f: impl FnOnce(String) -> Result<String, E>,
+    ) -> Result<String, E> {
+        let was = self.paste_opt()?.unwrap_or_default();
+        let now = f(was.clone())?;
+        if now != was {
+            self.copy(&now)?;
+        }
+        Ok(now)
+    }
+
+    /// Add `text` to the end of what is on this clipboard. See
+    /// [`append_with_separator`](Self::append_with_separator).
+    ///
+    /// # Errors
+    ///
+    /// If pasting or copying fails.
+    pub fn append(&self, text: &str) -> Result<()> {
+        self.append_with_separator(text, "")
+    }
+
+    /// Add `text` to the end of what is on this clipboard, with `sep` between them
+    /// if there was anything there.
+    ///
+    /// What isn't text, e.g. an image, can't be added to, so it is replaced.
+    ///
+    /// ```no_run
+    /// let clip = clipp::Clipboard::detect()?;
+    /// for line in ["first", "second"] {
+    ///     clip.append_with_separator(line, "\n")?;
+    /// }
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If pasting or copying fails.
+    pub fn append_with_separator(&self, text: &str, sep: &str) -> Result<()> {
+        let mut all = match self.paste() {
+            Ok(was) => was,
+            Err(Error::InvalidUtf8) => String::new(),
+            Err(e) => return Err(e),
+        };
+        if !all.is_empty() {
+            all.push_str(sep);
+        }
+        all.push_str(text);
+        self.copy(&all)
+    }
+
+    /// Empty this clipboard.
+    ///
+    /// # Errors
+    ///
+    /// If clearing fails.
+    pub fn clear(&self) -> Result<()> {
+        self.backend.clear()
+    }
+
+    /// Keep what was copied on this clipboard once this process has exited.
+    /// See [`persist`](crate::persist).
+    ///
+    /// # Errors
+    ///
+    /// If handing it over fails.
+    pub fn persist(&self) -> Result<()> {
+        self.backend.persist()
+    }
+}
+
+/// `mime`, if it is a MIME type. It ends up in a helper's arguments, so there can be nothing in it
+/// that could be taken for anything else.
+fn checked(mime: &str) -> Result<&str> {
+    let valid = mime
+        .split_once('/')
+        .is_some_and(|(ty, sub)| !ty.is_empty() && !sub.is_empty() && !mime.starts_with('-'))
+        && mime.bytes().all(|b| b.is_ascii_graphic());
+    if !valid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{mime:?} is not a MIME type"),
+        )
+        .into());
+    }
+    Ok(mime)
+}
+
+/// What every PNG starts with.
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// HTML, in whatever charset it came in.
+fn decoded(html: &[u8]) -> Result<String> {
+    let utf16 = |b: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units = b.chunks_exact(2).map(|c| unit([c[0], c[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|_| Error::InvalidUtf8)
+    };
+    match html {
+        [0xff, 0xfe, rest @ ..] => return utf16(rest, u16::from_le_bytes),
+        [0xfe, 0xff, rest @ ..] => return utf16(rest, u16::from_be_bytes),
+        [0xef, 0xbb, 0xbf, rest @ ..] => return decoded(rest),
+        // `<` as UTF-16, without a byte order mark
+        [b'<', 0, ..] => return utf16(html, u16::from_le_bytes),
+        [0, b'<', ..] => return utf16(html, u16::from_be_bytes),
+        _ => {}
+    }
+    if let Ok(s) = std::str::from_utf8(html) {
+        return Ok(s.to_owned());
+    }
+    // whatever it says it is, somewhere near the start
+    let head = String::from_utf8_lossy(&html[..html.len().min(1024)]).to_ascii_lowercase();
+    let charset = head.split_once("charset=").map_or("", |(_, rest)| {
+        let rest = rest.trim_start_matches(['"', '\'']);
+        let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || "-_".contains(c)));
+        &rest[..end.unwrap_or(rest.len())]
+    });
+    match charset {
+        "iso-8859-1" | "latin1" | "latin-1" => Ok(html.iter().map(|&b| char::from(b)).collect()),
+        "windows-1252" | "cp1252" => Ok(html.iter().map(|&b| cp1252(b)).collect()),
+        _ => Err(Error::InvalidUtf8),
+    }
+}
+
+/// A byte of windows-1252, which is latin-1 but for what it puts in 0x80 to 0x9f.
+fn cp1252(b: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+    match b {
+        0x80..=0x9f => HIGH[usize::from(b - 0x80)],
+        b => char::from(b),
+    }
+}
+
+/// Does `f` on a thread of its own, waiting for it for no longer than `timeout`.
+fn within<T: Send + 'static>(
+    timeout: Duration,
+    what: &str,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let (tx, rx) = mpsc::channel();
+    let cancel = Arc::new(crate::providers::Cancel::default());
+    let theirs = Arc::clone(&cancel);
+    std::thread::spawn(move || {
+        crate::providers::CANCEL.set(Some(theirs));
+        _ = tx.send(f());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(r) => r,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancel.cancel();
+            Err(Error::TimedOut {
+                what: format!("{what} after {timeout:?}"),
+            })
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(io::Error::other(format!("{what} panicked")).into())
+        }
+    }
+}
+
+impl From<Box<dyn Backend>> for Clipboard {
+    fn from(backend: Box<dyn Backend>) -> Self {
+        Self::from(Arc::from(backend))
+    }
+}
+
+impl From<Arc<dyn Backend>> for Clipboard {
+    fn from(backend: Arc<dyn Backend>) -> Self {
+        Self {
+            provider: Provider::Custom,
+            backend,
+        }
+    }
+}
+
+#[cfg(feature = "osc52")]
+impl From<crate::Osc52> for Clipboard {
+    fn from(osc52: crate::Osc52) -> Self {
+        Self {
+            provider: Provider::Osc52,
+            backend: Arc::new(osc52),
+        }
+    }
+}
+
+/// What was on a clipboard, as [`Clipboard::paste_any`] found it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClipboardContent {
+    Text(String),
+    /// With the text that was offered alongside it, if there was any.
+    Html {
+        html: String,
+        alt: Option<String>,
+    },
+    ImagePng(Vec<u8>),
+    Files(Vec<PathBuf>),
+    /// As neither of the others, or as text that isn't UTF-8.
+    Other {
+        mime: String,
+        data: Vec<u8>,
+    },
+}
+
+impl std::fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clipboard")
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A clipboard that lives in a string, for this process only.
+#[derive(Default)]
+pub(crate) struct Scratch(pub std::sync::Mutex<String>);
+
+impl Backend for Scratch {
+    fn copy(&self, text: &str) -> Result<()> {
+        text.clone_into(&mut self.0.lock().unwrap());
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+}
+
+#[test]
+fn swapped() {
+    let clip = Clipboard::memory();
+    assert_eq!(clip.swap("first").unwrap(), "");
+    assert_eq!(clip.swap("second").unwrap(), "first");
+    assert_eq!(clip.paste().unwrap(), "second");
+}
+
+#[test]
+fn changes() {
+    let clip = Clipboard::memory();
+    let empty = clip.change_token().unwrap();
+    assert_eq!(clip.change_token().unwrap(), empty);
+    clip.copy("a").unwrap();
+    let a = clip.change_token().unwrap();
+    assert_ne!(a, empty);
+    assert_eq!(clip.change_token().unwrap(), a);
+    clip.copy("b").unwrap();
+    assert_ne!(clip.change_token().unwrap(), a);
+    clip.clear().unwrap();
+    assert_eq!(clip.change_token().unwrap(), empty);
+}
+
+#[test]
+fn transformed() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    struct Counted(Scratch, Arc<AtomicUsize>);
+    impl Backend for Counted {
+        fn copy(&self, text: &str) -> Result<()> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.copy(text)
+        }
+
+        fn paste(&self) -> Result<String> {
+            self.0.paste()
+        }
+    }
+    let counted = Arc::new(AtomicUsize::new(0));
+    let clip = Clipboard::new(Counted(Scratch::default(), Arc::clone(&counted)));
+    let copies = || counted.load(Ordering::Relaxed);
+    assert_eq!(clip.transform(|s| s + "a").unwrap(), "a");
+    assert_eq!(clip.transform(|s| s.to_uppercase()).unwrap(), "A");
+    assert_eq!(copies(), 2);
+    // the same again isn't copied
+    assert_eq!(clip.transform(|s| s.to_uppercase()).unwrap(), "A");
+    assert_eq!(copies(), 2);
+    // nor is anything, when it fails
+    let failed = clip.try_transform(|_| Err(Error::InvalidUtf8));
+    assert!(matches!(failed, Err(Error::InvalidUtf8)));
+    assert_eq!(copies(), 2);
+    assert_eq!(clip.paste().unwrap(), "A");
+}
+
+#[test]
+fn formatted() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    /// Megabytes, a line at a time, or failing halfway.
+    struct Lines {
+        fail: bool,
+    }
+    impl fmt::Display for Lines {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for n in 0..200_000 {
+                if self.fail && n == 100_000 {
+                    return Err(fmt::Error);
+                }
+                writeln!(f, "line {n} of many")?;
+            }
+            Ok(())
+        }
+    }
+    // keeps the most it was given in one read
+    struct Reading(Scratch, AtomicUsize);
+    impl Backend for Reading {
+        fn copy(&self, text: &str) -> Result<()> {
+            self.0.copy(text)
+        }
+
+        fn copy_from(&self, r: &mut dyn io::Read) -> Result<()> {
+            let (mut buf, mut all) = (vec![0; 1 << 20], vec![]);
+            loop {
+                match r.read(&mut buf).map_err(Error::Read)? {
+                    0 => break,
+                    n => {
+                        self.1.fetch_max(n, Ordering::Relaxed);
+                        all.extend_from_slice(&buf[..n]);
+                    }
+                }
+            }
+            self.0.copy(&String::from_utf8(all).unwrap())
+        }
+
+        fn paste(&self) -> Result<String> {
+            self.0.paste()
+        }
+    }
+    let lines = |fail| Lines { fail };
+    let expected = lines(false).to_string();
+    let reading = Arc::new(Reading(Scratch::default(), AtomicUsize::new(0)));
+    let clip = Clipboard::from(reading.clone() as Arc<dyn Backend>);
+    clip.copy_fmt(format_args!("{}", lines(false))).unwrap();
+    assert_eq!(clip.paste().unwrap(), expected);
+    // never more than a chunk of it at once
+    assert!(reading.1.load(Ordering::Relaxed) <= 64 * 1024);
+    // nothing copied, for a failure
+    clip.copy("before").unwrap();
+    let e = clip.copy_fmt(format_args!("{}", lines(true))).unwrap_err();
+    assert!(matches!(e, Error::Read(_)), "{e}");
+    assert_eq!(clip.paste().unwrap(), "before");
+    clip.copy_fmt(format_args!("as it is")).unwrap();
+    assert_eq!(clip.paste().unwrap(), "as it is");
+    // and to a file, as it comes
+    #[cfg(feature = "file")]
+    {
+        let path = std::env::temp_dir().join(format!("clipp-formatted-{}", std::process::id()));
+        let file = Clipboard::new(crate::file::FileClipboard(path.clone()));
+        file.copy_fmt(format_args!("{}", lines(false))).unwrap();
+        assert_eq!(file.paste().unwrap(), expected);
+        _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn appended() {
+    let clip = Clipboard::memory();
+    clip.append("a").unwrap();
+    clip.append("b").unwrap();
+    assert_eq!(clip.paste().unwrap(), "ab");
+    clip.clear().unwrap();
+    for line in ["one", "two", "three"] {
+        clip.append_with_separator(line, "\n").unwrap();
+    }
+    assert_eq!(clip.paste().unwrap(), "one\ntwo\nthree");
+    // an image is replaced, whether it pastes as no text at all or as bytes
+    let clip = Clipboard::new(Offers::default());
+    let png = providers::png(1, 1);
+    for mime in ["image/png", "text/plain"] {
+        clip.copy_multi(&[(mime, &png)]).unwrap();
+        clip.append_with_separator("text", "\n").unwrap();
+        assert_eq!(clip.paste().unwrap(), "text");
+    }
+}
+
+#[test]
+fn charsets() {
+    let html = "<p>héllo <a href=\"https://example.com/\">wörld</a> 👋</p>";
+    let le: Vec<u8> = html.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let be: Vec<u8> = html.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    for bytes in [
+        html.as_bytes().to_vec(),
+        [&[0xef, 0xbb, 0xbf], html.as_bytes()].concat(),
+        [&[0xff, 0xfe], &le[..]].concat(),
+        [&[0xfe, 0xff], &be[..]].concat(),
+        le,
+        be,
+    ] {
+        assert_eq!(decoded(&bytes).unwrap(), html);
+    }
+    let latin1 = b"<meta charset=\"ISO-8859-1\"><p>h\xe9llo</p>";
+    assert_eq!(
+        decoded(latin1).unwrap(),
+        "<meta charset=\"ISO-8859-1\"><p>héllo</p>"
+    );
+    let cp1252 =
+        b"<meta http-equiv=content-type content='text/html; charset=windows-1252'>\x93hi\x94 \x80";
+    assert!(decoded(cp1252).unwrap().ends_with("“hi” €"));
+    assert!(matches!(decoded(b"<p>\xff</p>"), Err(Error::InvalidUtf8)));
+}
+
+#[test]
+fn no_html() {
+    struct Plain;
+    impl Backend for Plain {
+        fn copy(&self, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            Ok("plain".into())
+        }
+
+        fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+            Err(Error::FormatUnavailable { mime: mime.into() })
+        }
+    }
+    assert_eq!(Clipboard::new(Plain).paste_html().unwrap(), None);
+    // not being able to tell isn't the same as there being none
+    assert!(matches!(
+        Clipboard::new(Scratch::default()).paste_html(),
+        Err(Error::Unsupported { .. })
+    ));
+}
+
+/// Offers whatever it was last given, as a clipboard that takes several types would.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct Offers(std::sync::Mutex<Vec<(String, Vec<u8>)>>);
+#[cfg(test)]
+impl Backend for Offers {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.copy_multi(&[("text/plain", text.as_bytes())])
+    }
+
+    fn paste(&self) -> Result<String> {
+        String::from_utf8(self.paste_mime("text/plain").unwrap_or_default())
+            .map_err(|_| Error::InvalidUtf8)
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        Ok(w.write_all(&self.paste_mime("text/plain").unwrap_or_default())?)
+    }
+
+    fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        *self.0.lock().unwrap() = items
+            .iter()
+            .map(|&(mime, data)| (mime.into(), data.to_vec()))
+            .collect();
+        Ok(())
+    }
+
+    fn copies_multi(&self) -> bool {
+        true
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        let offered = self.0.lock().unwrap();
+        let found = offered.iter().find(|(m, _)| m == mime);
+        found
+            .map(|(_, data)| data.clone())
+            .ok_or_else(|| Error::FormatUnavailable { mime: mime.into() })
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(m, _)| m.clone())
+            .collect())
+    }
+}
+
+#[test]
+fn any() {
+    let clip = Clipboard::new(Offers::default());
+    let any = |items: &[(&str, &[u8])]| {
+        clip.copy_multi(items).unwrap();
+        clip.paste_any().unwrap()
+    };
+    let (uris, png) = (b"file:///tmp/a%20b\r\n", crate::providers::png(1, 1));
+    // as a file manager offers them, with their paths as text
+    assert_eq!(
+        any(&[("text/plain", b"/tmp/a b"), ("text/uri-list", uris)]),
+        ClipboardContent::Files(vec!["/tmp/a b".into()])
+    );
+    // a browser's image, with the html it was in
+    assert_eq!(
+        any(&[("text/html", b"<img src=a.png>"), ("image/png", &png)]),
+        ClipboardContent::ImagePng(png.clone())
+    );
+    assert_eq!(
+        any(&[("text/html", b"<b>hi</b>"), ("text/plain", b"hi")]),
+        ClipboardContent::Html {
+            html: "<b>hi</b>".into(),
+            alt: Some("hi".into())
+        }
+    );
+    assert_eq!(
+        any(&[("text/html", b"<b>hi</b>")]),
+        ClipboardContent::Html {
+            html: "<b>hi</b>".into(),
+            alt: None
+        }
+    );
+    assert_eq!(
+        any(&[("text/plain", b"hi")]),
+        ClipboardContent::Text("hi".into())
+    );
+    assert_eq!(
+        any(&[("application/x-thing", b"\0"), ("image/gif", b"GIF89a")]),
+        ClipboardContent::Other {
+            mime: "application/x-thing".into(),
+            data: b"\0".to_vec()
+        }
+    );
+    assert_eq!(
+        any(&[("text/plain", b"\xff")]),
+        ClipboardContent::Other {
+            mime: "text/plain".into(),
+            data: b"\xff".to_vec()
+        }
+    );
+    // links aren't files, but they're still text
+    assert_eq!(
+        any(&[
+            ("text/uri-list", b"https://example.com/"),
+            ("text/plain", b"x")
+        ]),
+        ClipboardContent::Text("x".into())
+    );
+    clip.clear().unwrap();
+    assert_eq!(
+        clip.paste_any().unwrap(),
+        ClipboardContent::Text(String::new())
+    );
+    // what can't tell what it has pastes as text
+    let clip = Clipboard::new(Scratch::default());
+    clip.copy("plain").unwrap();
+    assert_eq!(
+        clip.paste_any().unwrap(),
+        ClipboardContent::Text("plain".into())
+    );
+}
+
+#[test]
+fn independent() {
+    fn shareable<T: Clone + Send + Sync>() {}
+    shareable::<Clipboard>();
+
+    let (a, b) = (
+        Clipboard::new(Scratch::default()),
+        Clipboard::new(Scratch::default()),
+    );
+    assert_eq!(a.provider(), Provider::Custom);
+    a.copy("a").unwrap();
+    b.copy("b").unwrap();
+    assert_eq!(a.paste().unwrap(), "a");
+    assert_eq!(b.paste().unwrap(), "b");
+    b.clear().unwrap();
+    assert_eq!(a.paste_opt().unwrap().as_deref(), Some("a"));
+    assert_eq!(b.paste_opt().unwrap(), None);
+    let shared = b.clone();
+    std::thread::spawn(move || shared.copy("from elsewhere"))
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(b.paste().unwrap(), "from elsewhere");
+    assert_eq!(a.paste().unwrap(), "a");
+}
+
+#[test]
+fn timeouts() {
+    /// Takes its time about everything.
+    struct Slow(Duration, Scratch);
+    impl Backend for Slow {
+        fn copy(&self, text: &str) -> Result<()> {
+            std::thread::sleep(self.0);
+            self.1.copy(text)
+        }
+
+        fn paste(&self) -> Result<String> {
+            std::thread::sleep(self.0);
+            self.1.paste()
+        }
+    }
+    /// Fails at everything.
+    struct Broken;
+    impl Backend for Broken {
+        fn copy(&self, _: &str) -> Result<()> {
+            Err(Error::Unsupported { what: "copying" })
+        }
+
+        fn paste(&self) -> Result<String> {
+            Err(Error::Unsupported { what: "pasting" })
+        }
+    }
+    let slow = Clipboard::new(Slow(Duration::from_millis(50), Scratch::default()));
+    let e = slow
+        .paste_with_timeout(Duration::from_millis(5))
+        .unwrap_err();
+    assert!(matches!(&e, Error::TimedOut { what } if what.starts_with("pasting")));
+    assert!(slow
+        .copy_with_timeout("late", Duration::from_millis(5))
+        .is_err());
+    // just in time
+    slow.copy_with_timeout("on time", Duration::from_millis(500))
+        .unwrap();
+    assert_eq!(
+        slow.paste_with_timeout(Duration::from_millis(500)).unwrap(),
+        "on time"
+    );
+    // a failure in time is still the failure
+    assert!(matches!(
+        Clipboard::new(Broken).paste_with_timeout(Duration::from_secs(5)),
+        Err(Error::Unsupported { .. })
+    ));
+}
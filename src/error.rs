@@ -0,0 +1,188 @@
+//! what can go wrong
+use std::{fmt, io};
+
+/// Everything that can go wrong when talking to the clipboard.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// None of the supported clipboards are available.
+    NoProvider,
+    /// No provider is available because this is running in a sandbox, which keeps the
+    /// clipboard programs out of sight, e.g. `Flatpak`, `Snap`, or a container's name.
+    Sandboxed {
+        sandbox: String,
+    },
+    /// No provider is available, and neither `DISPLAY` nor `WAYLAND_DISPLAY` is set,
+    /// so there is no desktop whose clipboard could be used.
+    NoDisplay,
+    /// The helper program for the clipboard is not installed.
+    CommandNotFound {
+        command: String,
+    },
+    /// The helper program ran, but exited unsuccessfully.
+    CommandFailed {
+        command: String,
+        /// The exit code, or [`None`] if it was killed by a signal.
+        status: Option<i32>,
+        stderr: String,
+    },
+    /// `CLIPP_PROVIDER` named a provider that does not exist.
+    UnknownProvider {
+        name: String,
+    },
+    /// The clipboard held something that was not valid UTF-8.
+    InvalidUtf8,
+    /// The provider can't do this at all.
+    Unsupported {
+        what: &'static str,
+    },
+    /// Whatever is on the clipboard isn't offered as this MIME type, see
+    /// [`paste_mime`](crate::paste_mime).
+    FormatUnavailable {
+        mime: String,
+    },
+    /// Gave up waiting.
+    TimedOut {
+        what: String,
+    },
+    /// The text was more than the provider will take, e.g. what a terminal puts up with over OSC 52.
+    TooLong {
+        len: usize,
+        max: usize,
+    },
+    /// Copying to the clipboard worked, but copying the same to the
+    /// [primary selection](crate::Selection::Primary) didn't, see [`copy_both`](crate::copy_both).
+    PrimaryFailed(Box<Error>),
+    /// Reading what was to be copied failed, see [`copy_from`](crate::copy_from).
+    Read(io::Error),
+    /// Writing what was pasted failed, see [`paste_to`](crate::paste_to).
+    Write(io::Error),
+    Io(io::Error),
+}
+
+/// [`Result`](std::result::Result), with clipp's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoProvider => f.write_str("no clipboard provider available"),
+            Self::Sandboxed { sandbox } => write!(
+                f,
+                "running inside {sandbox}, where clipboard helpers are not visible"
+            ),
+            Self::NoDisplay => {
+                f.write_str("no clipboard provider available: no display server detected")
+            }
+            Self::CommandNotFound { command } => write!(f, "{command} could not be found"),
+            Self::CommandFailed {
+                command,
+                status,
+                stderr,
+            } => {
+                write!(f, "{command} was not successful (")?;
+                match status {
+                    Some(code) => write!(f, "exit status: {code})")?,
+                    None => f.write_str("killed by a signal)")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {stderr}"),
+                }
+            }
+            Self::UnknownProvider { name } => write!(
+                f,
+                "unknown clipboard provider {name:?} (expected one of {})",
+                crate::Provider::ALL
+                    .iter()
+                    .map(|p| p.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::InvalidUtf8 => f.write_str("clipboard contents were not valid UTF-8"),
+            Self::Unsupported { what } => write!(f, "{what} is not supported"),
+            Self::FormatUnavailable { mime } => write!(f, "the clipboard has nothing as {mime}"),
+            Self::TimedOut { what } => write!(f, "timed out {what}"),
+            Self::TooLong { len, max } => {
+                write!(f, "too much to copy ({len} bytes, at most {max})")
+            }
+            Self::PrimaryFailed(e) => write!(
+                f,
+                "copied to the clipboard, but not the primary selection: {e}"
+            ),
+            Self::Read(e) => write!(f, "could not read what was to be copied: {e}"),
+            Self::Write(e) => write!(f, "could not write what was pasted: {e}"),
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) | Self::Read(e) | Self::Write(e) => Some(e),
+            Self::PrimaryFailed(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        let kind = match e {
+            Error::Io(e) | Error::Read(e) | Error::Write(e) => return e,
+            Error::CommandNotFound { .. } | Error::FormatUnavailable { .. } => {
+                io::ErrorKind::NotFound
+            }
+            Error::InvalidUtf8 => io::ErrorKind::InvalidData,
+            Error::UnknownProvider { .. } | Error::TooLong { .. } => io::ErrorKind::InvalidInput,
+            Error::Unsupported { .. } => io::ErrorKind::Unsupported,
+            Error::TimedOut { .. } => io::ErrorKind::TimedOut,
+            Error::NoProvider
+            | Error::Sandboxed { .. }
+            | Error::NoDisplay
+            | Error::CommandFailed { .. }
+            | Error::PrimaryFailed(_) => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+#[test]
+fn into_io() {
+    let e = io::Error::from(Error::CommandNotFound {
+        command: "xclip".into(),
+    });
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    assert_eq!(e.to_string(), "xclip could not be found");
+    assert!(matches!(
+        e.into_inner().unwrap().downcast::<Error>().as_deref(),
+        Ok(Error::CommandNotFound { .. })
+    ));
+    let e = io::Error::from(Error::Io(io::ErrorKind::BrokenPipe.into()));
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn partly() {
+    use std::error::Error as _;
+    let e = Error::PrimaryFailed(Box::new(Error::CommandFailed {
+        command: "xsel".into(),
+        status: Some(1),
+        stderr: String::new(),
+    }));
+    assert_eq!(
+        e.to_string(),
+        "copied to the clipboard, but not the primary selection: xsel was not successful (exit status: 1)"
+    );
+    assert!(matches!(
+        e.source().unwrap().downcast_ref(),
+        Some(Error::CommandFailed { .. })
+    ));
+}
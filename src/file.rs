@@ -0,0 +1,190 @@
+//! a file standing in for the clipboard, when there is nothing else
+use crate::{providers::Probe, Backend, Error, Result};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Keeps the clipboard in a file of this user's, so that what one process copies
+/// another can paste, even with no desktop or terminal to hold it.
+pub(crate) struct FileClipboard(pub(crate) PathBuf);
+
+impl FileClipboard {
+    /// Where it is kept: `CLIPP_FILE`, or `clipp` in the runtime directory,
+    /// or failing that in the cache directory. Without any of them, nowhere.
+    pub(crate) fn path(p: &impl Probe) -> Option<PathBuf> {
+        let set = |key| p.var(key).filter(|v| !v.is_empty()).map(PathBuf::from);
+        set("CLIPP_FILE").or_else(|| {
+            set("XDG_RUNTIME_DIR")
+                .or_else(|| set("XDG_CACHE_HOME"))
+                .map(|dir| dir.join("clipp"))
+        })
+    }
+}
+
+impl Backend for FileClipboard {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.copy_from(&mut text.as_bytes())
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        /// Temporary files written so far, for each to have a name of its own.
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        if let Some(dir) = self.0.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        // written alongside and moved over it, so nobody pastes half of it
+        let mut tmp = self.0.clone().into_os_string();
+        tmp.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut o = fs::OpenOptions::new();
+        o.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut o, 0o600);
+        let written = o.open(&tmp).map_err(Error::Io).and_then(|mut f| {
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                match r.read(&mut buf) {
+                    Ok(0) => break Ok(fs::rename(&tmp, &self.0)?),
+                    Ok(n) => f.write_all(&buf[..n])?,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => break Err(Error::Read(e)),
+                }
+            }
+        });
+        if written.is_err() {
+            _ = fs::remove_file(&tmp);
+        }
+        written
+    }
+
+    fn paste(&self) -> Result<String> {
+        match fs::read_to_string(&self.0) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(Error::InvalidUtf8),
+            r => Ok(r?),
+        }
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        match fs::File::open(&self.0) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            f => Ok(io::copy(&mut f?, w).map(drop)?),
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.0) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => Ok(r?),
+        }
+    }
+}
+
+#[test]
+fn paths() {
+    let mut env = crate::providers::Fake::default();
+    assert_eq!(FileClipboard::path(&env), None);
+    // not just anywhere it could be
+    env.vars.insert("HOME", "/home/me".into());
+    assert_eq!(FileClipboard::path(&env), None);
+    env.vars.insert("XDG_CACHE_HOME", "/cache".into());
+    assert_eq!(
+        FileClipboard::path(&env).unwrap(),
+        PathBuf::from("/cache/clipp")
+    );
+    env.vars.insert("XDG_RUNTIME_DIR", "/run/user/1000".into());
+    assert_eq!(
+        FileClipboard::path(&env).unwrap(),
+        PathBuf::from("/run/user/1000/clipp")
+    );
+    env.vars.insert("CLIPP_FILE", "/tmp/mine".into());
+    assert_eq!(
+        FileClipboard::path(&env).unwrap(),
+        PathBuf::from("/tmp/mine")
+    );
+}
+
+#[test]
+fn round_trip() {
+    let dir = std::env::temp_dir().join(format!("clipp-file-{}", std::process::id()));
+    let f = FileClipboard(dir.join("nested").join("clipp"));
+    assert_eq!(f.paste().unwrap(), "");
+    f.clear().unwrap();
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語", ""] {
+        f.copy(text).unwrap();
+        assert_eq!(f.paste().unwrap(), text);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&f.0).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    // nothing is left lying around
+    assert_eq!(fs::read_dir(dir.join("nested")).unwrap().count(), 1);
+    f.clear().unwrap();
+    assert_eq!(f.paste().unwrap(), "");
+    fs::write(&f.0, b"\xff").unwrap();
+    assert!(matches!(f.paste(), Err(Error::InvalidUtf8)));
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn at_once() {
+    let dir = std::env::temp_dir().join(format!("clipp-file-at-once-{}", std::process::id()));
+    let f = FileClipboard(dir.join("clipp"));
+    let texts = (0..8)
+        .map(|i| i.to_string().repeat(1 << 20))
+        .collect::<Vec<_>>();
+    std::thread::scope(|s| {
+        for text in &texts {
+            s.spawn(|| {
+                for _ in 0..4 {
+                    f.copy(text).unwrap();
+                }
+            });
+        }
+    });
+    // one of them, whole
+    assert!(texts.contains(&f.paste().unwrap()));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn bytes() {
+    let path = std::env::temp_dir().join(format!("clipp-file-bytes-{}", std::process::id()));
+    let clip = crate::Clipboard::new(FileClipboard(path.clone()));
+    assert_eq!(clip.paste_bytes().unwrap(), b"");
+    let bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff";
+    clip.copy_bytes(bytes).unwrap();
+    assert_eq!(clip.paste_bytes().unwrap(), bytes);
+    assert!(matches!(clip.paste(), Err(Error::InvalidUtf8)));
+    clip.clear().unwrap();
+}
+
+#[test]
+fn across_processes() {
+    use std::process::Command;
+    // copying from a child, which has exited by the time its parent pastes
+    if let Some(path) = std::env::var_os("CLIPP_FILE_CHILD") {
+        FileClipboard(path.into()).copy("from the child").unwrap();
+        return;
+    }
+    let path = std::env::temp_dir().join(format!("clipp-file-child-{}", std::process::id()));
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["across_processes", "--quiet"])
+        .env("CLIPP_FILE_CHILD", &path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let f = FileClipboard(path);
+    assert_eq!(f.paste().unwrap(), "from the child");
+    f.clear().unwrap();
+}
@@ -0,0 +1,315 @@
+//! what was copied before, from `cliphist` or `clipman`
+use crate::{
+    providers::{output, real, Eat, Probe, Runner, System},
+    Error, Result,
+};
+use std::{io, process::Command, sync::Arc};
+
+/// Previews of what is in the clipboard history, newest first.
+///
+/// These come from `cliphist list`, which shortens long entries, or `clipman show-history`,
+/// which doesn't. [`history_get`] has the whole of each.
+///
+/// ```no_run
+/// for (i, preview) in clipp::history_list()?.iter().enumerate() {
+///     println!("{i}: {preview}");
+/// }
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If neither `cliphist` nor `clipman` is installed, or listing fails.
+pub fn history_list() -> Result<Vec<String>> {
+    History::new(real(), &System)?
+        .list()
+        .map(|l| l.into_iter().map(|e| e.preview).collect())
+}
+
+/// The entry at `index` in [`history_list`], in full.
+///
+/// The live clipboard is left alone, copy it back to put it there.
+///
+/// # Errors
+///
+/// If neither `cliphist` nor `clipman` is installed, there is no such entry, or it isn't text.
+pub fn history_get(index: usize) -> Result<String> {
+    History::new(real(), &System)?.get(index)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tool {
+    Cliphist,
+    Clipman,
+}
+
+/// The history kept by whichever of them is installed.
+struct History {
+    tool: Tool,
+    run: Arc<dyn Runner>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// What `cliphist decode` is given to find it.
+    id: String,
+    preview: String,
+}
+
+impl History {
+    fn new(run: Arc<dyn Runner>, p: &impl Probe) -> Result<Self> {
+        let tool = match p.which(&["cliphist", "clipman"]) {
+            Some("cliphist") => Tool::Cliphist,
+            Some(_) => Tool::Clipman,
+            None => {
+                return Err(Error::CommandNotFound {
+                    command: "cliphist".into(),
+                })
+            }
+        };
+        Ok(Self { tool, run })
+    }
+
+    fn list(&self) -> Result<Vec<Entry>> {
+        match self.tool {
+            Tool::Cliphist => Ok(entries(
+                &Command::new("cliphist")
+                    .arg("list")
+                    .eat_within(&*self.run, Some(crate::Options::TIMEOUT))?,
+            )),
+            Tool::Clipman => {
+                let out = Command::new("clipman")
+                    .arg("show-history")
+                    .eat_within(&*self.run, Some(crate::Options::TIMEOUT))?;
+                // an empty history prints nothing at all
+                if out.trim().is_empty() {
+                    return Ok(vec![]);
+                }
+                let mut all = strings(&out).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "clipman's history was not a list of strings",
+                    )
+                })?;
+                // it keeps the newest last
+                all.reverse();
+                Ok(all
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, preview)| Entry {
+                        id: i.to_string(),
+                        preview,
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    fn get(&self, index: usize) -> Result<String> {
+        let mut all = self.list()?;
+        if index >= all.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the history has {} entries, not {}", all.len(), index + 1),
+            )
+            .into());
+        }
+        let entry = all.swap_remove(index);
+        match self.tool {
+            // the line it listed, as it would be piped from a picker
+            Tool::Cliphist => {
+                let line = format!("{}\t{}\n", entry.id, entry.preview);
+                let mut c = Command::new("cliphist");
+                let out = output(
+                    &*self.run,
+                    c.arg("decode"),
+                    Some(line.as_bytes()),
+                    true,
+                    None,
+                )?;
+                String::from_utf8(out).map_err(|_| Error::InvalidUtf8)
+            }
+            Tool::Clipman => Ok(entry.preview),
+        }
+    }
+}
+
+/// The entries in what `cliphist list` prints: a line of `<id>\t<preview>` for each.
+/// Only the first tab is cliphist's, any others are in the preview.
+fn entries(list: &str) -> Vec<Entry> {
+    list.lines()
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(id, preview)| Entry {
+            id: id.to_owned(),
+            preview: preview.to_owned(),
+        })
+        .collect()
+}
+
+/// A JSON array of strings, which is all clipman prints.
+fn strings(json: &str) -> Option<Vec<String>> {
+    let mut chars = json.trim().strip_prefix('[')?.strip_suffix(']')?.chars();
+    let mut all = vec![];
+    loop {
+        match chars.by_ref().find(|c| !c.is_whitespace()) {
+            None if all.is_empty() => return Some(all),
+            Some('"') => all.push(string(&mut chars)?),
+            _ => return None,
+        }
+        match chars.by_ref().find(|c| !c.is_whitespace()) {
+            None => return Some(all),
+            Some(',') => {}
+            _ => return None,
+        }
+    }
+}
+
+/// The rest of a JSON string, after its opening quote.
+fn string(chars: &mut std::str::Chars) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => s.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let hi = hex(chars)?;
+                    if (0xd800..0xdc00).contains(&hi) {
+                        // the rest of a surrogate pair
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let lo = hex(chars)?.checked_sub(0xdc00).filter(|&l| l < 0x400)?;
+                        char::from_u32(0x10000 + ((hi - 0xd800) << 10) + lo)?
+                    } else {
+                        char::from_u32(hi)?
+                    }
+                }
+                c @ ('"' | '\\' | '/') => c,
+                _ => return None,
+            }),
+            c => s.push(c),
+        }
+    }
+}
+
+fn hex(chars: &mut std::str::Chars) -> Option<u32> {
+    let digits = chars.take(4).collect::<String>();
+    (digits.len() == 4)
+        .then(|| u32::from_str_radix(&digits, 16).ok())
+        .flatten()
+}
+
+/// What `cliphist list` printed for a text entry with tabs in it, an image, and a long line.
+#[cfg(test)]
+const CAPTURED: &str = "7\tname\tage\tcity\n\
+    6\t[[ binary data 1 KiB png 16x16 ]]\n\
+    5\tlorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut…\n\
+    2\t  indented\n";
+
+#[test]
+fn listed() {
+    let all = entries(CAPTURED);
+    assert_eq!(all.len(), 4);
+    assert_eq!(
+        all[0],
+        Entry {
+            id: "7".into(),
+            preview: "name\tage\tcity".into()
+        }
+    );
+    assert_eq!(all[1].preview, "[[ binary data 1 KiB png 16x16 ]]");
+    assert!(all[2].preview.ends_with('…'));
+    // ids aren't positions
+    assert_eq!(all[3].id, "2");
+    assert_eq!(all[3].preview, "  indented");
+    assert!(entries("").is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn cliphist() {
+    use crate::providers::Canned;
+    let bin = crate::providers::Bin::new("cliphist");
+    bin.add("cliphist", "");
+    let mut env = crate::providers::Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    let r = Arc::new(
+        Canned::default()
+            .answer("cliphist list", 0, CAPTURED.as_bytes(), "")
+            .answer(
+                "cliphist decode",
+                0,
+                b"name\tage\tcity\nada\t36\tlondon\n",
+                "",
+            ),
+    );
+    let h = History::new(r.clone(), &env).unwrap();
+    assert_eq!(h.tool, Tool::Cliphist);
+    assert_eq!(h.get(0).unwrap(), "name\tage\tcity\nada\t36\tlondon\n");
+    assert_eq!(
+        r.ran.lock().unwrap()[1].1.as_deref(),
+        Some(&b"7\tname\tage\tcity\n"[..])
+    );
+    let e = h.get(4).unwrap_err().to_string();
+    assert_eq!(e, "the history has 4 entries, not 5");
+}
+
+#[test]
+#[cfg(unix)]
+fn clipman() {
+    use crate::providers::Canned;
+    let bin = crate::providers::Bin::new("clipman");
+    bin.add("clipman", "");
+    let mut env = crate::providers::Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    let r = Arc::new(Canned::default().answer(
+        "clipman show-history",
+        0,
+        r#"["first","two\nlines","tab\there, \"quoted\" \\ é 👋"]"#.as_bytes(),
+        "",
+    ));
+    let h = History::new(r, &env).unwrap();
+    assert_eq!(h.tool, Tool::Clipman);
+    let all = h.list().unwrap();
+    assert_eq!(all[0].preview, "tab\there, \"quoted\" \\ é 👋");
+    assert_eq!(h.get(1).unwrap(), "two\nlines");
+    assert_eq!(h.get(2).unwrap(), "first");
+    let h = History::new(
+        Arc::new(Canned::default().answer("clipman show-history", 0, b"", "")),
+        &env,
+    )
+    .unwrap();
+    assert!(h.list().unwrap().is_empty());
+}
+
+#[test]
+fn json() {
+    assert_eq!(strings("[]").unwrap(), Vec::<String>::new());
+    assert_eq!(strings(" [ \"a\" , \"b\" ]\n").unwrap(), ["a", "b"]);
+    assert_eq!(strings(r#"["\/\b\f\r"]"#).unwrap(), ["/\u{8}\u{c}\r"]);
+    for bad in [
+        "",
+        "[",
+        "[\"a\",]",
+        "[\"a\" \"b\"]",
+        "[1]",
+        r#"["\ud83d"]"#,
+        r#"["\x"]"#,
+    ] {
+        assert!(strings(bad).is_none(), "{bad}");
+    }
+}
+
+#[test]
+fn missing() {
+    let env = crate::providers::Fake::default();
+    assert!(matches!(
+        History::new(real(), &env),
+        Err(Error::CommandNotFound { command }) if command == "cliphist"
+    ));
+}
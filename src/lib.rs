@@ -1,37 +1,951 @@
 //! simple possibly cross platform clipboard crate
 //!
+//! ```no_run
+//! clipp::copy("wow such clipboard")?;
+//! assert_eq!(clipp::paste()?, "wow such clipboard");
+//! # Ok::<_, clipp::Error>(())
 //! ```
-//! clipp::copy("wow such clipboard");
-//! assert_eq!(clipp::paste(), "wow such clipboard");
-//! ```
+//!
+//! where failing to copy or paste is a bug, [`copy_or_panic`] and [`paste_or_panic`] save
+//! the `?`.
+//!
+//! the clipboard is detected on first use. to skip detection, set `CLIPP_PROVIDER`
+//! to one of `wl`, `xclip`, `xsel`, `klipper`, `gpaste`, `copyq`, `termux`,
+//! `cygwin`, `interop`, `haiku`, `x11`, `kitty`, `osc52`, `file`, `snarf`,
+//! `lemonade` or `piknik`.
 #![warn(clippy::pedantic)]
 #![forbid(unsafe_code)]
+mod clipboard;
+mod error;
+#[cfg(feature = "file")]
+mod file;
+#[cfg(feature = "history-tools")]
+mod history;
+#[cfg(target_os = "macos")]
+pub mod macos;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod options;
+#[cfg(feature = "osc52")]
+mod osc52;
 mod providers;
+mod saved;
+mod secret;
+mod uri;
+mod watch;
+mod wipe;
+#[cfg(feature = "x11-native")]
+mod x11;
+
+// the README's example is compiled with the doctests, to keep it up to date
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct Readme;
+
+pub use clipboard::{Clipboard, ClipboardContent};
+pub use error::{Error, Result};
+#[cfg(feature = "history-tools")]
+pub use history::{history_get, history_list};
+pub use options::Options;
+#[cfg(feature = "osc52")]
+pub use osc52::{Osc52, Passthrough};
+pub use providers::{Backend, ChangeToken, Changes, Provider, Selection};
+pub use saved::ClipboardGuard;
+pub use secret::{ClearGuard, SecretGuard};
+use std::{
+    fmt::Display,
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+pub use watch::{StopHandle, Wait, Watch, WatcherHandle};
+#[cfg(feature = "zeroize")]
+pub use wipe::{Wipe, WipeOnDrop};
 
-use std::{io, fmt::Display, sync::OnceLock};
+/// The detected clipboard. Failed detection is not kept, so that it is tried again next time.
+static CLIP: RwLock<Option<Clipboard>> = RwLock::new(None);
+
+fn clip() -> Result<Clipboard> {
+    cached(&CLIP, detect)
+}
+
+fn detect() -> Result<Clipboard> {
+    Clipboard::detect()
+}
 
-static CLIP: OnceLock<io::Result<providers::Board>> = OnceLock::new();
+/// The primary and secondary selections of the detected clipboard's provider, once they
+/// have been asked for.
+static PRIMARY: RwLock<Option<Clipboard>> = RwLock::new(None);
+static SECONDARY: RwLock<Option<Clipboard>> = RwLock::new(None);
+
+fn on(selection: Selection) -> Result<Clipboard> {
+    let cache = match selection {
+        Selection::Clipboard => return clip(),
+        Selection::Primary => &PRIMARY,
+        Selection::Secondary => &SECONDARY,
+    };
+    cached(cache, || {
+        let o = options::DEFAULT
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .selection(selection);
+        Clipboard::of(clip()?.provider(), &o)
+    })
+}
+
+fn forget_selections() {
+    for cache in [&PRIMARY, &SECONDARY] {
+        *cache.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// Held by tests that change the global clipboard.
+#[cfg(test)]
+static GLOBAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Drops the detected clipboard, so that it is detected again next time.
+fn forget() {
+    *CLIP.write().unwrap_or_else(PoisonError::into_inner) = None;
+    forget_selections();
+}
+
+fn cached(
+    clip: &RwLock<Option<Clipboard>>,
+    detect: impl FnOnce() -> Result<Clipboard>,
+) -> Result<Clipboard> {
+    if let Some(c) = &*clip.read().unwrap_or_else(PoisonError::into_inner) {
+        return Ok(c.clone());
+    }
+    let mut clip = clip.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(c) = &*clip {
+        return Ok(c.clone());
+    }
+    let c = detect()?;
+    *clip = Some(c.clone());
+    Ok(c)
+}
+
+/// Which provider is in use, detecting it if that has not happened yet.
+///
+/// ```no_run
+/// println!("clipboard: {}", clipp::active_provider()?);
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available.
+pub fn active_provider() -> Result<Provider> {
+    clip().map(|c| c.provider())
+}
+
+/// Detect the clipboard again, and use it from now on.
+///
+/// Useful if the clipboard was not ready the first time around,
+/// or has changed since (e.g. `xclip` got installed).
+///
+/// # Errors
+///
+/// If no clipboard is available. The previously detected clipboard, if any, stays in use.
+pub fn refresh() -> Result<()> {
+    let c = detect()?;
+    *CLIP.write().unwrap_or_else(PoisonError::into_inner) = Some(c);
+    forget_selections();
+    Ok(())
+}
+
+/// Use this backend for [`copy`], [`paste`] and friends, instead of the detected clipboard.
+///
+/// It stays in use until the next [`refresh`] or [`Options::set_default`].
+///
+/// ```
+/// # use std::sync::Mutex;
+/// # struct Ipc(Mutex<String>);
+/// # impl clipp::Backend for Ipc {
+/// #     fn copy(&self, text: &str) -> clipp::Result<()> { *self.0.lock().unwrap() = text.into(); Ok(()) }
+/// #     fn paste(&self) -> clipp::Result<String> { Ok(self.0.lock().unwrap().clone()) }
+/// # }
+/// // route everything through the plugin host
+/// clipp::set_provider(Box::new(Ipc(Mutex::default())));
+/// clipp::copy("over ipc")?;
+/// assert_eq!(clipp::paste()?, "over ipc");
+/// # Ok::<_, clipp::Error>(())
+/// ```
+pub fn set_provider(backend: Box<dyn Backend>) {
+    *CLIP.write().unwrap_or_else(PoisonError::into_inner) = Some(Clipboard::from(backend));
+    forget_selections();
+}
+
+/// All the providers that could be used here, in the order detection would pick them.
+///
+/// ```no_run
+/// // e.g. [Wayland, XClip]: wl-clipboard and xclip are both installed, and wl-clipboard will be used
+/// println!("{:?}", clipp::available_providers());
+/// ```
+#[must_use]
+pub fn available_providers() -> Vec<Provider> {
+    options::DEFAULT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .available()
+}
+
+/// Use this provider, skipping detection. See [`Clipboard::with_provider`].
+///
+/// # Errors
+///
+/// If a program the provider needs is not installed.
+pub fn with_provider(provider: Provider) -> Result<Clipboard> {
+    Clipboard::with_provider(provider)
+}
 
 /// Copy text to the clipboard.
-pub fn copy(text: impl Display) {
-    CLIP.get_or_init(providers::provide)
-        .unwrap()
-        .0(&format!("{text}")).unwrap()
+///
+/// `text` is handed to the provider as it is, without a copy being made of it first.
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy(text: &str) -> Result<()> {
+    clip()?.copy(text)
+}
+
+/// Copy anything that displays to the clipboard, for when failing to is a bug.
+///
+/// It is formatted first, into a string of its own, which [`copy`] doesn't need for text
+/// there already is. With the `zeroize` feature, that string is wiped once copied.
+///
+/// # Panics
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy_or_panic(text: impl Display) {
+    let text = wipe::WipeOnDrop::from(text.to_string());
+    clip().unwrap().copy(&text).unwrap();
 }
 
 /// Copy text to the clipboard.
-pub fn copy2(text: &str) -> io::Result<()> {
-    CLIP.get_or_init(providers::provide)?.0(&text)
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+#[deprecated(since = "0.2.0", note = "`copy` does this now")]
+pub fn copy2(text: &str) -> Result<()> {
+    copy(text)
+}
+
+/// Copy what `reader` reads to the clipboard, without holding all of it at once
+/// where the provider allows. See [`Clipboard::copy_from`].
+///
+/// ```no_run
+/// let log = std::fs::File::open("build.log")?;
+/// clipp::copy_from(log)?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, reading fails, with [`Error::Read`], or copying fails.
+pub fn copy_from(mut reader: impl std::io::Read) -> Result<()> {
+    clip()?.copy_from(&mut reader)
+}
+
+/// Copy what `args` format to, a chunk at a time as it is formatted, rather than building a
+/// string of all of it first. See [`Clipboard::copy_fmt`].
+///
+/// ```no_run
+/// struct Squares(u64);
+/// impl std::fmt::Display for Squares {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         (0..self.0).try_for_each(|n| writeln!(f, "{n}\t{}", n * n))
+///     }
+/// }
+/// // megabytes of it, never all in one place
+/// clipp::copy_fmt(format_args!("{}", Squares(1_000_000)))?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or formatting or copying fails.
+pub fn copy_fmt(args: std::fmt::Arguments<'_>) -> Result<()> {
+    clip()?.copy_fmt(args)
+}
+
+/// Copy bytes to the clipboard, which needn't be text. See [`Clipboard::copy_bytes`].
+///
+/// ```no_run
+/// clipp::copy_bytes(&std::fs::read("plot.png")?)?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, they aren't UTF-8 and it only takes text,
+/// with [`Error::Unsupported`], or copying fails.
+pub fn copy_bytes(bytes: &[u8]) -> Result<()> {
+    clip()?.copy_bytes(bytes)
+}
+
+/// Copy `data` to the clipboard as `mime`, e.g. `text/html` or `image/png`.
+/// See [`Clipboard::copy_mime`].
+///
+/// ```no_run
+/// clipp::copy_mime("image/png", &std::fs::read("plot.png")?)?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, `mime` isn't a MIME type, the provider can't do this,
+/// with [`Error::Unsupported`], or copying fails.
+pub fn copy_mime(mime: &str, data: &[u8]) -> Result<()> {
+    clip()?.copy_mime(mime, data)
+}
+
+/// Copy each of `items` to the clipboard as its MIME type, at once, the first being
+/// the one preferred. See [`Clipboard::copy_multi`].
+///
+/// # Errors
+///
+/// If no clipboard is available, there are no items or one's type isn't a MIME type,
+/// the provider can't take the one it copies, with [`Error::Unsupported`], or copying fails.
+pub fn copy_multi(items: &[(&str, &[u8])]) -> Result<()> {
+    clip()?.copy_multi(items)
+}
+
+/// Copy `html` to the clipboard, with `alt_text` for whatever pastes it as text.
+/// See [`Clipboard::copy_html`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy_html(html: &str, alt_text: &str) -> Result<()> {
+    clip()?.copy_html(html, alt_text)
+}
+
+/// Copy `rtf` to the clipboard, with `alt_text` for whatever pastes it as text.
+/// See [`Clipboard::copy_rtf`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't do this, with [`Error::Unsupported`],
+/// or copying fails.
+pub fn copy_rtf(rtf: &str, alt_text: &str) -> Result<()> {
+    clip()?.copy_rtf(rtf, alt_text)
+}
+
+/// Copy a secret, e.g. a password, to the clipboard, marked for clipboard managers to leave
+/// it out of their history where the provider can. See [`Clipboard::copy_concealed`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy_concealed(text: &str) -> Result<()> {
+    clip()?.copy_concealed(text)
+}
+
+/// Copy a secret, e.g. a password, to the clipboard, and clear it after `ttl` unless something
+/// else has been copied by then. See [`Clipboard::copy_secret`].
+///
+/// ```no_run
+/// use std::time::Duration;
+/// clipp::copy_secret("hunter2", Duration::from_secs(30))?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy_secret(text: &str, ttl: Duration) -> Result<SecretGuard> {
+    clip()?.copy_secret(text, ttl)
+}
+
+/// Copy `text` to the clipboard while `f` runs, and put back what was there before afterwards.
+/// See [`Clipboard::with_temporary`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or saving what was there, copying, or putting it back fails.
+pub fn with_temporary<R>(text: &str, f: impl FnOnce() -> R) -> Result<R> {
+    clip()?.with_temporary(text, f)
+}
+
+/// Copy text to the clipboard for as long as the returned guard lives, e.g. a one-time code.
+/// See [`Clipboard::copy_scoped`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails.
+pub fn copy_scoped(text: &str) -> Result<ClearGuard> {
+    clip()?.copy_scoped(text)
+}
+
+/// Paste a secret, e.g. a password, for it to be wiped from memory once it is dropped.
+/// See [`Clipboard::paste_secret`].
+///
+/// ```no_run
+/// let password = clipp::paste_secret()?;
+/// assert!(!password.is_empty());
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+#[cfg(feature = "zeroize")]
+pub fn paste_secret() -> Result<WipeOnDrop<String>> {
+    clip()?.paste_secret()
+}
+
+/// Copy a PNG image to the clipboard. See [`Clipboard::copy_image_png`].
+///
+/// # Errors
+///
+/// If no clipboard is available, `png` isn't a PNG, the provider can't take images,
+/// with [`Error::Unsupported`], or copying fails.
+pub fn copy_image_png(png: &[u8]) -> Result<()> {
+    clip()?.copy_image_png(png)
+}
+
+/// Copy files to the clipboard, for a file manager to paste. See [`Clipboard::copy_files`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't take files, with [`Error::Unsupported`],
+/// or copying fails.
+pub fn copy_files(paths: &[&std::path::Path]) -> Result<()> {
+    clip()?.copy_files(paths)
+}
+
+/// Copy text to `selection`, e.g. for a middle click to paste, to the
+/// [primary selection](Selection::Primary).
+///
+/// The primary selection is the detected clipboard's provider's, with the
+/// [default options](Options::set_default).
+///
+/// # Errors
+///
+/// If no clipboard is available, its provider doesn't have that selection, with
+/// [`Error::Unsupported`], or copying fails.
+pub fn copy_to(selection: Selection, text: &str) -> Result<()> {
+    on(selection)?.copy(text)
+}
+
+/// Copy text to the clipboard and the [primary selection](Selection::Primary) both, as
+/// editors' yanks do, so that a middle click pastes it as well.
+///
+/// Where there is no primary selection, e.g. on macOS and Windows, this is just [`copy`].
+///
+/// ```no_run
+/// clipp::copy_both("yanked")?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails. If only copying to the primary selection
+/// fails, the clipboard has the text and the error is [`Error::PrimaryFailed`].
+pub fn copy_both(text: &str) -> Result<()> {
+    clip()?.copy(text)?;
+    match on(Selection::Primary) {
+        Err(Error::Unsupported { .. }) => Ok(()),
+        primary => primary
+            .and_then(|p| p.copy(text))
+            .map_err(|e| Error::PrimaryFailed(Box::new(e))),
+    }
+}
+
+/// Copy text to the clipboard, giving up with [`Error::TimedOut`] after `timeout`.
+/// See [`Clipboard::copy_with_timeout`].
+///
+/// ```no_run
+/// use std::time::Duration;
+/// if let Err(e) = clipp::copy_with_timeout("maybe", Duration::from_millis(250)) {
+///     eprintln!("clipboard unavailable: {e}");
+/// }
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or copying fails or takes too long.
+pub fn copy_with_timeout(text: &str, timeout: Duration) -> Result<()> {
+    clip()?.copy_with_timeout(text, timeout)
 }
 
 /// Paste text from the clipboard.
-pub fn paste() -> String {
-    CLIP.get_or_init(providers::provide)
-        .unwrap()
-        .1().unwrap()
+///
+/// An empty clipboard pastes as an empty string.
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+pub fn paste() -> Result<String> {
+    clip()?.paste()
+}
+
+/// Paste text from the clipboard, for when failing to is a bug.
+///
+/// # Panics
+///
+/// If no clipboard is available, or pasting fails.
+#[must_use]
+pub fn paste_or_panic() -> String {
+    clip().unwrap().paste().unwrap()
 }
 
 /// Paste text from the clipboard.
-pub fn paste2() -> io::Result<String> {
-    CLIP.get_or_init(providers::provide)?.1()
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+#[deprecated(since = "0.2.0", note = "`paste` does this now")]
+pub fn paste2() -> Result<String> {
+    paste()
+}
+
+/// Paste text from `selection`, e.g. what was last selected, for the
+/// [primary selection](Selection::Primary).
+///
+/// ```no_run
+/// let selected = clipp::paste_from(clipp::Selection::Primary)?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, its provider doesn't have that selection, with
+/// [`Error::Unsupported`], or pasting fails.
+pub fn paste_from(selection: Selection) -> Result<String> {
+    on(selection)?.paste()
+}
+
+/// Paste the bytes on the clipboard, whether they are text or not.
+/// See [`Clipboard::paste_bytes`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+pub fn paste_bytes() -> Result<Vec<u8>> {
+    clip()?.paste_bytes()
+}
+
+/// Write what is on the clipboard to `writer` as it comes, without holding all of it at once
+/// where the provider allows, returning how many bytes were written.
+/// See [`Clipboard::paste_to`].
+///
+/// ```no_run
+/// let mut out = std::io::stdout().lock();
+/// clipp::paste_to(&mut out)?;
+/// # Ok::<_, std::io::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting or writing fails, as the [`io::Error`](std::io::Error)
+/// the [`Error`] converts to.
+pub fn paste_to(writer: &mut impl std::io::Write) -> std::io::Result<u64> {
+    Ok(clip()?.paste_to(writer)?)
+}
+
+/// Paste what is on the clipboard as `mime`, e.g. `text/html`. See [`Clipboard::paste_mime`].
+///
+/// # Errors
+///
+/// If no clipboard is available, it isn't offered as `mime`, with [`Error::FormatUnavailable`],
+/// the provider can't do this, with [`Error::Unsupported`], or pasting fails.
+pub fn paste_mime(mime: &str) -> Result<Vec<u8>> {
+    clip()?.paste_mime(mime)
+}
+
+/// Paste the HTML on the clipboard, or [`None`] if there isn't any.
+/// See [`Clipboard::paste_html`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't do this, with [`Error::Unsupported`],
+/// or pasting fails.
+pub fn paste_html() -> Result<Option<String>> {
+    clip()?.paste_html()
+}
+
+/// Paste the RTF on the clipboard, or [`None`] if there isn't any.
+/// See [`Clipboard::paste_rtf`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't do this, with [`Error::Unsupported`],
+/// or pasting fails.
+pub fn paste_rtf() -> Result<Option<String>> {
+    clip()?.paste_rtf()
+}
+
+/// Paste the PNG image on the clipboard, or [`None`] if there isn't one.
+/// See [`Clipboard::paste_image_png`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't give images, with [`Error::Unsupported`],
+/// or pasting fails.
+pub fn paste_image_png() -> Result<Option<Vec<u8>>> {
+    clip()?.paste_image_png()
+}
+
+/// The files on the clipboard, e.g. what a file manager copied, or [`None`] if there aren't any.
+/// See [`Clipboard::paste_files`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't give files, with [`Error::Unsupported`],
+/// or pasting fails.
+pub fn paste_files() -> Result<Option<Vec<std::path::PathBuf>>> {
+    clip()?.paste_files()
+}
+
+/// The MIME types what is on the clipboard is offered as. See [`Clipboard::targets`].
+///
+/// # Errors
+///
+/// If no clipboard is available, the provider can't tell, with [`Error::Unsupported`],
+/// or asking fails.
+pub fn targets() -> Result<Vec<String>> {
+    clip()?.targets()
+}
+
+/// Paste whatever is on the clipboard, as the richest of what it is offered as.
+/// See [`Clipboard::paste_any`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+pub fn paste_any() -> Result<ClipboardContent> {
+    clip()?.paste_any()
+}
+
+/// Paste text from the clipboard, giving up with [`Error::TimedOut`] after `timeout`.
+/// See [`Clipboard::paste_with_timeout`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails or takes too long.
+pub fn paste_with_timeout(timeout: Duration) -> Result<String> {
+    clip()?.paste_with_timeout(timeout)
+}
+
+/// Copy text to the clipboard, returning the text it replaced. See [`Clipboard::swap`].
+///
+/// ```no_run
+/// let was = clipp::swap("next")?;
+/// println!("rotated out: {was}");
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting or copying fails.
+pub fn swap(text: &str) -> Result<String> {
+    clip()?.swap(text)
+}
+
+/// Run what is on the clipboard through `f`, copying what it returns in its place.
+/// See [`Clipboard::transform`].
+///
+/// ```no_run
+/// clipp::transform(|s| s.replace('\t', "    "))?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting or copying fails.
+pub fn transform(f: impl FnOnce(String) -> String) -> Result<String> {
+    clip()?.transform(f)
+}
+
+/// [`transform`], with an `f` that can fail, copying nothing if it does.
+/// See [`Clipboard::try_transform`].
+///
+/// # Errors
+///
+/// If no clipboard is available, pasting or copying fails, or `f` does.
+pub fn try_transform<E: From<Error>>(
+    f: impl FnOnce(String) -> Result<String, E>,
+) -> Result<String, E> {
+    clip()?.try_transform(f)
+}
+
+/// Add text to the end of what is on the clipboard. See [`Clipboard::append`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting or copying fails.
+pub fn append(text: &str) -> Result<()> {
+    clip()?.append(text)
+}
+
+/// Add text to the end of what is on the clipboard, with `sep` between them.
+/// See [`Clipboard::append_with_separator`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting or copying fails.
+pub fn append_with_separator(text: &str, sep: &str) -> Result<()> {
+    clip()?.append_with_separator(text, sep)
+}
+
+/// Paste text from the clipboard, or [`None`] if it is empty.
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting fails.
+/// An empty clipboard is not an error.
+pub fn paste_opt() -> Result<Option<String>> {
+    clip()?.paste_opt()
+}
+
+/// Empty the clipboard.
+///
+/// [`paste`] will return an empty string afterwards.
+///
+/// # Errors
+///
+/// If no clipboard is available, or clearing fails.
+pub fn clear() -> Result<()> {
+    clip()?.clear()
+}
+
+/// Something that changes whenever what is on the clipboard does.
+/// See [`Clipboard::change_token`].
+///
+/// # Errors
+///
+/// If no clipboard is available, or pasting, or asking for it, fails.
+pub fn change_token() -> Result<ChangeToken> {
+    clip()?.change_token()
+}
+
+/// Wait for something to be copied, or the clipboard to be cleared, and return what is on
+/// it then, or [`None`] if `timeout` is up first. See [`Clipboard::wait_for_change`].
+///
+/// ```no_run
+/// use std::time::Duration;
+/// match clipp::wait_for_change(Some(Duration::from_secs(30)))? {
+///     Some(copied) => println!("copied: {copied}"),
+///     None => println!("nothing in 30s"),
+/// }
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or watching it, or pasting, fails.
+pub fn wait_for_change(timeout: Option<Duration>) -> Result<Option<String>> {
+    clip()?.wait_for_change(timeout)
+}
+
+/// Each new thing copied from now on, looking every `interval` where the clipboard can't
+/// say when it changes. See [`Watch`].
+///
+/// # Errors
+///
+/// If no clipboard is available.
+pub fn watch(interval: Duration) -> Result<Watch> {
+    Ok(clip()?.watch(interval))
+}
+
+/// Call `on_change` with each new thing copied from now on, on a thread of its own, until
+/// the handle is stopped or dropped. See [`Clipboard::spawn_watcher`].
+///
+/// # Errors
+///
+/// If no clipboard is available.
+pub fn spawn_watcher(on_change: impl Fn(String) + Send + 'static) -> Result<WatcherHandle> {
+    Ok(clip()?.spawn_watcher(on_change))
+}
+
+/// Keep what was copied on the clipboard once this process has exited.
+///
+/// Short-lived programs should call this before they exit. Most clipboards keep what was
+/// copied anyway, but the native X11 one is held by this process: this hands it to the
+/// clipboard manager if there is one, and otherwise waits until something else is copied.
+///
+/// ```no_run
+/// clipp::copy("token")?;
+/// clipp::persist()?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If no clipboard is available, or handing it over fails.
+pub fn persist() -> Result<()> {
+    clip()?.persist()
+}
+
+#[test]
+fn empty() {
+    let Ok(c) = detect() else {
+        return; // nothing to test against
+    };
+    if !providers::integration() {
+        return;
+    }
+    let _restore = providers::Restore::new(&*c.backend);
+    c.clear().unwrap();
+    assert_eq!(c.paste_opt().unwrap(), None);
+    assert_eq!(c.paste().unwrap(), "");
+}
+
+#[test]
+fn persisted() {
+    use std::process::Command;
+    // copying from a child that exits (or says it would) as soon as it can
+    if let Some(text) = std::env::var_os("CLIPP_PERSIST_CHILD") {
+        copy(text.to_str().unwrap()).unwrap();
+        persist().unwrap();
+        return;
+    }
+    let Ok(c) = detect() else {
+        return;
+    };
+    if !providers::integration() {
+        return;
+    }
+    let _restore = providers::Restore::new(&*c.backend);
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(["persisted", "--exact", "--quiet"])
+        .env("CLIPP_PERSIST_CHILD", "outlived")
+        .spawn()
+        .unwrap();
+    for _ in 0..50 {
+        if child.try_wait().unwrap().is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(c.paste().unwrap(), "outlived");
+    // without a clipboard manager, it waits for something else to be copied
+    if child.try_wait().unwrap().is_none() {
+        c.copy("something else").unwrap();
+    }
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn retried() {
+    let clip = RwLock::new(None);
+    assert!(cached(&clip, || Err(Error::NoProvider)).is_err());
+    assert!(clip.read().unwrap().is_none());
+    let hi = Clipboard::new(clipboard::Scratch("hi".to_owned().into()));
+    assert!(cached(&clip, || Ok(hi)).is_ok());
+    // and it sticks
+    let c = cached(&clip, || Err(Error::NoProvider)).unwrap();
+    assert_eq!(c.paste().unwrap(), "hi");
+}
+
+#[test]
+fn active() {
+    if let Ok(p) = active_provider() {
+        // (unless set_provider has been at it)
+        assert!(Provider::ALL.contains(&p) || p == Provider::Custom);
+        assert!(!p.name().is_empty());
+    }
+}
+
+#[test]
+fn primary() {
+    let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    set_provider(Box::new(clipboard::Scratch::default()));
+    copy_to(Selection::Clipboard, "copied").unwrap();
+    assert_eq!(paste_from(Selection::Clipboard).unwrap(), "copied");
+    // a clipboard of one's own has no primary selection to go with it
+    assert!(matches!(
+        copy_to(Selection::Primary, "selected"),
+        Err(Error::Unsupported { .. })
+    ));
+    assert!(matches!(
+        paste_from(Selection::Primary),
+        Err(Error::Unsupported { .. })
+    ));
+    // which is no reason not to copy to the clipboard
+    copy_both("both").unwrap();
+    assert_eq!(paste().unwrap(), "both");
+    forget();
+}
+
+#[test]
+fn both() {
+    let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    forget();
+    let Ok(c) = clip() else {
+        return;
+    };
+    if !providers::integration() || !c.provider().has_selection(Selection::Primary) {
+        return;
+    }
+    let _restore = providers::Restore::new(&*c.backend);
+    copy_to(Selection::Primary, "before").unwrap();
+    copy_both("mirrored").unwrap();
+    assert_eq!(paste_from(Selection::Clipboard).unwrap(), "mirrored");
+    assert_eq!(paste_from(Selection::Primary).unwrap(), "mirrored");
+}
+
+#[test]
+fn custom() {
+    use std::sync::{Arc, Mutex};
+    #[derive(Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+    impl Backend for Recorder {
+        fn copy(&self, text: &str) -> Result<()> {
+            self.0.lock().unwrap().push(text.to_owned());
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            Ok(self.0.lock().unwrap().last().cloned().unwrap_or_default())
+        }
+    }
+    let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    let log = Arc::default();
+    set_provider(Box::new(Recorder(Arc::clone(&log))));
+    copy("one").unwrap();
+    copy_or_panic(2);
+    assert_eq!(paste().unwrap(), "2");
+    assert_eq!(paste_or_panic(), "2");
+    #[allow(deprecated)]
+    {
+        copy2("three").unwrap();
+        assert_eq!(paste2().unwrap(), "three");
+    }
+    assert_eq!(active_provider().unwrap(), Provider::Custom);
+    assert_eq!(*log.lock().unwrap(), ["one", "2", "three"]);
+    forget();
+}
+
+#[test]
+fn borrowed() {
+    use std::sync::{Arc, Mutex};
+    // where the text it was given is
+    struct At(Arc<Mutex<Vec<usize>>>);
+    impl Backend for At {
+        fn copy(&self, text: &str) -> Result<()> {
+            self.0.lock().unwrap().push(text.as_ptr() as usize);
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+    let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    let at = Arc::default();
+    set_provider(Box::new(At(Arc::clone(&at))));
+    let big = "entry ".repeat(1 << 20);
+    copy(&big).unwrap();
+    clip().unwrap().copy(&big).unwrap();
+    // and not for what is formatted
+    copy_or_panic(&big);
+    let at = at.lock().unwrap();
+    assert_eq!(at[..2], [big.as_ptr() as usize; 2]);
+    assert_ne!(at[2], big.as_ptr() as usize);
+    forget();
 }
@@ -0,0 +1,85 @@
+//! the pasteboards macOS has besides the clipboard
+use crate::{
+    providers::{real, PbCopy},
+    Backend, Result,
+};
+
+/// One of the pasteboards `pbcopy` and `pbpaste` can be pointed at with `-pboard`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Pasteboard {
+    /// The clipboard, that Cmd+C copies to and Cmd+V pastes from.
+    #[default]
+    General,
+    /// What every app's find field searches for, that Cmd+E sets and Cmd+G looks for again.
+    Find,
+    /// The paragraph formatting copied with Cmd+Ctrl+C.
+    Ruler,
+}
+
+impl Pasteboard {
+    /// What `-pboard` calls it.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Find => "find",
+            Self::Ruler => "ruler",
+        }
+    }
+}
+
+/// Copy text to `pasteboard`.
+///
+/// # Errors
+///
+/// If copying fails.
+pub fn copy_to(pasteboard: Pasteboard, text: &str) -> Result<()> {
+    PbCopy::new(real(), pasteboard).copy(text)
+}
+
+/// Paste text from `pasteboard`.
+///
+/// # Errors
+///
+/// If pasting fails.
+pub fn paste_from(pasteboard: Pasteboard) -> Result<String> {
+    PbCopy::new(real(), pasteboard).paste()
+}
+
+/// Set what to find, for the next Cmd+G in any app, leaving the clipboard alone.
+///
+/// ```no_run
+/// clipp::macos::copy_find("TODO")?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// If copying fails.
+pub fn copy_find(text: &str) -> Result<()> {
+    copy_to(Pasteboard::Find, text)
+}
+
+/// What is being found, the last search of any app.
+///
+/// # Errors
+///
+/// If pasting fails.
+pub fn paste_find() -> Result<String> {
+    paste_from(Pasteboard::Find)
+}
+
+#[test]
+fn find() {
+    if !crate::providers::integration() {
+        return;
+    }
+    let general = PbCopy::new(real(), Pasteboard::General);
+    let _restore = crate::providers::Restore::new(&general);
+    let was = paste_find().unwrap();
+    general.copy("on the clipboard").unwrap();
+    copy_find("needle").unwrap();
+    assert_eq!(paste_find().unwrap(), "needle");
+    assert_eq!(general.paste().unwrap(), "on the clipboard");
+    copy_find(&was).unwrap();
+}
@@ -0,0 +1,62 @@
+//! an in-memory clipboard, for tests.
+//!
+//! enable the `mock` feature (e.g. as a dev-dependency), and [`install`] it,
+//! so that code calling [`copy`](crate::copy) and [`paste`](crate::paste) can be tested
+//! without a display server, and without clobbering what's on your actual clipboard.
+//!
+//! ```
+//! clipp::mock::install();
+//! clipp::copy("not on your clipboard")?;
+//! assert_eq!(clipp::mock::contents(), "not on your clipboard");
+//! # Ok::<_, clipp::Error>(())
+//! ```
+//!
+//! there is one mock clipboard per process, so tests running in parallel share it.
+use crate::{Backend, Result};
+use std::sync::{Mutex, PoisonError};
+
+static CONTENTS: Mutex<String> = Mutex::new(String::new());
+
+struct Mock;
+impl Backend for Mock {
+    fn copy(&self, text: &str) -> Result<()> {
+        set(text);
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        Ok(contents())
+    }
+}
+
+/// Use the mock clipboard for [`copy`](crate::copy), [`paste`](crate::paste) and friends.
+pub fn install() {
+    crate::set_provider(Box::new(Mock));
+}
+
+/// What's on the mock clipboard.
+#[must_use]
+pub fn contents() -> String {
+    CONTENTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Put something on the mock clipboard, e.g. for the code under test to paste.
+pub fn set(text: &str) {
+    text.clone_into(&mut CONTENTS.lock().unwrap_or_else(PoisonError::into_inner));
+}
+
+#[test]
+fn mocked() {
+    let _global = crate::GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    install();
+    assert_eq!(crate::active_provider().unwrap(), crate::Provider::Custom);
+    crate::copy("mocked").unwrap();
+    assert_eq!(contents(), "mocked");
+    set("pasted");
+    assert_eq!(crate::paste().unwrap(), "pasted");
+    crate::clear().unwrap();
+    assert_eq!(crate::paste_opt().unwrap(), None);
+}
@@ -0,0 +1,181 @@
+//! configurable detection
+use crate::{
+    providers::{self, Probe},
+    Clipboard, Provider, Result, Selection,
+};
+use std::{
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+
+/// What [`copy`](crate::copy) and friends detect with, see [`Options::set_default`].
+pub(crate) static DEFAULT: RwLock<Options> = RwLock::new(Options::new());
+
+/// How to go about detecting the clipboard.
+///
+/// ```no_run
+/// use clipp::{Options, Provider};
+/// // plasma users want their copies to end up in klipper's history
+/// let clip = Options::new()
+///     .prefer([Provider::Klipper, Provider::Wayland])
+///     .detect()?;
+/// clip.copy("remember me")?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    prefer: Vec<Provider>,
+    pub(crate) unix_newlines: bool,
+    pub(crate) clip_exe: bool,
+    fallback_to_memory: bool,
+    pub(crate) timeout: Duration,
+    pub(crate) selection: Selection,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    /// How long the programs clipboards are reached through get by default.
+    pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The defaults: try every provider in the order of [`Provider::ALL`].
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            prefer: Vec::new(),
+            unix_newlines: false,
+            clip_exe: false,
+            fallback_to_memory: false,
+            timeout: Self::TIMEOUT,
+            selection: Selection::Clipboard,
+        }
+    }
+
+    /// Try these providers first, in this order.
+    /// Providers that are not listed are tried afterwards, in the usual order.
+    #[must_use]
+    pub fn prefer(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.prefer = providers.into_iter().collect();
+        self
+    }
+
+    /// Paste the Windows clipboard's `\r\n` line endings as `\n`,
+    /// when it is pasted from through WSL, MSYS2 or Git Bash.
+    #[must_use]
+    pub fn unix_newlines(mut self, unix_newlines: bool) -> Self {
+        self.unix_newlines = unix_newlines;
+        self
+    }
+
+    /// Copy through WSL, MSYS2 or Git Bash with `clip.exe`, which starts a lot faster
+    /// than powershell's `Set-Clipboard` does.
+    #[must_use]
+    pub fn clip_exe(mut self, clip_exe: bool) -> Self {
+        self.clip_exe = clip_exe;
+        self
+    }
+
+    /// Give the programs clipboards are reached through, such as xclip, wl-copy or `pbcopy`,
+    /// this long to copy or paste, and fail with [`Error::TimedOut`](crate::Error::TimedOut)
+    /// after that. Left to themselves, xclip and xsel wait forever on an X server that has gone
+    /// away. `copyq`, Termux and the network clipboards keep limits of their own.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Copy to and paste from this selection. Only providers that
+    /// [have it](Provider::has_selection) can be detected for it.
+    ///
+    /// ```no_run
+    /// use clipp::{Options, Selection};
+    /// let primary = Options::new().selection(Selection::Primary).detect()?;
+    /// println!("selected: {}", primary.paste()?);
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    #[must_use]
+    pub const fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// When detection fails, use a clipboard that only this process can see instead of failing,
+    /// so that copying and pasting within it still works. Its provider is [`Provider::Memory`].
+    ///
+    /// ```no_run
+    /// clipp::Options::new().fallback_to_memory(true).set_default();
+    /// // even on a box with no clipboard at all
+    /// clipp::copy("kept")?;
+    /// assert_eq!(clipp::paste()?, "kept");
+    /// # Ok::<_, clipp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn fallback_to_memory(mut self, fallback_to_memory: bool) -> Self {
+        self.fallback_to_memory = fallback_to_memory;
+        self
+    }
+
+    /// Detect a clipboard with these options.
+    ///
+    /// `CLIPP_PROVIDER` still wins over any preferences.
+    ///
+    /// # Errors
+    ///
+    /// If no clipboard is available, unless falling back to memory.
+    pub fn detect(&self) -> Result<Clipboard> {
+        self.detect_in(&providers::System)
+    }
+
+    fn detect_in(&self, p: &impl Probe) -> Result<Clipboard> {
+        match providers::detect(p, &self.prefer) {
+            Ok(provider) => Clipboard::of(provider, self),
+            Err(_) if self.fallback_to_memory => Ok(Clipboard::memory()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The providers that could be used here, in the order [`detect`](Self::detect) would pick them.
+    #[must_use]
+    pub fn available(&self) -> Vec<Provider> {
+        providers::available(&providers::System, &self.prefer).collect()
+    }
+
+    /// Use these options for [`copy`](crate::copy), [`paste`](crate::paste) and friends.
+    ///
+    /// The clipboard is detected again on next use, so this can be done at any time,
+    /// not just before the first.
+    pub fn set_default(self) {
+        *DEFAULT.write().unwrap_or_else(PoisonError::into_inner) = self;
+        crate::forget();
+    }
+}
+
+#[test]
+fn memory() {
+    let env = providers::Fake::default();
+    assert!(Options::new().detect_in(&env).is_err());
+    let o = Options::new().fallback_to_memory(true);
+    let clip = o.detect_in(&env).unwrap();
+    assert_eq!(clip.provider(), Provider::Memory);
+    assert_eq!(clip.provider().name(), "memory");
+    clip.copy("kept").unwrap();
+    assert_eq!(clip.paste().unwrap(), "kept");
+    // each is its own
+    assert_eq!(o.detect_in(&env).unwrap().paste().unwrap(), "");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn memory_last() {
+    let bin = providers::Bin::new("memory-last");
+    bin.add("gpaste-client", "");
+    let mut env = providers::Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    let o = Options::new().fallback_to_memory(true);
+    assert_eq!(o.detect_in(&env).unwrap().provider(), Provider::GPaste);
+}
@@ -0,0 +1,494 @@
+//! copying through the terminal, with OSC 52
+use crate::{
+    providers::{Probe, System},
+    wipe::WipeOnDrop,
+    Backend, Error, Result,
+};
+use std::{
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+/// Copies by asking the terminal to, with the OSC 52 escape sequence.
+///
+/// This needs no clipboard program, so it works over SSH, as long as the terminal
+/// supports it (alacritty, kitty, wezterm and iTerm2 do).
+/// Detection falls back to it when `SSH_TTY` is set and nothing else is available.
+///
+/// The sequence goes to `/dev/tty`, or to stdout if there is no tty and stdout is a terminal.
+/// If it is neither, copying fails instead of putting escape codes where they don't belong.
+///
+/// Inside tmux or screen, the sequence is wrapped so that it gets through to the terminal,
+/// see [`passthrough`](Self::passthrough).
+///
+/// Pasting asks the terminal what is on its clipboard. Most only answer if configured to
+/// (e.g. kitty's `clipboard_control`), so pasting gives up with [`Error::TimedOut`]
+/// after a [`timeout`](Self::timeout).
+///
+/// ```no_run
+/// // this terminal takes more than most
+/// let clip = clipp::Clipboard::from(clipp::Osc52::new().max_len(1 << 20));
+/// clip.copy("to the other side")?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Osc52 {
+    max_len: usize,
+    through: Option<Vec<Passthrough>>,
+    timeout: Duration,
+}
+
+/// A terminal multiplexer that OSC 52 has to be smuggled through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Passthrough {
+    Tmux,
+    /// GNU screen.
+    Screen,
+}
+
+impl Passthrough {
+    /// Readies `seq` to go through this multiplexer.
+    fn wrap(self, seq: &[u8]) -> Vec<u8> {
+        match self {
+            // tmux wants the escapes in it doubled
+            Self::Tmux => {
+                let mut v = b"\x1bPtmux;".to_vec();
+                for &b in seq {
+                    if b == 0x1b {
+                        v.push(0x1b);
+                    }
+                    v.push(b);
+                }
+                v.extend_from_slice(b"\x1b\\");
+                v
+            }
+            // screen drops anything past 768 bytes, so it goes in pieces
+            Self::Screen => seq
+                .chunks(768)
+                .flat_map(|c| [b"\x1bP", c, b"\x1b\\"].concat())
+                .collect(),
+        }
+    }
+}
+
+/// The multiplexers we are running in, innermost first.
+fn multiplexers(p: &impl Probe) -> Vec<Passthrough> {
+    // tmux sets TERM=screen too
+    if p.var("TMUX").is_some_and(|v| !v.is_empty()) {
+        vec![Passthrough::Tmux]
+    } else if p
+        .var("TERM")
+        .is_some_and(|t| t.to_string_lossy().starts_with("screen"))
+    {
+        vec![Passthrough::Screen]
+    } else {
+        vec![]
+    }
+}
+
+impl Osc52 {
+    /// How long the encoded text may be by default.
+    /// Terminals cut the sequence off at limits of their own, and most put up with this much.
+    pub const MAX_LEN: usize = 100_000;
+
+    /// How long to wait for the terminal to answer a paste, by default.
+    pub const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_len: Self::MAX_LEN,
+            through: None,
+            timeout: Self::TIMEOUT,
+        }
+    }
+
+    /// Refuse to copy anything longer than this, once encoded, with [`Error::TooLong`].
+    #[must_use]
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Wrap the sequence to get it through these multiplexers, innermost first,
+    /// instead of going by `TMUX` and `TERM`.
+    ///
+    /// ```no_run
+    /// use clipp::{Osc52, Passthrough};
+    /// // tmux, inside screen
+    /// let osc52 = Osc52::new().passthrough([Passthrough::Tmux, Passthrough::Screen]);
+    /// // no multiplexer, whatever TERM says
+    /// let osc52 = Osc52::new().passthrough([]);
+    /// ```
+    #[must_use]
+    pub fn passthrough(mut self, through: impl IntoIterator<Item = Passthrough>) -> Self {
+        self.through = Some(through.into_iter().collect());
+        self
+    }
+
+    /// Wait this long for the terminal to answer a paste.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn through(&self) -> Vec<Passthrough> {
+        match &self.through {
+            Some(through) => through.clone(),
+            None => multiplexers(&System),
+        }
+    }
+
+    /// The escape sequence that copies `text`, wrapped for `through`.
+    fn sequence(&self, text: &str, through: &[Passthrough]) -> Result<Vec<u8>> {
+        let b64 = WipeOnDrop::from(base64(text.as_bytes()));
+        if b64.len() > self.max_len {
+            return Err(Error::TooLong {
+                len: b64.len(),
+                max: self.max_len,
+            });
+        }
+        let seq = [b"\x1b]52;c;", b64.as_bytes(), b"\x07"].concat();
+        Ok(wrap(seq, through))
+    }
+}
+
+impl Default for Osc52 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for Osc52 {
+    fn copy(&self, text: &str) -> Result<()> {
+        send(&WipeOnDrop::from(self.sequence(text, &self.through())?))
+    }
+
+    #[cfg(unix)]
+    fn paste(&self) -> Result<String> {
+        use std::{io::Read, time::Instant};
+        let mut tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|e| io::Error::new(e.kind(), "there is no terminal to paste from"))?;
+        let _raw = Raw::new(&tty)?;
+        tty.write_all(&wrap(b"\x1b]52;c;?\x07".to_vec(), &self.through()))?;
+        tty.flush()?;
+        let deadline = Instant::now() + self.timeout;
+        let (mut got, mut buf) = (vec![], [0; 4096]);
+        loop {
+            if let Some(text) = reply(&got)? {
+                return String::from_utf8(text).map_err(|_| Error::InvalidUtf8);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::TimedOut {
+                    what: "waiting for the terminal to answer".into(),
+                });
+            }
+            // gives up after a tenth of a second, see Raw
+            let n = tty.read(&mut buf)?;
+            got.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn paste(&self) -> Result<String> {
+        Err(Error::Unsupported {
+            what: "pasting over OSC 52",
+        })
+    }
+}
+
+fn wrap(seq: Vec<u8>, through: &[Passthrough]) -> Vec<u8> {
+    through.iter().fold(seq, |seq, m| m.wrap(&seq))
+}
+
+/// Keeps the terminal in raw mode, so that its answer isn't echoed or held back until a newline,
+/// and puts it back how it was when dropped.
+#[cfg(unix)]
+struct Raw {
+    tty: std::fs::File,
+    was: String,
+}
+
+#[cfg(unix)]
+impl Raw {
+    fn new(tty: &std::fs::File) -> Result<Self> {
+        let was = stty(tty, &["-g"])?.trim().to_owned();
+        let raw = Self {
+            tty: tty.try_clone()?,
+            was,
+        };
+        // reads give up after a tenth of a second when there is nothing to read
+        stty(tty, &["raw", "-echo", "min", "0", "time", "1"])?;
+        Ok(raw)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Raw {
+    fn drop(&mut self) {
+        _ = stty(&self.tty, &[&self.was]);
+    }
+}
+
+#[cfg(unix)]
+fn stty(tty: &std::fs::File, args: &[&str]) -> Result<String> {
+    let out = std::process::Command::new("stty")
+        .args(args)
+        .stdin(tty.try_clone()?)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::CommandNotFound {
+                command: "stty".into(),
+            },
+            _ => Error::Io(e),
+        })?;
+    if !out.status.success() {
+        return Err(Error::CommandFailed {
+            command: "stty".into(),
+            status: out.status.code(),
+            stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Finds the terminal's answer to a paste in what it has sent so far,
+/// or [`None`] if it has not all arrived yet. Anything before it (e.g. keys pressed meanwhile) is skipped.
+fn reply(got: &[u8]) -> Result<Option<Vec<u8>>> {
+    const START: &[u8] = b"\x1b]52;";
+    let Some(at) = got.windows(START.len()).position(|w| w == START) else {
+        return Ok(None);
+    };
+    // the selection it answers for, then ;
+    let Some(semi) = got[at + START.len()..].iter().position(|&b| b == b';') else {
+        return Ok(None);
+    };
+    let body = &got[at + START.len() + semi + 1..];
+    // ended by BEL, or ST
+    let Some(end) = body.iter().position(|&b| b == 0x07 || b == 0x1b) else {
+        return Ok(None);
+    };
+    match (body[end], body.get(end + 1)) {
+        (0x1b, None) => return Ok(None),
+        (0x1b, Some(b'\\')) | (0x07, _) => {}
+        _ => return Err(garbled()),
+    }
+    unbase64(&body[..end]).map(Some).ok_or_else(garbled)
+}
+
+fn garbled() -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the terminal's answer was garbled",
+    ))
+}
+
+/// Writes to the controlling terminal, falling back to stdout if that is a terminal.
+fn send(seq: &[u8]) -> Result<()> {
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => {
+            tty.write_all(seq)?;
+            tty.flush()?;
+        }
+        Err(_) if io::stdout().is_terminal() => {
+            let mut out = io::stdout().lock();
+            out.write_all(seq)?;
+            out.flush()?;
+        }
+        Err(e) => {
+            return Err(Error::Io(io::Error::new(
+                e.kind(),
+                "there is no terminal to copy through",
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Standard, padded base64.
+fn base64(data: &[u8]) -> String {
+    const ABC: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(char::from(ABC[(n >> (18 - 6 * i) & 63) as usize]));
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Undoes [`base64`], padded or not.
+fn unbase64(s: &[u8]) -> Option<Vec<u8>> {
+    let s = s.strip_suffix(b"==").or(s.strip_suffix(b"=")).unwrap_or(s);
+    if s.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    for chunk in s.chunks(4) {
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            let v = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+            n |= u32::from(v) << (18 - 6 * i);
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+#[test]
+fn encoding() {
+    // rfc 4648's test vectors
+    for (data, b64) in [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ] {
+        assert_eq!(base64(data.as_bytes()), b64);
+    }
+    assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    assert_eq!(base64("héllo".as_bytes()), "aMOpbGxv");
+}
+
+#[test]
+fn sequence() {
+    let o = Osc52::new();
+    assert_eq!(o.sequence("hello", &[]).unwrap(), b"\x1b]52;c;aGVsbG8=\x07");
+    // an empty selection clears it
+    assert_eq!(o.sequence("", &[]).unwrap(), b"\x1b]52;c;\x07");
+    let big = "x".repeat(Osc52::MAX_LEN);
+    assert!(matches!(
+        o.sequence(&big, &[]),
+        Err(Error::TooLong { max: Osc52::MAX_LEN, len }) if len > Osc52::MAX_LEN
+    ));
+    assert!(Osc52::new().max_len(1 << 20).sequence(&big, &[]).is_ok());
+    // "aGk=" exactly fits
+    assert!(Osc52::new().max_len(4).sequence("hi", &[]).is_ok());
+    assert!(Osc52::new().max_len(3).sequence("hi", &[]).is_err());
+}
+
+#[test]
+fn wrapped() {
+    let o = Osc52::new();
+    let tmux = o.sequence("hi", &[Passthrough::Tmux]).unwrap();
+    assert_eq!(tmux, b"\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+    let screen = o.sequence("hi", &[Passthrough::Screen]).unwrap();
+    assert_eq!(screen, b"\x1bP\x1b]52;c;aGk=\x07\x1b\\");
+    let both = o
+        .sequence("hi", &[Passthrough::Tmux, Passthrough::Screen])
+        .unwrap();
+    assert_eq!(both, [b"\x1bP", &tmux[..], b"\x1b\\"].concat());
+
+    // 7 + 1000 + 1 bytes, so a full piece and the rest
+    let long = o
+        .sequence(&"x".repeat(750), &[Passthrough::Screen])
+        .unwrap();
+    let plain = o.sequence(&"x".repeat(750), &[]).unwrap();
+    assert_eq!(
+        long,
+        [
+            b"\x1bP",
+            &plain[..768],
+            b"\x1b\\\x1bP",
+            &plain[768..],
+            b"\x1b\\"
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn multiplexed() {
+    let mut env = crate::providers::Fake::default();
+    assert_eq!(multiplexers(&env), []);
+    env.vars.insert("TERM", "xterm-256color".into());
+    assert_eq!(multiplexers(&env), []);
+    env.vars.insert("TERM", "screen.xterm-256color".into());
+    assert_eq!(multiplexers(&env), [Passthrough::Screen]);
+    env.vars
+        .insert("TMUX", "/tmp/tmux-1000/default,1234,0".into());
+    assert_eq!(multiplexers(&env), [Passthrough::Tmux]);
+}
+
+#[test]
+fn decoding() {
+    for text in [
+        "",
+        "f",
+        "fo",
+        "foo",
+        "foob",
+        "fooba",
+        "foobar",
+        "héllo wörld 👋",
+    ] {
+        assert_eq!(
+            unbase64(base64(text.as_bytes()).as_bytes()).unwrap(),
+            text.as_bytes()
+        );
+    }
+    assert_eq!(unbase64(b"Zm8").unwrap(), b"fo");
+    assert_eq!(unbase64(b"Zg").unwrap(), b"f");
+    assert_eq!(unbase64(b"Zm9vY"), None);
+    assert_eq!(unbase64(b"Zm9v!A=="), None);
+    assert_eq!(unbase64(b"Zm 9v"), None);
+}
+
+#[test]
+fn replies() {
+    let ok = |got: &[u8]| reply(got).unwrap();
+    assert_eq!(ok(b"\x1b]52;c;aGk=\x07").unwrap(), b"hi");
+    assert_eq!(ok(b"\x1b]52;c;aGk=\x1b\\").unwrap(), b"hi");
+    // whichever selection it says it is answering for
+    assert_eq!(ok(b"\x1b]52;p;aGk=\x07").unwrap(), b"hi");
+    assert_eq!(ok(b"\x1b]52;;aGk=\x07").unwrap(), b"hi");
+    assert_eq!(ok(b"\x1b]52;c;\x07").unwrap(), b"");
+    // keys pressed while waiting
+    assert_eq!(ok(b"jk\x1b[A\x1b]52;c;aGk=\x07").unwrap(), b"hi");
+    assert_eq!(ok(b"\x1b]52;c;aGk=\x07 and more").unwrap(), b"hi");
+    // not all here yet
+    for partial in [
+        &b""[..],
+        b"garbage",
+        b"\x1b",
+        b"\x1b]5",
+        b"\x1b]52;c",
+        b"\x1b]52;c;aG",
+        b"\x1b]52;c;aGk=",
+        b"\x1b]52;c;aGk=\x1b",
+    ] {
+        assert_eq!(ok(partial), None, "{partial:?}");
+    }
+    for garbage in [&b"\x1b]52;c;a!k=\x07"[..], b"\x1b]52;c;aGk=\x1b[A"] {
+        assert!(
+            matches!(reply(garbage), Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData),
+            "{garbage:?}"
+        );
+    }
+}
@@ -1,12 +1,375 @@
 //! implements different clipboard types
+use crate::{
+    wipe::{Wipe, WipeOnDrop},
+    Error, Result,
+};
 use std::{
-    io::{self, Read, Write},
-    process::{Command, Stdio},
+    ffi::{OsStr, OsString},
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio},
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
 };
 
-pub trait Clipboard {
-    fn copy(text: &str) -> io::Result<()>;
-    fn paste() -> io::Result<String>;
+#[cfg(all(test, target_os = "linux"))]
+mod headless;
+
+/// Something that can be used as a clipboard.
+///
+/// clipp's own providers implement this, and so can yours:
+/// hand it to [`Clipboard::new`](crate::Clipboard::new) to use it like any other clipboard.
+///
+/// ```
+/// use std::sync::Mutex;
+/// struct Scratch(Mutex<String>);
+/// impl clipp::Backend for Scratch {
+///     fn copy(&self, text: &str) -> clipp::Result<()> {
+///         *self.0.lock().unwrap() = text.to_owned();
+///         Ok(())
+///     }
+///
+///     fn paste(&self) -> clipp::Result<String> {
+///         Ok(self.0.lock().unwrap().clone())
+///     }
+/// }
+///
+/// let clip = clipp::Clipboard::new(Scratch(Mutex::default()));
+/// clip.copy("mine")?;
+/// assert_eq!(clip.paste()?, "mine");
+/// # Ok::<_, clipp::Error>(())
+/// ```
+pub trait Backend: Send + Sync {
+    /// Put this text on the clipboard.
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    fn copy(&self, text: &str) -> Result<()>;
+
+    /// Get the text on the clipboard. An empty clipboard should paste as an empty string.
+    ///
+    /// # Errors
+    ///
+    /// If pasting fails.
+    fn paste(&self) -> Result<String>;
+
+    /// Put what `r` reads on the clipboard, as it is read where the provider can take it that way.
+    /// Nothing at all is the same as copying an empty string.
+    ///
+    /// By default, this reads all of it and copies it as text, so it must be UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// If reading fails, with [`Error::Read`], it isn't UTF-8 and this only takes text,
+    /// with [`Error::Unsupported`], or copying fails.
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        let mut v = vec![];
+        r.read_to_end(&mut v).map_err(Error::Read)?;
+        self.copy(&String::from_utf8(v).map_err(|_| Error::Unsupported { what: TEXT_ONLY })?)
+    }
+
+    /// Write what is on the clipboard to `w`, as it comes where the provider can give it that way.
+    ///
+    /// By default, this pastes all of it and writes that.
+    ///
+    /// # Errors
+    ///
+    /// If pasting or writing fails.
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        Ok(w.write_all(self.paste()?.as_bytes())?)
+    }
+
+    /// Put `data` on the clipboard as `mime`, e.g. `text/html` or `image/png`,
+    /// for whatever pastes it to ask for.
+    ///
+    /// By default, this can't be done.
+    ///
+    /// # Errors
+    ///
+    /// If the clipboard can't do it, with [`Error::Unsupported`], or copying fails.
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        _ = (mime, data);
+        Err(Error::Unsupported {
+            what: "copying as a MIME type to this clipboard",
+        })
+    }
+
+    /// What is on the clipboard as `mime`.
+    ///
+    /// By default, this can't be done.
+    ///
+    /// # Errors
+    ///
+    /// If it isn't there as `mime`, with [`Error::FormatUnavailable`], the clipboard can't do it,
+    /// with [`Error::Unsupported`], or pasting fails.
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        _ = mime;
+        Err(Error::Unsupported {
+            what: "pasting as a MIME type from this clipboard",
+        })
+    }
+
+    /// Put each of `items` on the clipboard as its MIME type, all at once, the first being
+    /// the one preferred. Where they are all offered, they are listed in this order.
+    ///
+    /// By default, only one of them is copied: the first `text/*` one, or failing that the
+    /// first, as the helper programs offer one type at a time.
+    /// [`copies_multi`](Self::copies_multi) tells which it is.
+    ///
+    /// # Errors
+    ///
+    /// If the clipboard can't take the one it copies, with [`Error::Unsupported`],
+    /// or copying fails.
+    fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        one_of(self, items)
+    }
+
+    /// Whether [`copy_multi`](Self::copy_multi) offers all of what it is given,
+    /// rather than one of them. By default, it doesn't.
+    fn copies_multi(&self) -> bool {
+        false
+    }
+
+    /// Put `html` on the clipboard, with `alt_text` for whatever pastes it as text,
+    /// e.g. a terminal, through [`copy_multi`](Self::copy_multi).
+    ///
+    /// Where only one can be offered, it is `alt_text`: what only takes text would
+    /// get nothing from `html` alone. Use [`copy_mime`](Self::copy_mime) for that.
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    fn copy_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        if !self.copies_multi() {
+            return self.copy(alt_text);
+        }
+        self.copy_multi(&[
+            ("text/html", html.as_bytes()),
+            ("text/plain;charset=utf-8", alt_text.as_bytes()),
+        ])
+    }
+
+    /// Put `rtf` on the clipboard, with `alt_text` for whatever pastes it as text,
+    /// through [`copy_multi`](Self::copy_multi).
+    ///
+    /// Where only one can be offered, this can't be done, as what only takes text would
+    /// get RTF markup. Use [`copy_mime`](Self::copy_mime) with `text/rtf` for that.
+    ///
+    /// # Errors
+    ///
+    /// If the clipboard can't do it, with [`Error::Unsupported`], or copying fails.
+    fn copy_rtf(&self, rtf: &str, alt_text: &str) -> Result<()> {
+        if !self.copies_multi() {
+            return Err(Error::Unsupported {
+                what: "copying rich text alongside plain text to this clipboard",
+            });
+        }
+        self.copy_multi(&[
+            ("text/rtf", rtf.as_bytes()),
+            ("text/plain;charset=utf-8", alt_text.as_bytes()),
+        ])
+    }
+
+    /// Put `text` on the clipboard marked as a secret, e.g. a password, for clipboard managers
+    /// to keep out of their history.
+    ///
+    /// By default, it is offered alongside `x-kde-passwordManagerHint` of `secret`, which
+    /// klipper, gpaste and copyq look for, through [`copy_multi`](Self::copy_multi). Where only
+    /// one type can be offered, `text` is copied without it, see [`conceals`](Self::conceals).
+    ///
+    /// # Errors
+    ///
+    /// If copying fails.
+    fn copy_concealed(&self, text: &str) -> Result<()> {
+        if !self.conceals() {
+            return self.copy(text);
+        }
+        self.copy_multi(&[
+            ("text/plain;charset=utf-8", text.as_bytes()),
+            ("x-kde-passwordManagerHint", b"secret"),
+        ])
+    }
+
+    /// Whether [`copy_concealed`](Self::copy_concealed) marks what it copies as a secret,
+    /// rather than copying it like anything else. By default, it does where
+    /// [`copy_multi`](Self::copy_multi) offers all of what it is given.
+    fn conceals(&self) -> bool {
+        self.copies_multi()
+    }
+
+    /// Put these files on the clipboard, for a file manager to paste. They are all absolute.
+    ///
+    /// By default, they are copied as `text/uri-list`.
+    ///
+    /// # Errors
+    ///
+    /// If the clipboard can't do it, with [`Error::Unsupported`], or copying fails.
+    fn copy_files(&self, paths: &[&Path]) -> Result<()> {
+        self.copy_mime("text/uri-list", crate::uri::list(paths)?.as_bytes())
+    }
+
+    /// The files on the clipboard.
+    ///
+    /// By default, those in its `text/uri-list`.
+    ///
+    /// # Errors
+    ///
+    /// If there are none, with [`Error::FormatUnavailable`], the clipboard can't do it,
+    /// with [`Error::Unsupported`], or pasting fails.
+    fn paste_files(&self) -> Result<Vec<PathBuf>> {
+        let list = self.paste_mime("text/uri-list")?;
+        Ok(crate::uri::paths(&String::from_utf8_lossy(&list)))
+    }
+
+    /// The MIME types what is on the clipboard is offered as, which is none at all when it is empty.
+    ///
+    /// By default, this can't be told.
+    ///
+    /// # Errors
+    ///
+    /// If the clipboard can't tell, with [`Error::Unsupported`], or asking fails.
+    fn targets(&self) -> Result<Vec<String>> {
+        Err(Error::Unsupported {
+            what: "listing what is offered on this clipboard",
+        })
+    }
+
+    /// Empty the clipboard. By default, this copies an empty string.
+    ///
+    /// # Errors
+    ///
+    /// If clearing fails.
+    fn clear(&self) -> Result<()> {
+        self.copy("")
+    }
+
+    /// Make sure what was copied stays on the clipboard once this process has exited.
+    ///
+    /// By default this does nothing, as most clipboards need nothing doing: the system keeps
+    /// what was copied, or a program like `xclip` or `wl-copy` leaves a process of its own
+    /// holding it. Backends that hold it themselves hand it over here, which may block.
+    ///
+    /// # Errors
+    ///
+    /// If handing it over fails.
+    fn persist(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Something that changes whenever what is on the clipboard does, to tell whether
+    /// anything has been copied since it was last asked for.
+    ///
+    /// By default, this is a hash of what [`paste_to`](Self::paste_to) writes, which costs
+    /// as much as pasting does.
+    ///
+    /// # Errors
+    ///
+    /// If pasting, or asking for it, fails.
+    fn change_token(&self) -> Result<ChangeToken> {
+        hashed(self)
+    }
+
+    /// Told of each change to what is on the clipboard from now on, for
+    /// [`Clipboard::wait_for_change`](crate::Clipboard::wait_for_change) not to have to
+    /// ask for [`change_token`](Self::change_token) over and over.
+    ///
+    /// By default, and where there is no way to be told, there are none.
+    ///
+    /// # Errors
+    ///
+    /// If starting to watch fails.
+    fn changes(&self) -> Result<Option<Changes>> {
+        Ok(None)
+    }
+}
+
+/// Something that changes whenever what is on a clipboard does, see
+/// [`Clipboard::change_token`](crate::Clipboard::change_token). Two are equal if nothing
+/// was copied between asking for one and the other.
+///
+/// Where the system counts what is copied, asking for one is cheap:
+/// - the Windows clipboard has its sequence number,
+/// - macOS has the general pasteboard's `changeCount`, through `osascript`,
+/// - X11, through `xclip` or natively, has when the selection was taken, its `TIMESTAMP`.
+///
+/// Everywhere else it is a hash of what is pasted, which costs a whole paste, and doesn't
+/// change when the same thing is copied again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChangeToken(Token);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Token {
+    Counted(u64),
+    Hashed(u64),
+}
+
+impl ChangeToken {
+    /// For a [`Backend`] that counts what is copied, `n` being where that count is now.
+    #[must_use]
+    pub fn counted(n: u64) -> Self {
+        Self(Token::Counted(n))
+    }
+}
+
+/// Each change to what is on a clipboard, from [`Backend::changes`], until this is dropped.
+pub struct Changes {
+    rx: mpsc::Receiver<()>,
+    stop: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl Changes {
+    /// Each `()` sent to `rx` is a change. `stop` is run when this is dropped, to stop
+    /// watching for them.
+    pub fn new(rx: mpsc::Receiver<()>, stop: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            rx,
+            stop: Some(Box::new(stop)),
+        }
+    }
+
+    /// Whether there was a change within `timeout`.
+    pub(crate) fn next(&self, timeout: Duration) -> Result<bool> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(()) => Ok(true),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(false),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(io::Error::other("stopped being told of changes to the clipboard").into())
+            }
+        }
+    }
+}
+
+impl Drop for Changes {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
+
+impl std::fmt::Debug for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Changes").finish_non_exhaustive()
+    }
+}
+
+/// The [`ChangeToken`] of what `b` pastes, hashed as it comes.
+pub(crate) fn hashed(b: &(impl Backend + ?Sized)) -> Result<ChangeToken> {
+    struct Hashing(std::hash::DefaultHasher);
+    impl Write for Hashing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::hash::Hasher::write(&mut self.0, buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut h = Hashing(std::hash::DefaultHasher::new());
+    b.paste_to(&mut h)?;
+    Ok(ChangeToken(Token::Hashed(std::hash::Hasher::finish(&h.0))))
 }
 
 macro_rules! c {
@@ -22,211 +385,5065 @@ macro_rules! c {
     }
 }
 
-trait Eat {
-    fn eat(&mut self) -> io::Result<String>;
+fn name(c: &Command) -> String {
+    c.get_program().to_string_lossy().into_owned()
 }
 
-impl Eat for Command {
-    fn eat(&mut self) -> io::Result<String> {
-        let mut s = String::new();
-        self.stdout(Stdio::piped())
-            .spawn()?
-            .stdout
-            .take()
-            .expect("stdout")
-            .read_to_string(&mut s)?;
-        Ok(s)
+/// What a provider that only takes text can't do.
+pub(crate) const TEXT_ONLY: &str = "copying anything but UTF-8 text to this clipboard";
+
+/// Runs the helper programs providers are built on, so tests can stand in for them.
+pub trait Runner: Send + Sync {
+    /// Runs `c` until it exits, with `input` on its stdin, or nothing if [`None`].
+    /// Its stdout is only kept if `read`, and its stderr only if it failed.
+    ///
+    /// If it is still going after `timeout`, it is killed, and this fails with [`io::ErrorKind::TimedOut`].
+    fn output(
+        &self,
+        c: &mut Command,
+        input: Option<&[u8]>,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output>;
+
+    /// Like [`output`](Self::output), with what `input` reads on its stdin.
+    /// By default, all of it is read first.
+    fn output_from(
+        &self,
+        c: &mut Command,
+        input: &mut dyn Read,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        let v = WipeOnDrop::from(crate::wipe::read_to_end(&mut &mut *input)?);
+        self.output(c, Some(&v), read, timeout)
+    }
+
+    /// Like [`output`](Self::output), with its stdout written to `out` rather than kept.
+    /// By default, all of it is kept first, and only written if it was successful.
+    fn output_to(
+        &self,
+        c: &mut Command,
+        out: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        let mut o = self.output(c, None, true, timeout)?;
+        if o.status.success() {
+            out.write_all(&o.stdout)?;
+        }
+        o.stdout.wipe();
+        Ok(o)
+    }
+
+    /// Starts `c`, to go on running alongside, with its stdout piped and nothing on its stdin.
+    /// By default, it is spawned.
+    fn spawn(&self, c: &mut Command) -> io::Result<Child> {
+        c.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
     }
 }
 
-trait Put {
-    fn put(&mut self, s: impl AsRef<[u8]>) -> io::Result<()>;
+/// What becomes of a helper's stdout.
+enum Out<'a> {
+    Null,
+    Kept,
+    /// Written here as it comes.
+    To(&'a mut dyn Write),
 }
 
-impl Put for Command {
-    fn put(&mut self, s: impl AsRef<[u8]>) -> io::Result<()> {
-        let mut ch = self.stdin(Stdio::piped()).spawn()?;
-        ch.stdin.take().expect("stdin").write_all(s.as_ref())?;
-        ch.wait()?;
-        Ok(())
+/// Actually runs them.
+pub struct Real;
+impl Runner for Real {
+    fn output(
+        &self,
+        c: &mut Command,
+        mut input: Option<&[u8]>,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        let out = if read { Out::Kept } else { Out::Null };
+        Self::spawned(c, input.as_mut().map(|i| i as &mut dyn Read), out, timeout)
+    }
+
+    fn output_from(
+        &self,
+        c: &mut Command,
+        input: &mut dyn Read,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        let out = if read { Out::Kept } else { Out::Null };
+        Self::spawned(c, Some(input), out, timeout)
+    }
+
+    fn output_to(
+        &self,
+        c: &mut Command,
+        out: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Self::spawned(c, None, Out::To(out), timeout)
     }
 }
 
-#[cfg(target_os = "macos")]
-pub struct PbCopy {}
-#[cfg(target_os = "macos")]
-impl Clipboard for PbCopy {
-    fn copy(text: &str) -> io::Result<()> {
-        c!(pbcopy w).put(text)
+impl Real {
+    fn spawned(
+        c: &mut Command,
+        input: Option<&mut dyn Read>,
+        out: Out,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        // a paste command has no business reading stdin; if it tries, give it eof instead of hanging
+        let mut child = c
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(if matches!(out, Out::Null) {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (stdin, stdout, stderr) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take());
+        let ch = Reaper::new(child)?;
+        let ch = &ch.0;
+        // drained on the side, so that a helper with a lot to say can't fill a pipe
+        // and stall while we are still writing to it
+        let (stdout, to) = match out {
+            Out::Null => (None, None),
+            Out::Kept => (Some(drain(stdout.expect("stdout"))), None),
+            Out::To(w) => (None, Some((w, chunks(stdout.expect("stdout"))))),
+        };
+        let stderr = drain(stderr.expect("stderr"));
+        // from the start, so that one which never reads what it is given can't keep us writing
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut io = Ok(());
+        if let Some(input) = input {
+            io = feed(ch, stdin.expect("stdin"), input, deadline)?;
+        }
+        if let Some((w, chunks)) = to {
+            loop {
+                // a little at a time, where it could be cancelled meanwhile
+                let step = cancellable().then_some(Duration::from_millis(10));
+                let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                let chunk = match left.into_iter().chain(step).min() {
+                    None => chunks
+                        .recv()
+                        .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                    Some(t) => chunks.recv_timeout(t),
+                };
+                match chunk {
+                    // a failed write leaves it to the reaper too
+                    Ok(Ok(chunk)) => w.write_all(&chunk)?,
+                    Ok(Err(e)) => {
+                        io = io.and(Err(e));
+                        break;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) if cancelled() => {
+                        return Err(io::ErrorKind::Interrupted.into())
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout)
+                        if deadline.is_some_and(|d| Instant::now() >= d) =>
+                    {
+                        return Err(io::ErrorKind::TimedOut.into())
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        }
+        let status = match deadline {
+            // reap it before anything can bail
+            None if !cancellable() => lock(ch).wait()?,
+            // so that a helper that never finishes can't keep us waiting
+            d => wait(ch, d.map(|d| d.saturating_duration_since(Instant::now())))?,
+        };
+        let stdout = match stdout.map(|r| r.recv().expect("reader")) {
+            Some(Ok(mut v)) => std::mem::take(&mut *v),
+            Some(Err(e)) => {
+                io = io.and(Err(e));
+                vec![]
+            }
+            None => vec![],
+        };
+        let mut errors = vec![];
+        if status.success() {
+            // what it said doesn't matter, and whatever it left running may still hold on to it
+            io?;
+        } else if let Ok(Ok(mut v)) = stderr.recv() {
+            errors = std::mem::take(&mut *v);
+        }
+        Ok(Output {
+            status,
+            stdout,
+            stderr: errors,
+        })
     }
+}
 
-    fn paste() -> io::Result<String> {
-        c!(pbcopy r).eat()
+/// Writes all `input` reads to `ch`'s stdin, then closes it, for it to see the end of it.
+/// Fails if it's no use going on, and otherwise gives what to fail with once `ch` has finished.
+fn feed(
+    ch: &Arc<Mutex<Child>>,
+    mut stdin: ChildStdin,
+    input: &mut dyn Read,
+    deadline: Option<Instant>,
+) -> io::Result<io::Result<()>> {
+    let watchdog = deadline.map(|d| Watchdog::new(ch, d));
+    let mut buf = WipeOnDrop::from(vec![0; 64 * 1024]);
+    let written = loop {
+        // a failed read leaves it to the reaper
+        let n = match input.read(&mut buf) {
+            Ok(0) => break Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Err(e) = stdin.write_all(&buf[..n]) {
+            break Err(e);
+        }
+    };
+    drop(stdin);
+    if watchdog.is_some_and(Watchdog::fired) {
+        return Err(io::ErrorKind::TimedOut.into());
     }
+    if let Err(e) = written {
+        // it stopped reading: if that's because it gave up, what it says about it is more
+        // use, and otherwise there's no telling when it will finish
+        if wait(ch, Some(Duration::from_millis(100))).is_err() {
+            return Err(e);
+        }
+        return Ok(Err(e));
+    }
+    Ok(Ok(()))
+}
+
+/// Reads all of `r` on a thread of its own.
+fn drain(mut r: impl Read + Send + 'static) -> mpsc::Receiver<io::Result<WipeOnDrop<Vec<u8>>>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        _ = tx.send(crate::wipe::read_to_end(&mut r).map(WipeOnDrop::from));
+    });
+    rx
+}
+
+/// Reads `r` on a thread of its own, a chunk at a time, for them to be passed on as they come.
+/// Only so many are let pile up, so that a helper can't get far ahead of where they are going.
+fn chunks(mut r: impl Read + Send + 'static) -> mpsc::Receiver<io::Result<WipeOnDrop<Vec<u8>>>> {
+    let (tx, rx) = mpsc::sync_channel(16);
+    std::thread::spawn(move || loop {
+        let mut buf = WipeOnDrop::from(vec![0; 64 * 1024]);
+        let sent = match r.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                buf.truncate(n);
+                tx.send(Ok(buf))
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+            Err(e) => {
+                _ = tx.send(Err(e));
+                break;
+            }
+        };
+        if sent.is_err() {
+            break;
+        }
+    });
+    rx
 }
 
-pub struct XClip {}
-impl Clipboard for XClip {
-    fn copy(text: &str) -> io::Result<()> {
-        c!("xclip" "-selection" "c").put(text)
+/// Kills the helper if it is still going when dropped, and reaps it either way,
+/// so that no way out of [`Real::output`] leaves it running or a zombie.
+///
+/// On a thread that can be [cancelled](CANCEL), it is handed to what cancels it, to be
+/// killed from there, whatever this thread is stuck on meanwhile.
+struct Reaper(Arc<Mutex<Child>>);
+
+impl Reaper {
+    /// Fails with [`io::ErrorKind::Interrupted`], having killed it, if what it was started
+    /// for was already given up on.
+    fn new(ch: Child) -> io::Result<Self> {
+        let ch = Self(Arc::new(Mutex::new(ch)));
+        CANCEL.with_borrow(|cancel| match cancel {
+            Some(cancel) => cancel.running(&ch.0),
+            None => Ok(()),
+        })?;
+        Ok(ch)
     }
+}
 
-    fn paste() -> io::Result<String> {
-        c!("xclip" "-selection" "c" "-o") // xclip is complainy
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .eat() // If stdout is nulled does this work?
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        CANCEL.with_borrow(|cancel| {
+            if let Some(cancel) = cancel {
+                cancel.done();
+            }
+        });
+        let mut ch = lock(&self.0);
+        if !matches!(ch.try_wait(), Ok(Some(_))) {
+            _ = ch.kill();
+        }
+        _ = ch.wait();
     }
 }
 
-pub struct XSel {}
-impl Clipboard for XSel {
-    fn copy(text: &str) -> io::Result<()> {
-        c!("xsel" "-b" "-i").put(text)
+/// Kills a helper once its deadline has passed, for a write to it that is stuck to fail.
+struct Watchdog {
+    stop: mpsc::Sender<()>,
+    thread: std::thread::JoinHandle<bool>,
+}
+
+impl Watchdog {
+    fn new(ch: &Arc<Mutex<Child>>, deadline: Instant) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let ch = ch.clone();
+        let thread = std::thread::spawn(move || {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if stopped.recv_timeout(left) != Err(mpsc::RecvTimeoutError::Timeout) {
+                return false;
+            }
+            _ = lock(&ch).kill();
+            true
+        });
+        Self { stop, thread }
     }
 
-    fn paste() -> io::Result<String> {
-        c!("xsel" "-b" "-o").eat()
+    /// Stops it, and whether it had already gone off.
+    fn fired(self) -> bool {
+        _ = self.stop.send(());
+        self.thread.join().unwrap_or(true)
     }
 }
 
-struct Wayland {}
-impl Clipboard for Wayland {
-    fn copy(text: &str) -> io::Result<()> {
-        match text {
-            "" => c!("wl-copy" "-p" "--clear")
-                .status()?
-                .success()
-                .then_some(())
-                .ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        String::from("wl-copy was not successful"),
-                    )
-                }),
-            s => c!("wl-copy" "-p").put(s),
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Waits for `ch` to exit, killing it if it takes longer than `timeout`, or if what it is
+/// run for is [cancelled](CANCEL) meanwhile.
+fn wait(ch: &Mutex<Child>, timeout: Option<Duration>) -> io::Result<ExitStatus> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        if let Some(status) = lock(ch).try_wait()? {
+            return Ok(status);
         }
+        let gone = if deadline.is_some_and(|d| Instant::now() >= d) {
+            io::ErrorKind::TimedOut
+        } else if cancelled() {
+            io::ErrorKind::Interrupted
+        } else {
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        };
+        let mut ch = lock(ch);
+        _ = ch.kill();
+        _ = ch.wait();
+        return Err(gone.into());
     }
+}
 
-    fn paste() -> io::Result<String> {
-        c!("wl-paste" "-n" "-p").eat()
-    }
+thread_local! {
+    /// Set on the threads per-call timeouts run on, for the helper they are waiting on to be
+    /// killed once nothing is waiting for them.
+    pub(crate) static CANCEL: std::cell::RefCell<Option<Arc<Cancel>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Gives up on what a thread is running helpers for, from another.
+#[derive(Default)]
+pub(crate) struct Cancel {
+    /// The helper running meanwhile, or [`None`] once given up on.
+    running: Mutex<Option<Arc<Mutex<Child>>>>,
+    cancelled: std::sync::atomic::AtomicBool,
 }
 
-struct Klipper {}
-impl Clipboard for Klipper {
-    fn copy(text: &str) -> io::Result<()> {
-        c!("qdbus" "org.kde.klipper" "/klipper" "setClipboardContents").arg(text);
+impl Cancel {
+    /// Kills the helper running, if there is one, and reaps it before returning. Those that
+    /// would be started after are not.
+    pub(crate) fn cancel(&self) {
+        let mut running = lock(&self.running);
+        self.cancelled
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        if let Some(ch) = running.take() {
+            let mut ch = lock(&ch);
+            _ = ch.kill();
+            _ = ch.wait();
+        }
+    }
+
+    fn running(&self, ch: &Arc<Mutex<Child>>) -> io::Result<()> {
+        let mut running = lock(&self.running);
+        if self.is_cancelled() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        *running = Some(Arc::clone(ch));
         Ok(())
     }
 
-    fn paste() -> io::Result<String> {
-        let mut s = c!("qdbus" "org.kde.klipper" "/klipper" "getClipboardContents").eat()?;
-        assert!(s.ends_with('\n'));
-        s.truncate(s.len() - 1);
-        Ok(s)
+    fn done(&self) {
+        lock(&self.running).take();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// Whether helpers run on this thread can be [cancelled](CANCEL).
+fn cancellable() -> bool {
+    CANCEL.with_borrow(Option::is_some)
+}
+
+fn cancelled() -> bool {
+    CANCEL.with_borrow(|c| c.as_ref().is_some_and(|c| c.is_cancelled()))
+}
+
+/// The runner providers use unless told otherwise.
+pub(crate) fn real() -> Arc<dyn Runner> {
+    Arc::new(Real)
+}
+
+/// Runs the helper programs from where they were found when the provider was picked, rather
+/// than looking for them on PATH every time, which may have changed since.
+pub(crate) struct Resolved {
+    run: Arc<dyn Runner>,
+    found: Vec<(&'static str, PathBuf)>,
+}
+
+impl Resolved {
+    pub(crate) fn new(
+        run: Arc<dyn Runner>,
+        p: &impl Probe,
+        programs: impl IntoIterator<Item = &'static str>,
+    ) -> Self {
+        let found = programs
+            .into_iter()
+            .filter_map(|program| Some((program, p.find(program)?)))
+            .collect();
+        Self { run, found }
+    }
+
+    /// Runs `c` with `f`, from where its program was found. What wasn't is left to PATH.
+    fn there<T>(&self, c: &mut Command, f: impl FnOnce(&mut Command) -> T) -> T {
+        let Some((_, path)) = self.found.iter().find(|(p, _)| c.get_program() == *p) else {
+            return f(c);
+        };
+        let mut there = Command::new(path);
+        there.args(c.get_args());
+        for (key, value) in c.get_envs() {
+            match value {
+                Some(value) => there.env(key, value),
+                None => there.env_remove(key),
+            };
+        }
+        if let Some(dir) = c.get_current_dir() {
+            there.current_dir(dir);
+        }
+        f(&mut there)
     }
 }
 
-#[cfg(target_family = "windows")]
-struct Windows {}
-#[cfg(target_family = "windows")]
-impl Clipboard for Windows {
-    fn copy(text: &str) -> io::Result<()> {
-        clipboard_win::set_clipboard_string(text)?
+impl Runner for Resolved {
+    fn output(
+        &self,
+        c: &mut Command,
+        input: Option<&[u8]>,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        self.there(c, |c| self.run.output(c, input, read, timeout))
+    }
+
+    fn output_from(
+        &self,
+        c: &mut Command,
+        input: &mut dyn Read,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        self.there(c, |c| self.run.output_from(c, input, read, timeout))
     }
 
-    fn paste() -> io::Result<String> {
-        clipboard_win::get_clipboard_string()?
+    fn output_to(
+        &self,
+        c: &mut Command,
+        out: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        self.there(c, |c| self.run.output_to(c, out, timeout))
     }
+
+    fn spawn(&self, c: &mut Command) -> io::Result<Child> {
+        self.there(c, |c| self.run.spawn(c))
+    }
+}
+
+/// Runs `c` with `r`, telling a missing program, an unsuccessful exit, and one that never finishes
+/// apart from other failures.
+pub(crate) fn output(
+    r: &dyn Runner,
+    c: &mut Command,
+    input: Option<&[u8]>,
+    read: bool,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    let out = r.output(c, input, read, timeout);
+    checked(c, out)
+}
+
+/// What `c` printed, if it ran and was successful.
+fn checked(c: &Command, out: io::Result<Output>) -> Result<Vec<u8>> {
+    let mut out = out.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::CommandNotFound { command: name(c) },
+        io::ErrorKind::TimedOut => Error::TimedOut {
+            what: format!("waiting for {}", name(c)),
+        },
+        _ => Error::Io(e),
+    })?;
+    if !out.status.success() {
+        out.stdout.wipe();
+        return Err(Error::CommandFailed {
+            command: name(c),
+            status: out.status.code(),
+            stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+        });
+    }
+    Ok(out.stdout)
+}
+
+/// Whether this error is a helper failing with `msg` somewhere in its stderr.
+fn said(e: &Error, msg: &str) -> bool {
+    matches!(e, Error::CommandFailed { stderr, .. } if stderr.contains(msg))
+}
+
+pub(crate) trait Eat {
+    fn eat_within(&mut self, r: &dyn Runner, timeout: Option<Duration>) -> Result<String>;
+
+    /// Writes what it prints to `w` as it comes, whether it is text or not.
+    fn eat_to(
+        &mut self,
+        r: &dyn Runner,
+        w: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> Result<()>;
 }
 
-struct Wsl {}
+impl Eat for Command {
+    fn eat_within(&mut self, r: &dyn Runner, timeout: Option<Duration>) -> Result<String> {
+        String::from_utf8(output(r, self, None, true, timeout)?).map_err(|e| {
+            e.into_bytes().wipe();
+            Error::InvalidUtf8
+        })
+    }
 
-impl Clipboard for Wsl {
-    fn copy(text: &str) -> io::Result<()> {
-        c!("clip.exe").put(text)
+    fn eat_to(
+        &mut self,
+        r: &dyn Runner,
+        w: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let out = r.output_to(self, w, timeout);
+        checked(self, out).map(drop)
     }
+}
+
+pub(crate) trait Put {
+    fn put_within(
+        &mut self,
+        r: &dyn Runner,
+        s: impl AsRef<[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<()>;
+}
 
-    fn paste() -> io::Result<String> {
-        let mut s = c!("powershell.exe" "-noprofile" "-command" "Get-Clipboard").eat()?;
-        s.truncate(s.len() - 2); // \r\n
-        Ok(s)
+impl Put for Command {
+    fn put_within(
+        &mut self,
+        r: &dyn Runner,
+        s: impl AsRef<[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        output(r, self, Some(s.as_ref()), false, timeout).map(drop)
     }
 }
 
-pub type Board = (
-    for<'a> fn(&'a str) -> io::Result<()>,
-    fn() -> io::Result<String>,
-);
+/// Streams what `input` reads into `c`, telling its read errors apart from `c`'s.
+fn put_from(
+    r: &dyn Runner,
+    c: &mut Command,
+    input: &mut dyn Read,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    /// Keeps hold of the error it failed to read with, for there to be no mistaking it.
+    struct Source<'a>(&'a mut dyn Read, Option<io::Error>);
+    impl Read for Source<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf).map_err(|e| {
+                let kind = e.kind();
+                if kind != io::ErrorKind::Interrupted {
+                    self.1 = Some(e);
+                }
+                kind.into()
+            })
+        }
+    }
+    let mut source = Source(input, None);
+    let out = r.output_from(c, &mut source, false, timeout);
+    match source.1 {
+        Some(e) => Err(Error::Read(e)),
+        None => checked(c, out).map(drop),
+    }
+}
 
-fn get<T: Clipboard>() -> Board {
-    (T::copy, T::paste)
+trait Run {
+    fn run_within(&mut self, r: &dyn Runner, timeout: Option<Duration>) -> Result<()>;
 }
 
-fn has(c: &str) -> bool {
-    c!("which")
-        .arg(c)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .ok()
-        .map_or(false, |status| status.success())
+impl Run for Command {
+    fn run_within(&mut self, r: &dyn Runner, timeout: Option<Duration>) -> Result<()> {
+        output(r, self, None, false, timeout).map(drop)
+    }
 }
 
-fn wsl() -> bool {
-std::fs::read_to_string("/proc/version").map_or(false, |s| 
-        s.to_lowercase().contains("microsoft")
-)
+#[cfg(target_os = "macos")]
+pub struct PbCopy {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    pasteboard: crate::macos::Pasteboard,
 }
 
-pub fn provide() -> io::Result<Board> {
-    #[cfg(target_family = "windows")]
-    return get::<Windows>();
-    #[cfg(target_os = "macos")]
-    return get::<PbCopy>();
-
-    if wsl() {
-        return Ok(get::<Wsl>());
-    }
-    if std::env::var_os("WAYLAND_DISPLAY").is_some() && has("wl-copy") {
-        Ok(get::<Wayland>())
-    } else if has("xsel") {
-        Ok(get::<XSel>())
-    } else if has("xclip") {
-        Ok(get::<XClip>())
-    } else if has("klipper") && has("qdbus") {
-        Ok(get::<Klipper>())
-    } else {
-        Err(io::Error::new(io::ErrorKind::Other, String::from("no clipboard provided available")))
+#[cfg(target_os = "macos")]
+impl PbCopy {
+    pub(crate) fn new(run: Arc<dyn Runner>, pasteboard: crate::macos::Pasteboard) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            pasteboard,
+        }
+    }
+
+    /// `program`, on its pasteboard.
+    fn pb(&self, program: &str) -> Command {
+        let mut c = Command::new(program);
+        if self.pasteboard != crate::macos::Pasteboard::General {
+            c.args(["-pboard", self.pasteboard.name()]);
+        }
+        c
+    }
+
+    /// The scripts only know the general pasteboard.
+    fn general(&self) -> Result<()> {
+        if self.pasteboard == crate::macos::Pasteboard::General {
+            return Ok(());
+        }
+        Err(Error::Unsupported {
+            what: "anything but text on pasteboards other than the general one",
+        })
     }
 }
 
-#[test]
-fn test() {
-    macro_rules! test {
-        ($clipboard:ty) => {
-            <$clipboard>::copy("text");
-            assert_eq!(<$clipboard>::paste().unwrap(), "text");
-            <$clipboard>::copy("");
+#[cfg(target_os = "macos")]
+impl Backend for PbCopy {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.pb("pbcopy")
+            .put_within(&*self.run, text, Some(self.timeout))
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        put_from(&*self.run, &mut self.pb("pbcopy"), r, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        self.pb("pbpaste")
+            .eat_within(&*self.run, Some(self.timeout))
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.pb("pbpaste").eat_to(&*self.run, w, Some(self.timeout))
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.pb("pbcopy")
+            .put_within(&*self.run, "", Some(self.timeout))
+    }
+
+    /// Text, HTML and RTF, through `osascript`, as `pbcopy` only copies text.
+    fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        self.general()?;
+        let mut c = Command::new("osascript");
+        c.args(["-l", "JavaScript", "-e", COPY_TYPES]);
+        for &(mime, data) in items {
+            let ty = match mime {
+                mime if is_text(mime) => "public.utf8-plain-text",
+                "text/html" => "public.html",
+                "text/rtf" => "public.rtf",
+                CONCEALED => CONCEALED,
+                _ => {
+                    return Err(Error::Unsupported {
+                        what: "copying anything but text, HTML or RTF to this clipboard",
+                    })
+                }
+            };
+            let text =
+                std::str::from_utf8(data).map_err(|_| Error::Unsupported { what: TEXT_ONLY })?;
+            c.args([ty, text]);
+        }
+        c.run_within(&*self.run, Some(self.timeout))
+    }
+
+    fn copies_multi(&self) -> bool {
+        self.pasteboard == crate::macos::Pasteboard::General
+    }
+
+    /// With `org.nspasteboard.ConcealedType`, which macOS clipboard managers look for.
+    fn copy_concealed(&self, text: &str) -> Result<()> {
+        if !self.conceals() {
+            return self.copy(text);
+        }
+        self.copy_multi(&[("text/plain", text.as_bytes()), (CONCEALED, b"")])
+    }
+
+    /// What is there as text, HTML or RTF, through `osascript` for the last two.
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        if mime == "text/plain" {
+            return self.paste().map(String::into_bytes);
+        }
+        self.general()?;
+        let ty = match mime {
+            "text/html" => "public.html",
+            "text/rtf" => "public.rtf",
+            _ => {
+                return Err(Error::Unsupported {
+                    what: "pasting anything but text, HTML or RTF from this clipboard",
+                })
+            }
         };
+        let mut c = Command::new("osascript");
+        c.args(["-l", "JavaScript", "-e", PASTE_TYPE, ty]);
+        match output(&*self.run, &mut c, None, true, Some(self.timeout)) {
+            Err(e) if said(&e, "nothing as") => Err(Error::FormatUnavailable { mime: mime.into() }),
+            // osascript ends what it prints with a newline of its own
+            Ok(mut v) if v.last() == Some(&b'\n') => {
+                v.pop();
+                Ok(v)
+            }
+            r => r,
+        }
     }
-    #[cfg(target_os = "macos")]
-    test!(PbCopy);
-    #[cfg(target_os = "linux")]
-    test!(XClip);
-    #[cfg(target_os = "linux")]
-    test!(XSel);
-    #[cfg(target_os = "linux")]
-    if std::env::var("WAYLAND_DISPLAY").is_ok() {
-        test!(Wayland);
+
+    /// The general pasteboard's `changeCount`, through `osascript`.
+    fn change_token(&self) -> Result<ChangeToken> {
+        if self.general().is_err() {
+            return hashed(self);
+        }
+        let mut c = Command::new("osascript");
+        c.args(["-l", "JavaScript", "-e", CHANGE_COUNT]);
+        let count = c.eat_within(&*self.run, Some(self.timeout))?;
+        let count = count.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{count:?} is not a change count"),
+            )
+        })?;
+        Ok(ChangeToken::counted(count))
     }
-    #[cfg(target_os = "linux")]
-    test!(Klipper);
-    #[cfg(target_family = "windows")]
-    test!(Windows);
-    if wsl() {
-        #[cfg(target_os = "linux")]
-        test!(Wsl);
+}
+
+/// Prints how many times the general pasteboard has changed.
+#[cfg(target_os = "macos")]
+const CHANGE_COUNT: &str = "ObjC.import('AppKit');
+function run() {
+    return $.NSPasteboard.generalPasteboard.changeCount;
+}";
+
+/// What marks a secret on the pasteboard, see <http://nspasteboard.org>.
+#[cfg(target_os = "macos")]
+const CONCEALED: &str = "org.nspasteboard.ConcealedType";
+
+/// Puts each type and string it is run with on the pasteboard.
+#[cfg(target_os = "macos")]
+const COPY_TYPES: &str = "ObjC.import('AppKit');
+function run(argv) {
+    const pb = $.NSPasteboard.generalPasteboard;
+    pb.clearContents;
+    for (let i = 0; i + 1 < argv.length; i += 2) {
+        pb.setStringForType($(argv[i + 1]), $(argv[i]));
+    }
+}";
+
+/// Prints what is on the pasteboard as the type it is run with.
+#[cfg(target_os = "macos")]
+const PASTE_TYPE: &str = "ObjC.import('AppKit');
+function run(argv) {
+    const s = $.NSPasteboard.generalPasteboard.stringForType($(argv[0]));
+    if (s.isNil()) throw new Error('nothing as ' + argv[0]);
+    return s.js;
+}";
+
+/// Haiku's own `clipboard` command.
+#[cfg(target_os = "haiku")]
+struct Haiku(Arc<dyn Runner>);
+#[cfg(target_os = "haiku")]
+impl Backend for Haiku {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("clipboard" "-c").put_within(&*self.0, text, Some(crate::Options::TIMEOUT))
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("clipboard" "-p").eat_within(&*self.0, Some(crate::Options::TIMEOUT))
+    }
+}
+
+/// xclip and xsel wait forever on an X server that has gone away, e.g. a stale SSH forward,
+/// so they are given [`Options::timeout`](crate::Options::timeout) to finish in.
+pub struct XClip {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    selection: Selection,
+}
+
+impl XClip {
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            selection: Selection::Clipboard,
+        }
+    }
+
+    /// `xclip`, on its selection, with `args`.
+    fn xclip(&self, args: &[&str]) -> Command {
+        let sel = match self.selection {
+            Selection::Clipboard => "c",
+            Selection::Primary => "primary",
+            Selection::Secondary => "secondary",
+        };
+        let mut c = Command::new("xclip");
+        c.args(["-selection", sel]).args(args);
+        c
+    }
+}
+
+impl Backend for XClip {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.xclip(&[])
+            .put_within(&*self.run, text, Some(self.timeout))
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        put_from(&*self.run, &mut self.xclip(&[]), r, Some(self.timeout))
+    }
+
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.xclip(&["-t", mime])
+            .put_within(&*self.run, data, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        match self
+            .xclip(&["-o"])
+            .eat_within(&*self.run, Some(self.timeout))
+        {
+            // "Error: target STRING not available", when nothing owns the clipboard
+            Err(e) if said(&e, "not available") => Ok(String::new()),
+            r => r,
+        }
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        match self
+            .xclip(&["-o"])
+            .eat_to(&*self.run, w, Some(self.timeout))
+        {
+            Err(e) if said(&e, "not available") => Ok(()),
+            r => r,
+        }
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        match output(
+            &*self.run,
+            &mut self.xclip(&["-o", "-t", mime]),
+            None,
+            true,
+            Some(self.timeout),
+        ) {
+            // "Error: target text/html not available"
+            Err(e) if said(&e, "not available") => {
+                Err(Error::FormatUnavailable { mime: mime.into() })
+            }
+            r => r,
+        }
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        match self
+            .xclip(&["-o", "-t", "TARGETS"])
+            .eat_within(&*self.run, Some(self.timeout))
+        {
+            // when nothing owns it
+            Err(e) if said(&e, "not available") => Ok(vec![]),
+            r => r.map(|list| targets(&list)),
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.copy("")
+    }
+
+    /// When the selection was taken, its `TIMESTAMP`, as xclip prints it: as it came.
+    fn change_token(&self) -> Result<ChangeToken> {
+        match output(
+            &*self.run,
+            &mut self.xclip(&["-o", "-t", "TIMESTAMP"]),
+            None,
+            true,
+            Some(self.timeout),
+        ) {
+            // nothing owns it, or what does won't say
+            Err(e) if said(&e, "not available") => hashed(self),
+            Ok(stamp) if stamp.is_empty() => hashed(self),
+            Ok(stamp) => {
+                let mut n = [0; 8];
+                let len = stamp.len().min(n.len());
+                n[..len].copy_from_slice(&stamp[..len]);
+                Ok(ChangeToken::counted(u64::from_le_bytes(n)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn changes(&self) -> Result<Option<Changes>> {
+        clipnotify(self.run.clone(), self.selection)
+    }
+}
+
+pub struct XSel {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    selection: Selection,
+}
+
+impl XSel {
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            selection: Selection::Clipboard,
+        }
+    }
+
+    /// `xsel`, on its selection, doing `what`.
+    fn xsel(&self, what: &str) -> Command {
+        let sel = match self.selection {
+            Selection::Clipboard => "-b",
+            Selection::Primary => "-p",
+            Selection::Secondary => "-s",
+        };
+        let mut c = Command::new("xsel");
+        c.args([sel, what]);
+        c
+    }
+}
+
+impl Backend for XSel {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.xsel("-i")
+            .put_within(&*self.run, text, Some(self.timeout))
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        put_from(&*self.run, &mut self.xsel("-i"), r, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        self.xsel("-o").eat_within(&*self.run, Some(self.timeout))
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.xsel("-o").eat_to(&*self.run, w, Some(self.timeout))
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.xsel("-c").run_within(&*self.run, Some(self.timeout))
+    }
+
+    fn changes(&self) -> Result<Option<Changes>> {
+        clipnotify(self.run.clone(), self.selection)
+    }
+}
+
+/// `clipnotify`, where it is installed, which exits when `selection` changes, as XFIXES
+/// tells it: run again after each. What is copied before it is running again goes unseen.
+///
+/// One too old to take `-s` ignores it, and watches both the clipboard and primary.
+fn clipnotify(run: Arc<dyn Runner>, selection: Selection) -> Result<Option<Changes>> {
+    let sel = match selection {
+        Selection::Clipboard => "clipboard",
+        Selection::Primary => "primary",
+        Selection::Secondary => "secondary",
+    };
+    let notify = move || run.spawn(Command::new("clipnotify").args(["-s", sel]));
+    let child = match notify() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        r => r?,
+    };
+    // none once stopped
+    let child = Arc::new(Mutex::new(Some(child)));
+    let (tx, rx) = mpsc::channel();
+    let waiting = child.clone();
+    std::thread::spawn(move || loop {
+        let lock = || waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(mut stdout) = lock().as_mut().and_then(|c| c.stdout.take()) else {
+            return;
+        };
+        // until it exits, without holding the lock, for it to be killed meanwhile
+        _ = io::copy(&mut stdout, &mut io::sink());
+        let mut child = lock();
+        let Some(c) = child.as_mut() else {
+            return;
+        };
+        // killed, or it couldn't watch, e.g. without a display
+        if !c.wait().is_ok_and(|s| s.success()) || tx.send(()).is_err() {
+            *child = None;
+            return;
+        }
+        *child = notify().ok();
+    });
+    Ok(Some(Changes::new(rx, move || {
+        let taken = child.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(mut c) = taken {
+            _ = c.kill();
+            _ = c.wait();
+        }
+    })))
+}
+
+struct Wayland {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    selection: Selection,
+}
+
+impl Wayland {
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            selection: Selection::Clipboard,
+        }
+    }
+
+    /// `program`, on its selection.
+    fn wl(&self, program: &str) -> Command {
+        let mut c = Command::new(program);
+        if self.selection == Selection::Primary {
+            c.arg("--primary");
+        }
+        c
+    }
+}
+
+impl Backend for Wayland {
+    fn copy(&self, text: &str) -> Result<()> {
+        match text {
+            "" => self.clear(),
+            s => self
+                .wl("wl-copy")
+                .put_within(&*self.run, s, Some(self.timeout)),
+        }
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        let mut r = io::BufReader::new(r);
+        match r.fill_buf() {
+            Ok([]) => self.clear(),
+            Ok(_) => put_from(
+                &*self.run,
+                &mut self.wl("wl-copy"),
+                &mut r,
+                Some(self.timeout),
+            ),
+            Err(e) => Err(Error::Read(e)),
+        }
+    }
+
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.wl("wl-copy")
+            .args(["--type", mime])
+            .put_within(&*self.run, data, Some(self.timeout))
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        match self
+            .wl("wl-paste")
+            .arg("--list-types")
+            .eat_within(&*self.run, Some(self.timeout))
+        {
+            Err(e) if said(&e, "Nothing is copied") || said(&e, "No selection") => Ok(vec![]),
+            r => r.map(|list| targets(&list)),
+        }
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        // without -n, which would take a newline off the end of an image as readily as text
+        match output(
+            &*self.run,
+            self.wl("wl-paste").args(["--type", mime]),
+            None,
+            true,
+            Some(self.timeout),
+        ) {
+            Err(e)
+                if said(&e, "No suitable type")
+                    || said(&e, "Nothing is copied")
+                    || said(&e, "No selection") =>
+            {
+                Err(Error::FormatUnavailable { mime: mime.into() })
+            }
+            r => r,
+        }
+    }
+
+    fn paste(&self) -> Result<String> {
+        match self
+            .wl("wl-paste")
+            .arg("-n")
+            .eat_within(&*self.run, Some(self.timeout))
+        {
+            // wl-paste considers a cleared clipboard an error
+            Err(e) if said(&e, "Nothing is copied") || said(&e, "No selection") => {
+                Ok(String::new())
+            }
+            r => r,
+        }
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        match self
+            .wl("wl-paste")
+            .arg("-n")
+            .eat_to(&*self.run, w, Some(self.timeout))
+        {
+            Err(e) if said(&e, "Nothing is copied") || said(&e, "No selection") => Ok(()),
+            r => r,
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.wl("wl-copy")
+            .arg("--clear")
+            .run_within(&*self.run, Some(self.timeout))
+    }
+
+    /// `wl-paste --watch`, which runs what it is given each time something is copied, or
+    /// the clipboard is cleared, and once to begin with, which is skipped.
+    fn changes(&self) -> Result<Option<Changes>> {
+        let mut c = self.wl("wl-paste");
+        c.args(["--watch", "sh", "-c", "cat >/dev/null; echo"]);
+        let mut child = self.run.spawn(&mut c).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::CommandNotFound { command: name(&c) },
+            _ => Error::Io(e),
+        })?;
+        let stdout = child.stdout.take().expect("stdout");
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for _ in io::BufReader::new(stdout)
+                .lines()
+                .map_while(io::Result::ok)
+                .skip(1)
+            {
+                if tx.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Some(Changes::new(rx, move || {
+            _ = child.kill();
+            _ = child.wait();
+        })))
+    }
+}
+
+/// What qdbus is installed as from one distro to the next, then `dbus-send`,
+/// which nearly every desktop has.
+const KLIPPER_TOOLS: &[&str] = &["qdbus", "qdbus6", "qdbus-qt6", "qdbus-qt5", "dbus-send"];
+
+/// The longest argument linux takes, less room for `string:`.
+const MAX_ARG: usize = 128 * 1024 - 8;
+
+struct Klipper {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    tool: &'static str,
+}
+
+impl Klipper {
+    /// With whichever qdbus is installed, or `dbus-send`.
+    fn new(run: Arc<dyn Runner>, p: &impl Probe) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            tool: p.which(KLIPPER_TOOLS).unwrap_or(KLIPPER_TOOLS[0]),
+        }
+    }
+
+    /// Calls one of klipper's methods, with the text if there is any.
+    fn call(&self, method: &str, text: Option<&str>) -> Command {
+        if self.tool == "dbus-send" {
+            let mut c = Command::new("dbus-send");
+            c.args([
+                "--session",
+                "--print-reply",
+                "--dest=org.kde.klipper",
+                "/klipper",
+            ])
+            .arg(format!("org.kde.klipper.klipper.{method}"))
+            // taken as it is, whatever quotes or commas are in it
+            .args(text.map(|t| format!("string:{t}")));
+            c
+        } else {
+            let mut c = Command::new(self.tool);
+            c.args(["org.kde.klipper", "/klipper", method]).args(text);
+            c
+        }
+    }
+}
+
+impl Backend for Klipper {
+    fn copy(&self, text: &str) -> Result<()> {
+        // it goes as an argument, which can only be so long
+        if text.len() > MAX_ARG {
+            return Err(Error::TooLong {
+                len: text.len(),
+                max: MAX_ARG,
+            });
+        }
+        self.call("setClipboardContents", Some(text))
+            .run_within(&*self.run, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        let out = self
+            .call("getClipboardContents", None)
+            .eat_within(&*self.run, Some(self.timeout))?;
+        if self.tool == "dbus-send" {
+            unquote(&out)
+        } else {
+            Ok(chomp(out))
+        }
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        // its quoting can't be undone until it is all there
+        if self.tool == "dbus-send" {
+            return Ok(w.write_all(self.paste()?.as_bytes())?);
+        }
+        let mut w = Chomped::new(w);
+        self.call("getClipboardContents", None)
+            .eat_to(&*self.run, &mut w, Some(self.timeout))?;
+        Ok(w.finish().map(drop)?)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.call("clearClipboardContents", None)
+            .run_within(&*self.run, Some(self.timeout))
+    }
+}
+
+/// The string in what `dbus-send --print-reply` prints: a line about the reply, then
+/// `   string "..."`, with any quotes or newlines in it left as they are.
+fn unquote(reply: &str) -> Result<String> {
+    reply
+        .split_once('\n')
+        .and_then(|(_, s)| s.strip_prefix("   string \"")?.strip_suffix("\"\n"))
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("dbus-send's reply was not a string: {reply:?}"),
+            )
+            .into()
+        })
+}
+
+struct GPaste {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+}
+
+impl Backend for GPaste {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("gpaste-client" "add").put_within(&*self.run, text, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        match c!("gpaste-client" "get" "0").eat_within(&*self.run, Some(self.timeout)) {
+            // there is no entry 0 while the history is empty
+            Err(_) if self.empty() => Ok(String::new()),
+            // it adds a newline of its own
+            r => r.map(chomp),
+        }
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        let mut w = Chomped::new(w);
+        match c!("gpaste-client" "get" "0").eat_to(&*self.run, &mut w, Some(self.timeout)) {
+            Err(_) if self.empty() => Ok(()),
+            r => r.and_then(|()| Ok(w.finish().map(drop)?)),
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        match c!("gpaste-client" "delete" "0").run_within(&*self.run, Some(self.timeout)) {
+            Err(_) if self.empty() => Ok(()),
+            r => r,
+        }
+    }
+}
+
+impl GPaste {
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+        }
+    }
+
+    fn empty(&self) -> bool {
+        c!("gpaste-client" "history-size")
+            .eat_within(&*self.run, Some(self.timeout))
+            .is_ok_and(|n| n.trim() == "0")
+    }
+}
+
+/// `copyq`'s scripting interface, which passes what it is given along as it is.
+struct CopyQ(Arc<dyn Runner>);
+
+impl CopyQ {
+    /// Its commands go through the server, and can wait a long while when there isn't one.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+}
+
+impl Backend for CopyQ {
+    fn copy(&self, text: &str) -> Result<()> {
+        // over stdin, so the text doesn't show up in anyone's `ps`
+        c!("copyq" "copy" "-")
+            .put_within(&*self.0, text, Some(Self::TIMEOUT))
+            .map_err(server)
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        put_from(&*self.0, c!("copyq" "copy" "-"), r, Some(Self::TIMEOUT)).map_err(server)
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        c!("copyq" "clipboard" "?")
+            .eat_within(&*self.0, Some(Self::TIMEOUT))
+            .map(|list| targets(&list))
+            .map_err(server)
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        // what isn't there pastes as nothing, just as something empty would
+        if !self.targets()?.iter().any(|t| t == mime) {
+            return Err(Error::FormatUnavailable { mime: mime.into() });
+        }
+        output(
+            &*self.0,
+            c!("copyq" "clipboard").arg(mime),
+            None,
+            true,
+            Some(Self::TIMEOUT),
+        )
+        .map_err(server)
+    }
+
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        c!("copyq" "copy")
+            .args([mime, "-"])
+            .put_within(&*self.0, data, Some(Self::TIMEOUT))
+            .map_err(server)
+    }
+
+    /// With the hint that copyq itself, as well as klipper, leaves out of its history.
+    fn copy_concealed(&self, text: &str) -> Result<()> {
+        c!("copyq" "copy" "text/plain" "-" "x-kde-passwordManagerHint" "secret")
+            .put_within(&*self.0, text, Some(Self::TIMEOUT))
+            .map_err(server)
+    }
+
+    fn conceals(&self) -> bool {
+        true
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("copyq" "clipboard")
+            .eat_within(&*self.0, Some(Self::TIMEOUT))
+            .map_err(server)
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        c!("copyq" "clipboard")
+            .eat_to(&*self.0, w, Some(Self::TIMEOUT))
+            .map_err(server)
+    }
+}
+
+/// Points out what is most likely missing when copyq doesn't answer.
+fn server(e: Error) -> Error {
+    match e {
+        Error::TimedOut { what } => Error::TimedOut {
+            what: format!(
+                "{what}, start its server with `copyq --start-server` if it is not running"
+            ),
+        },
+        e => e,
+    }
+}
+
+/// kitty's clipboard, through `kitten clipboard`, which reaches it over SSH too.
+struct Kitty {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    /// `kitten`, or `kitty` from before there was one.
+    tool: &'static str,
+}
+
+impl Kitty {
+    fn new(run: Arc<dyn Runner>, p: &impl Probe) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            tool: p.which(&["kitten", "kitty"]).unwrap_or("kitten"),
+        }
+    }
+
+    fn clipboard(&self) -> Command {
+        let mut c = Command::new(self.tool);
+        if self.tool == "kitty" {
+            c.arg("+kitten");
+        }
+        c.arg("clipboard");
+        c
+    }
+}
+
+impl Backend for Kitty {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.clipboard()
+            .put_within(&*self.run, text, Some(self.timeout))
+    }
+
+    fn paste(&self) -> Result<String> {
+        match self
+            .clipboard()
+            .arg("--get-clipboard")
+            .eat_within(&*self.run, Some(self.timeout))
+        {
+            // kitty.conf's clipboard_control leaves reading out, or whoever was asked said no
+            Err(e) if said(&e, "denied") => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "kitty did not allow reading the clipboard, add read-clipboard to clipboard_control in kitty.conf",
+            )
+            .into()),
+            r => r,
+        }
+    }
+}
+
+/// How long to give a clipboard on another machine, which may well be unreachable.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// lemonade, which shares a clipboard over the network.
+struct Lemonade(Arc<dyn Runner>);
+
+impl Backend for Lemonade {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("lemonade" "copy").put_within(&*self.0, text, Some(NETWORK_TIMEOUT))
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("lemonade" "paste").eat_within(&*self.0, Some(NETWORK_TIMEOUT))
+    }
+}
+
+/// piknik, which shares a clipboard over the network.
+struct Piknik(Arc<dyn Runner>);
+
+impl Backend for Piknik {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("piknik" "-copy").put_within(&*self.0, text, Some(NETWORK_TIMEOUT))
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("piknik" "-paste").eat_within(&*self.0, Some(NETWORK_TIMEOUT))
+    }
+}
+
+/// plan9port's snarf buffer, through its `9p` command.
+struct Snarf {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+}
+
+impl Snarf {
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+        }
+    }
+}
+
+impl Backend for Snarf {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("9" "9p" "write" "snarf")
+            .put_within(&*self.run, text, Some(self.timeout))
+            .map_err(unmounted)
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("9" "9p" "read" "snarf")
+            .eat_within(&*self.run, Some(self.timeout))
+            .map_err(unmounted)
+    }
+}
+
+/// `9p` fails when there is no devdraw or plumber serving the snarf buffer.
+fn unmounted(e: Error) -> Error {
+    match e {
+        Error::CommandFailed { stderr, .. } => io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("snarf buffer unavailable: {}", stderr.trim()),
+        )
+        .into(),
+        e => e,
+    }
+}
+
+struct Termux(Arc<dyn Runner>);
+
+impl Termux {
+    /// Without the Termux:API app, its commands wait for it forever.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+}
+
+impl Backend for Termux {
+    fn copy(&self, text: &str) -> Result<()> {
+        c!("termux-clipboard-set")
+            .put_within(&*self.0, text, Some(Self::TIMEOUT))
+            .map_err(app)
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("termux-clipboard-get")
+            .eat_within(&*self.0, Some(Self::TIMEOUT))
+            .map_err(app)
+    }
+}
+
+/// Points out what is most likely missing when termux-api doesn't answer.
+fn app(e: Error) -> Error {
+    match e {
+        Error::TimedOut { what } => Error::TimedOut {
+            what: format!("{what}, install the Termux:API app if it is not"),
+        },
+        e => e,
+    }
+}
+
+/// Cygwin's `/dev/clipboard`, which is the Windows clipboard.
+///
+/// Cygwin converts between UTF-8 and the clipboard's UTF-16, but leaves line endings alone,
+/// so as with the `Windows` provider what is pasted is what was copied.
+struct CygwinDev(PathBuf);
+
+impl CygwinDev {
+    fn new() -> Self {
+        Self("/dev/clipboard".into())
+    }
+}
+
+impl Backend for CygwinDev {
+    fn copy(&self, text: &str) -> Result<()> {
+        let mut f = std::fs::File::create(&self.0)?;
+        // a write of nothing still empties it, so make sure one happens
+        match text {
+            "" => f.write(b"").map(drop)?,
+            text => f.write_all(text.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        String::from_utf8(std::fs::read(&self.0)?).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+/// The clipboard itself, with the Win32 API, through clipboard-win. Text is `CF_UNICODETEXT`,
+/// which clipboard-win converts to and from UTF-16; it also closes the clipboard and frees
+/// what it allocated however things go.
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+struct Windows;
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+impl Backend for Windows {
+    fn copy(&self, text: &str) -> Result<()> {
+        held(|| clipboard_win::set_clipboard_string(text))
+    }
+
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.copy_multi(&[(mime, data)])
+    }
+
+    /// Each as the format other programs know it by, where there is one, and otherwise one
+    /// registered under the MIME type's own name.
+    fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        let formats = items
+            .iter()
+            .map(|&(mime, data)| format(mime, data))
+            .collect::<Result<Vec<_>>>()?;
+        held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            clipboard_win::raw::empty()?;
+            for (format, data) in &formats {
+                clipboard_win::raw::set_without_clear(*format, data)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn copies_multi(&self) -> bool {
+        true
+    }
+
+    /// With the formats that keep it out of the clipboard history and cloud clipboard,
+    /// and away from clipboard managers.
+    fn copy_concealed(&self, text: &str) -> Result<()> {
+        self.copy_multi(&[
+            ("text/plain", text.as_bytes()),
+            ("ExcludeClipboardContentFromMonitorProcessing", &[0; 4]),
+            ("CanIncludeInClipboardHistory", &0u32.to_le_bytes()),
+            ("CanUploadToCloudClipboard", &0u32.to_le_bytes()),
+        ])
+    }
+
+    /// As `CF_HDROP`, which is what explorer copies.
+    fn copy_files(&self, paths: &[&Path]) -> Result<()> {
+        let paths = paths
+            .iter()
+            .map(|p| {
+                p.to_str().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is not unicode", p.display()),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            clipboard_win::raw::set_file_list(&paths)
+        })
+    }
+
+    fn paste_files(&self) -> Result<Vec<PathBuf>> {
+        if !clipboard_win::is_format_avail(clipboard_win::formats::CF_HDROP) {
+            return Err(Error::FormatUnavailable {
+                mime: "text/uri-list".into(),
+            });
+        }
+        held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            let mut v = vec![];
+            clipboard_win::raw::get_file_list_path(&mut v).map(|_| v)
+        })
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        let name = match mime {
+            "text/plain" => return self.paste().map(String::into_bytes),
+            "text/html" => "HTML Format",
+            "text/rtf" => "Rich Text Format",
+            "image/png" => "PNG",
+            mime => mime,
+        };
+        let unavailable = || Error::FormatUnavailable { mime: mime.into() };
+        let format = clipboard_win::register_format(name)
+            .ok_or_else(io::Error::last_os_error)?
+            .get();
+        if !clipboard_win::is_format_avail(format) {
+            return Err(unavailable());
+        }
+        let data = held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            let mut v = vec![];
+            clipboard_win::raw::get_vec(format, &mut v).map(|_| v)
+        })?;
+        if mime == "text/html" {
+            return fragment(&data).map(<[u8]>::to_vec).ok_or_else(unavailable);
+        }
+        Ok(data)
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        let formats = held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            Ok(clipboard_win::EnumFormats::new().collect::<Vec<_>>())
+        })?;
+        let mut all = vec![];
+        for format in formats {
+            let name = match format {
+                clipboard_win::formats::CF_UNICODETEXT => "text/plain;charset=utf-8".into(),
+                clipboard_win::formats::CF_TEXT => "text/plain".into(),
+                clipboard_win::formats::CF_HDROP => "text/uri-list".into(),
+                // only those registered by name have one, the rest are windows' own
+                format => match clipboard_win::raw::format_name_big(format).as_deref() {
+                    Some("HTML Format") => "text/html".into(),
+                    Some("Rich Text Format") => "text/rtf".into(),
+                    Some("PNG") => "image/png".into(),
+                    Some(name) => name.into(),
+                    None => continue,
+                },
+            };
+            if !all.contains(&name) {
+                all.push(name);
+            }
+        }
+        Ok(all)
+    }
+
+    fn paste(&self) -> Result<String> {
+        if !clipboard_win::is_format_avail(clipboard_win::formats::CF_UNICODETEXT) {
+            return Ok(String::new());
+        }
+        held(clipboard_win::get_clipboard_string)
+    }
+
+    fn clear(&self) -> Result<()> {
+        held(|| {
+            let _open = clipboard_win::Clipboard::new_attempts(10)?;
+            clipboard_win::raw::empty()
+        })
+    }
+
+    /// The clipboard's sequence number, which needn't open it.
+    fn change_token(&self) -> Result<ChangeToken> {
+        match clipboard_win::raw::seq_num() {
+            Some(n) => Ok(ChangeToken::counted(n.get().into())),
+            // without access to the window station's
+            None => hashed(self),
+        }
+    }
+}
+
+/// The Windows clipboard format `data` goes as for `mime`, and what it is in that format.
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+fn format(mime: &str, data: &[u8]) -> Result<(u32, Vec<u8>)> {
+    let (name, data) = match mime {
+        mime if is_text(mime) => {
+            let text =
+                std::str::from_utf8(data).map_err(|_| Error::Unsupported { what: TEXT_ONLY })?;
+            // CF_UNICODETEXT is UTF-16 with a nul at the end
+            let text = text.encode_utf16().chain([0]).flat_map(u16::to_le_bytes);
+            return Ok((clipboard_win::formats::CF_UNICODETEXT, text.collect()));
+        }
+        "text/html" => ("HTML Format", cf_html(data)),
+        "text/rtf" => ("Rich Text Format", data.to_vec()),
+        "image/png" => ("PNG", data.to_vec()),
+        mime => (mime, data.to_vec()),
+    };
+    let format = clipboard_win::register_format(name)
+        .ok_or_else(io::Error::last_os_error)?
+        .get();
+    Ok((format, data))
+}
+
+/// Does `f`, trying again for a while if another program has the clipboard open.
+/// Only one can, and some (clipboard managers, remote desktop) hang on to it for a moment.
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+fn held<T>(f: impl Fn() -> Result<T, clipboard_win::ErrorCode>) -> Result<T> {
+    const ACCESS_DENIED: i32 = 5;
+    for _ in 0..20 {
+        match f() {
+            Err(e) if e.raw_code() == ACCESS_DENIED => {
+                std::thread::sleep(Duration::from_millis(25))
+            }
+            r => return r.map_err(win),
+        }
+    }
+    f().map_err(win)
+}
+
+/// HTML as the Windows clipboard's `HTML Format` has it: a header of where in it the html
+/// and the fragment that was copied are, then the html, with the fragment marked.
+#[cfg(any(test, all(target_family = "windows", feature = "windows-native")))]
+fn cf_html(fragment: &[u8]) -> Vec<u8> {
+    const START: &[u8] = b"<html><body>\r\n<!--StartFragment-->";
+    const END: &[u8] = b"<!--EndFragment-->\r\n</body></html>";
+    let header = |html, end_html, fragment, end_fragment| {
+        format!(
+            "Version:0.9\r\nStartHTML:{html:010}\r\nEndHTML:{end_html:010}\r\n\
+            StartFragment:{fragment:010}\r\nEndFragment:{end_fragment:010}\r\n"
+        )
+    };
+    // the offsets are all ten digits, whatever they are
+    let html = header(0, 0, 0, 0).len();
+    let start = html + START.len();
+    let end = start + fragment.len();
+    let mut v = header(html, end + END.len(), start, end).into_bytes();
+    v.extend_from_slice(START);
+    v.extend_from_slice(fragment);
+    v.extend_from_slice(END);
+    v
+}
+
+/// The fragment that was copied, in the Windows clipboard's `HTML Format`, see [`cf_html`].
+#[cfg(any(test, all(target_family = "windows", feature = "windows-native")))]
+fn fragment(cf_html: &[u8]) -> Option<&[u8]> {
+    let text = String::from_utf8_lossy(cf_html);
+    let at = |key: &str| -> Option<usize> {
+        let line = text
+            .lines()
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))?;
+        line.trim().parse().ok()
+    };
+    cf_html.get(at("StartFragment")?..at("EndFragment")?)
+}
+
+/// Converts a clipboard-win error, keeping the os error code (and with it, the message).
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+#[allow(clippy::needless_pass_by_value)]
+fn win(e: clipboard_win::ErrorCode) -> Error {
+    Error::Io(io::Error::from_raw_os_error(e.raw_code()))
+}
+
+/// The Windows clipboard, from a unix-like environment on top of it, through its own programs.
+///
+/// Text is copied and pasted exactly as it is, line endings and all,
+/// unless asked for [`unix_newlines`](crate::Options::unix_newlines).
+struct WindowsInterop {
+    run: Arc<dyn Runner>,
+    timeout: Duration,
+    unix_newlines: bool,
+    clip_exe: bool,
+}
+
+impl WindowsInterop {
+    /// Left to themselves, powershell and `clip.exe` read and write the console's code page,
+    /// which mangles anything that isn't ASCII. This has it use UTF-8, and read all of stdin
+    /// as one string rather than line by line, so that its line endings stay as they are.
+    const COPY: &'static str = "[Console]::InputEncoding = New-Object Text.UTF8Encoding; \
+        Set-Clipboard -Value ([Console]::In.ReadToEnd())";
+    /// Without -Raw, it gives the lines one by one, and they are put back together with `\r\n`.
+    const PASTE: &'static str =
+        "[Console]::OutputEncoding = New-Object Text.UTF8Encoding; Get-Clipboard -Raw";
+
+    fn new(run: Arc<dyn Runner>) -> Self {
+        Self {
+            run,
+            timeout: crate::Options::TIMEOUT,
+            unix_newlines: false,
+            clip_exe: false,
+        }
+    }
+}
+
+/// UTF-16LE, with a byte order mark, which is the only way `clip.exe` takes anything but ASCII.
+fn utf16le(s: &str) -> Vec<u8> {
+    std::iter::once(0xfeff)
+        .chain(s.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+impl Backend for WindowsInterop {
+    fn copy(&self, text: &str) -> Result<()> {
+        match text {
+            // Set-Clipboard won't take an empty string, and a lone bom would paste as a zero width space
+            "" => self.clear(),
+            text if self.clip_exe => {
+                c!("clip.exe").put_within(&*self.run, utf16le(text), Some(self.timeout))
+            }
+            text => c!("powershell.exe" "-noprofile" "-command")
+                .arg(Self::COPY)
+                .put_within(&*self.run, text, Some(self.timeout)),
+        }
+    }
+
+    fn paste(&self) -> Result<String> {
+        c!("powershell.exe" "-noprofile" "-command")
+            .arg(Self::PASTE)
+            .eat_within(&*self.run, Some(self.timeout))
+            // powershell writes a newline after it
+            .map(chomp)
+            .map(|s| {
+                if self.unix_newlines {
+                    s.replace("\r\n", "\n")
+                } else {
+                    s
+                }
+            })
+    }
+
+    fn paste_to(&self, w: &mut dyn Write) -> Result<()> {
+        let mut c = Command::new("powershell.exe");
+        c.args(["-noprofile", "-command", Self::PASTE]);
+        if self.unix_newlines {
+            let mut w = Chomped::new(Unix::new(w));
+            c.eat_to(&*self.run, &mut w, Some(self.timeout))?;
+            w.finish()?.finish()?;
+        } else {
+            let mut w = Chomped::new(w);
+            c.eat_to(&*self.run, &mut w, Some(self.timeout))?;
+            w.finish()?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        c!("powershell.exe" "-noprofile" "-command" "Set-Clipboard -Value $null")
+            .run_within(&*self.run, Some(self.timeout))
+    }
+}
+
+/// The MIME types in a list of them, one a line, as `wl-paste --list-types` prints.
+/// X11's own targets are in there too, from an X11 program or `xclip -t TARGETS`: its names
+/// for text are given as MIME types, and those that are about the selection rather than what
+/// is on it are left out.
+pub(crate) fn targets(list: &str) -> Vec<String> {
+    let mut all = Vec::<String>::new();
+    for target in list.lines().map(str::trim) {
+        let mime = match target {
+            "" | "TARGETS" | "TIMESTAMP" | "MULTIPLE" | "SAVE_TARGETS" | "DELETE" => continue,
+            "UTF8_STRING" => "text/plain;charset=utf-8",
+            "STRING" | "TEXT" | "COMPOUND_TEXT" => "text/plain",
+            target => target,
+        };
+        if !all.iter().any(|m| m == mime) {
+            all.push(mime.into());
+        }
+    }
+    all
+}
+
+/// Whether `mime` is plain text, whatever charset it says (UTF-8 is all that is copied).
+pub(crate) fn is_text(mime: &str) -> bool {
+    mime.split(';')
+        .next()
+        .is_some_and(|m| m.trim().eq_ignore_ascii_case("text/plain"))
+}
+
+/// Copies the one of `items` a clipboard that can only offer one should,
+/// see [`Backend::copy_multi`]. With none at all, it is left empty.
+fn one_of(b: &(impl Backend + ?Sized), items: &[(&str, &[u8])]) -> Result<()> {
+    let Some(&(mime, data)) = items
+        .iter()
+        .find(|(mime, _)| mime.starts_with("text/"))
+        .or(items.first())
+    else {
+        return b.clear();
+    };
+    if is_text(mime) {
+        b.copy(std::str::from_utf8(data).map_err(|_| Error::Unsupported { what: TEXT_ONLY })?)
+    } else {
+        b.copy_mime(mime, data)
+    }
+}
+
+/// Strips a single trailing `\r\n` or `\n`, if there is one.
+fn chomp(mut s: String) -> String {
+    if s.ends_with('\n') {
+        s.pop();
+        if s.ends_with('\r') {
+            s.pop();
+        }
+    }
+    s
+}
+
+/// Passes what is written on to `W`, less a trailing `\r\n` or `\n`, as [`chomp`] would.
+/// The end of what has been written so far is held back until it is clear whether it is the end.
+struct Chomped<W> {
+    w: W,
+    held: Vec<u8>,
+}
+
+impl<W: Write> Chomped<W> {
+    fn new(w: W) -> Self {
+        Self { w, held: vec![] }
+    }
+
+    /// Writes all of `buf` but the newline it may end with, and holds on to that.
+    fn pass(&mut self, buf: &[u8]) -> io::Result<()> {
+        let end = if buf.ends_with(b"\r\n") {
+            2
+        } else {
+            usize::from(buf.ends_with(b"\n") || buf.ends_with(b"\r"))
+        };
+        let (now, later) = buf.split_at(buf.len() - end);
+        self.w.write_all(now)?;
+        self.held = later.to_vec();
+        Ok(())
+    }
+
+    /// Finishes it, with whatever was held back that turned out not to be the end.
+    fn finish(mut self) -> io::Result<W> {
+        if self.held == b"\r" {
+            self.w.write_all(b"\r")?;
+        }
+        Ok(self.w)
+    }
+}
+
+impl<W: Write> Write for Chomped<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut held = std::mem::take(&mut self.held);
+        if buf.len() < 2 {
+            held.extend_from_slice(buf);
+            self.pass(&held)?;
+        } else {
+            self.w.write_all(&held)?;
+            self.pass(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Passes what is written on to `W` with its `\r\n`s as `\n`s.
+struct Unix<W> {
+    w: W,
+    /// Whether the last write ended with a `\r`, which was held back.
+    cr: bool,
+}
+
+impl<W: Write> Unix<W> {
+    fn new(w: W) -> Self {
+        Self { w, cr: false }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        if self.cr {
+            self.w.write_all(b"\r")?;
+        }
+        Ok(self.w)
+    }
+}
+
+impl<W: Write> Write for Unix<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut v = Vec::with_capacity(buf.len() + 1);
+        if std::mem::take(&mut self.cr) && buf.first() != Some(&b'\n') {
+            v.push(b'\r');
+        }
+        let mut bytes = buf.iter().peekable();
+        while let Some(&b) = bytes.next() {
+            match (b, bytes.peek()) {
+                (b'\r', Some(b'\n')) => {}
+                (b'\r', None) => self.cr = true,
+                (b, _) => v.push(b),
+            }
+        }
+        self.w.write_all(&v)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Everything detection looks at, so tests can make it up.
+pub trait Probe {
+    fn var(&self, key: &str) -> Option<OsString>;
+
+    fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        Path::new(path).exists()
+    }
+
+    /// Where `program` is on this PATH.
+    fn find(&self, program: &str) -> Option<PathBuf> {
+        find(program, &self.var("PATH")?)
+    }
+
+    /// Whether `program` is on this PATH.
+    fn has(&self, program: &str) -> bool {
+        self.find(program).is_some()
+    }
+
+    /// Whether `program` is on this PATH and succeeds with these arguments, for programs
+    /// that are no use without a server of theirs running. It gets a second to do so.
+    fn answers(&self, program: &str, args: &[&str]) -> bool {
+        self.find(program).is_some_and(|p| {
+            let mut c = Command::new(p);
+            output(
+                &Real,
+                c.args(args),
+                None,
+                false,
+                Some(Duration::from_secs(1)),
+            )
+            .is_ok()
+        })
+    }
+
+    /// The first of these programs that is on this PATH.
+    fn which<'a>(&self, programs: &[&'a str]) -> Option<&'a str> {
+        programs.iter().copied().find(|p| self.has(p))
+    }
+}
+
+/// The real environment.
+pub struct System;
+impl Probe for System {
+    fn var(&self, key: &str) -> Option<OsString> {
+        std::env::var_os(key)
+    }
+}
+
+/// Looks for `program` in `path`, like `which`.
+fn find(program: &str, path: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .flat_map(|dir| {
+            #[cfg(target_family = "windows")]
+            return [dir.join(program), dir.join(format!("{program}.exe"))];
+            #[cfg(not(target_family = "windows"))]
+            [dir.join(program)]
+        })
+        .find(|p| executable(p))
+}
+
+fn executable(p: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        p.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    p.is_file()
+}
+
+fn is_wsl(p: &impl Probe) -> bool {
+    // not every kernel says so in /proc/version, but wsl always sets these
+    p.var("WSL_DISTRO_NAME").is_some()
+        || p.var("WSL_INTEROP").is_some()
+        || p.read("/proc/version")
+            .is_some_and(|s| s.to_lowercase().contains("microsoft"))
+}
+
+/// Whether WSL can run windows programs. It can be turned off, in which case
+/// it doesn't put them on PATH either.
+fn interop(p: &impl Probe) -> bool {
+    let off = ["WSLInterop", "WSLInterop-late"].iter().any(|f| {
+        p.read(&format!("/proc/sys/fs/binfmt_misc/{f}"))
+            .is_some_and(|s| s.starts_with("disabled"))
+    });
+    !off && p.has("clip.exe")
+}
+
+/// Whether there is an X server to talk to: `DISPLAY` is set, and this isn't a session
+/// on a text console, which some leave `DISPLAY` set in.
+fn has_x11(p: &impl Probe) -> bool {
+    p.var("DISPLAY").is_some_and(|d| !d.is_empty())
+        && p.var("XDG_SESSION_TYPE").is_none_or(|t| t != "tty")
+}
+
+/// MSYS2, or Git Bash, which is built on it.
+fn is_msys(p: &impl Probe) -> bool {
+    p.var("MSYSTEM").is_some()
+        // what uname says
+        || p.read("/proc/version")
+            .is_some_and(|s| s.starts_with("MINGW") || s.starts_with("MSYS"))
+}
+
+/// Cygwin, or something built on it like MSYS2.
+fn is_cygwin(p: &impl Probe) -> bool {
+    p.read("/proc/version")
+        .is_some_and(|s| s.contains("CYGWIN"))
+        || p.var("OSTYPE").is_some_and(|t| t == "cygwin")
+        || p.var("MSYSTEM").is_some()
+}
+
+/// Picks the first available provider, trying those in `prefer` before the rest.
+pub(crate) fn detect(p: &impl Probe, prefer: &[Provider]) -> Result<Provider> {
+    if let Some(name) = p.var("CLIPP_PROVIDER").filter(|v| !v.is_empty()) {
+        return name.to_string_lossy().parse::<Provider>()?.check(p);
+    }
+    available(p, prefer).next().ok_or_else(|| unavailable(p))
+}
+
+/// Why nothing is available, as far as can be told.
+fn unavailable(p: &impl Probe) -> Error {
+    let sandbox = if p.exists("/.flatpak-info") {
+        Some("Flatpak".to_owned())
+    } else if p.var("SNAP").is_some() || p.var("SNAP_NAME").is_some() {
+        Some("Snap".to_owned())
+    } else {
+        // what systemd, podman and lxc set
+        p.var("container")
+            .filter(|c| !c.is_empty())
+            .map(|c| format!("a container ({})", c.to_string_lossy()))
+    };
+    if let Some(sandbox) = sandbox {
+        return Error::Sandboxed { sandbox };
+    }
+    let desktop = cfg!(all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "android"),
+        not(target_os = "haiku")
+    )) && !is_wsl(p)
+        && !is_cygwin(p)
+        && p.var("TERMUX_VERSION").is_none();
+    if desktop && p.var("DISPLAY").is_none() && p.var("WAYLAND_DISPLAY").is_none() {
+        Error::NoDisplay
+    } else {
+        Error::NoProvider
+    }
+}
+
+/// Every provider that could be used here, best first.
+pub fn available<'a>(
+    p: &'a impl Probe,
+    prefer: &'a [Provider],
+) -> impl Iterator<Item = Provider> + 'a {
+    prefer
+        .iter()
+        .chain(Provider::ALL.iter().filter(|x| !prefer.contains(x)))
+        .copied()
+        .filter(|x| x.available(p))
+}
+
+/// Which of the selections to copy to and paste from, where there is more than one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Selection {
+    /// The clipboard, that Ctrl+C copies to and Ctrl+V pastes from.
+    #[default]
+    Clipboard,
+    /// What was last selected, which a middle click pastes. Only X11 and Wayland have it.
+    Primary,
+    /// X11's spare selection, which nothing uses unless asked to.
+    Secondary,
+}
+
+/// A clipboard that clipp knows how to talk to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Provider {
+    /// `wl-copy` and `wl-paste`, from wl-clipboard.
+    Wayland,
+    XClip,
+    XSel,
+    /// KDE's Klipper, over `qdbus` or `dbus-send`.
+    Klipper,
+    /// `gpaste-client`, from the clipboard manager GNOME users run.
+    ///
+    /// Detection only picks it when nothing above it can be used, so prefer it to have copies
+    /// land in its history. Clearing takes the latest entry out of that history, so the one
+    /// before it pastes next.
+    GPaste,
+    /// `copyq`, from the clipboard manager of the same name, on X11, Wayland and Windows alike.
+    ///
+    /// It is only detected while its server is running, and after the native providers.
+    /// Unlike most clipboard programs it passes bytes along untouched, which makes it the one
+    /// to use for anything that isn't text.
+    CopyQ,
+    /// `termux-clipboard-set` and `termux-clipboard-get` on Android, from termux-api.
+    Termux,
+    /// `/dev/clipboard`, under Cygwin.
+    CygwinDev,
+    /// The Windows clipboard from inside WSL, MSYS2 or Git Bash, with `powershell.exe`.
+    ///
+    /// Under Windows 11's own Wayland compositor, [`Wayland`](Self::Wayland) is used instead
+    /// if wl-clipboard is installed.
+    /// To use this anyway, set `CLIPP_PROVIDER=interop` or [prefer](crate::Options::prefer) it.
+    WindowsInterop,
+    /// `pbcopy` and `pbpaste`.
+    #[cfg(target_os = "macos")]
+    PbCopy,
+    /// Haiku's `clipboard` command.
+    #[cfg(target_os = "haiku")]
+    Haiku,
+    /// The X11 clipboard, talking to the X server without xclip or xsel.
+    #[cfg(feature = "x11-native")]
+    X11,
+    #[cfg(all(target_family = "windows", feature = "windows-native"))]
+    Windows,
+    /// kitty's `kitten clipboard`, or `kitty +kitten clipboard`, when running in kitty.
+    ///
+    /// Pasting needs kitty to allow it, with `read-clipboard` in `clipboard_control`.
+    Kitty,
+    /// The terminal, over the OSC 52 escape sequence. See [`Osc52`](crate::Osc52).
+    #[cfg(feature = "osc52")]
+    Osc52,
+    /// A file of this user's, for when there is nothing else, so that what one process copies
+    /// another can still paste. It is `CLIPP_FILE`, or `clipp` in `XDG_RUNTIME_DIR`,
+    /// or failing that in `XDG_CACHE_HOME`.
+    ///
+    /// It is only there with the `file` feature.
+    #[cfg(feature = "file")]
+    File,
+    /// plan9port's snarf buffer, with `9p`, for acme and friends.
+    ///
+    /// It is never detected: set `CLIPP_PROVIDER=snarf` or use
+    /// [`Clipboard::with_provider`](crate::Clipboard::with_provider).
+    Plan9Snarf,
+    /// `lemonade copy` and `lemonade paste`, for a clipboard shared over the network.
+    ///
+    /// Like [`Piknik`](Self::Piknik), it is never detected, as sending the clipboard off
+    /// somewhere should be asked for: set `CLIPP_PROVIDER=lemonade` or use
+    /// [`Clipboard::with_provider`](crate::Clipboard::with_provider).
+    Lemonade,
+    /// `piknik -copy` and `piknik -paste`, for a clipboard shared over the network.
+    Piknik,
+    /// A clipboard of this process's own, which nothing else can see, for when there is no other.
+    /// See [`Options::fallback_to_memory`](crate::Options::fallback_to_memory).
+    Memory,
+    /// A [`Backend`] of your own, see [`Clipboard::new`](crate::Clipboard::new).
+    Custom,
+}
+
+impl Provider {
+    #[deprecated = "it's WindowsInterop now, as it is not just for WSL"]
+    #[allow(non_upper_case_globals)]
+    pub const Wsl: Self = Self::WindowsInterop;
+
+    /// Every provider, in the order detection tries them.
+    pub const ALL: &'static [Self] = &[
+        #[cfg(all(target_family = "windows", feature = "windows-native"))]
+        Self::Windows,
+        #[cfg(target_os = "macos")]
+        Self::PbCopy,
+        #[cfg(target_os = "haiku")]
+        Self::Haiku,
+        Self::Termux,
+        Self::CygwinDev,
+        // WSLg's wayland clipboard is bridged to windows anyway, and much faster than powershell
+        Self::Wayland,
+        Self::WindowsInterop,
+        #[cfg(feature = "x11-native")]
+        Self::X11,
+        Self::XSel,
+        Self::XClip,
+        Self::Klipper,
+        Self::GPaste,
+        Self::CopyQ,
+        Self::Kitty,
+        #[cfg(feature = "osc52")]
+        Self::Osc52,
+        #[cfg(feature = "file")]
+        Self::File,
+        Self::Plan9Snarf,
+        Self::Lemonade,
+        Self::Piknik,
+    ];
+
+    /// The name used for this provider in `CLIPP_PROVIDER`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Wayland => "wl",
+            Self::XClip => "xclip",
+            Self::XSel => "xsel",
+            Self::Klipper => "klipper",
+            Self::GPaste => "gpaste",
+            Self::CopyQ => "copyq",
+            Self::Termux => "termux",
+            Self::CygwinDev => "cygwin",
+            Self::WindowsInterop => "interop",
+            #[cfg(target_os = "macos")]
+            Self::PbCopy => "pbcopy",
+            #[cfg(target_os = "haiku")]
+            Self::Haiku => "haiku",
+            #[cfg(feature = "x11-native")]
+            Self::X11 => "x11",
+            #[cfg(all(target_family = "windows", feature = "windows-native"))]
+            Self::Windows => "windows",
+            #[cfg(feature = "osc52")]
+            Self::Osc52 => "osc52",
+            #[cfg(feature = "file")]
+            Self::File => "file",
+            Self::Plan9Snarf => "snarf",
+            Self::Kitty => "kitty",
+            Self::Lemonade => "lemonade",
+            Self::Piknik => "piknik",
+            Self::Memory => "memory",
+            Self::Custom => "custom",
+        }
+    }
+
+    /// The programs it needs, each one of several that would do.
+    const fn needs(self) -> &'static [&'static [&'static str]] {
+        match self {
+            Self::Wayland => &[&["wl-copy"], &["wl-paste"]],
+            Self::XClip => &[&["xclip"]],
+            Self::XSel => &[&["xsel"]],
+            Self::Klipper => &[KLIPPER_TOOLS],
+            Self::GPaste => &[&["gpaste-client"]],
+            Self::CopyQ => &[&["copyq"]],
+            Self::Termux => &[&["termux-clipboard-set"], &["termux-clipboard-get"]],
+            Self::CygwinDev => &[],
+            Self::WindowsInterop => &[&["powershell.exe"]],
+            #[cfg(target_os = "macos")]
+            Self::PbCopy => &[&["pbcopy"], &["pbpaste"]],
+            #[cfg(target_os = "haiku")]
+            Self::Haiku => &[&["clipboard"]],
+            #[cfg(feature = "x11-native")]
+            Self::X11 => &[],
+            #[cfg(all(target_family = "windows", feature = "windows-native"))]
+            Self::Windows => &[],
+            #[cfg(feature = "osc52")]
+            Self::Osc52 => &[],
+            #[cfg(feature = "file")]
+            Self::File => &[],
+            Self::Plan9Snarf => &[&["9"]],
+            Self::Kitty => &[&["kitten", "kitty"]],
+            Self::Lemonade => &[&["lemonade"]],
+            Self::Piknik => &[&["piknik"]],
+            Self::Memory | Self::Custom => &[],
+        }
+    }
+
+    /// Everything it runs, to be found once, when it is picked.
+    fn programs(self) -> impl Iterator<Item = &'static str> {
+        let besides: &[&str] = match self {
+            Self::XClip | Self::XSel => &["clipnotify"],
+            Self::WindowsInterop => &["clip.exe"],
+            #[cfg(target_os = "macos")]
+            Self::PbCopy => &["osascript"],
+            _ => &[],
+        };
+        self.needs()
+            .iter()
+            .copied()
+            .flatten()
+            .chain(besides)
+            .copied()
+    }
+
+    pub(crate) fn backend(self, o: &crate::Options) -> Result<Arc<dyn Backend>> {
+        self.found(o, &System, real())
+    }
+
+    /// Its backend, running its programs with `run` from where `p` finds them.
+    #[cfg_attr(not(feature = "file"), allow(clippy::unnecessary_wraps))]
+    fn found(
+        self,
+        o: &crate::Options,
+        p: &impl Probe,
+        run: Arc<dyn Runner>,
+    ) -> Result<Arc<dyn Backend>> {
+        let run: Arc<dyn Runner> = Arc::new(Resolved::new(run, p, self.programs()));
+        Ok(match self {
+            Self::Wayland => Arc::new(Wayland {
+                timeout: o.timeout,
+                selection: o.selection,
+                ..Wayland::new(run.clone())
+            }),
+            Self::XClip => Arc::new(XClip {
+                timeout: o.timeout,
+                selection: o.selection,
+                ..XClip::new(run.clone())
+            }),
+            Self::XSel => Arc::new(XSel {
+                timeout: o.timeout,
+                selection: o.selection,
+                ..XSel::new(run.clone())
+            }),
+            Self::Klipper => Arc::new(Klipper {
+                timeout: o.timeout,
+                ..Klipper::new(run.clone(), p)
+            }),
+            Self::GPaste => Arc::new(GPaste {
+                timeout: o.timeout,
+                ..GPaste::new(run.clone())
+            }),
+            Self::CopyQ => Arc::new(CopyQ(run.clone())),
+            Self::Termux => Arc::new(Termux(run.clone())),
+            Self::CygwinDev => Arc::new(CygwinDev::new()),
+            Self::WindowsInterop => Arc::new(WindowsInterop {
+                timeout: o.timeout,
+                unix_newlines: o.unix_newlines,
+                clip_exe: o.clip_exe,
+                ..WindowsInterop::new(run.clone())
+            }),
+            #[cfg(target_os = "macos")]
+            Self::PbCopy => Arc::new(PbCopy {
+                timeout: o.timeout,
+                ..PbCopy::new(run.clone(), crate::macos::Pasteboard::General)
+            }),
+            #[cfg(target_os = "haiku")]
+            Self::Haiku => Arc::new(Haiku(run.clone())),
+            #[cfg(feature = "x11-native")]
+            Self::X11 => Arc::new(crate::x11::X11::new(match o.selection {
+                Selection::Clipboard => "CLIPBOARD",
+                Selection::Primary => "PRIMARY",
+                Selection::Secondary => "SECONDARY",
+            })),
+            #[cfg(all(target_family = "windows", feature = "windows-native"))]
+            Self::Windows => Arc::new(Windows),
+            #[cfg(feature = "osc52")]
+            Self::Osc52 => Arc::new(crate::Osc52::new()),
+            #[cfg(feature = "file")]
+            Self::File => Arc::new(crate::file::FileClipboard(
+                crate::file::FileClipboard::path(p).ok_or(Error::NoProvider)?,
+            )),
+            Self::Plan9Snarf => Arc::new(Snarf {
+                timeout: o.timeout,
+                ..Snarf::new(run.clone())
+            }),
+            Self::Kitty => Arc::new(Kitty {
+                timeout: o.timeout,
+                ..Kitty::new(run.clone(), p)
+            }),
+            Self::Lemonade => Arc::new(Lemonade(run.clone())),
+            Self::Piknik => Arc::new(Piknik(run.clone())),
+            Self::Memory => Arc::new(crate::clipboard::Scratch::default()),
+            Self::Custom => unreachable!("custom backends are brought by whoever made them"),
+        })
+    }
+
+    /// Whether this provider has `selection`. They all have the clipboard.
+    #[must_use]
+    pub fn has_selection(self, selection: Selection) -> bool {
+        match (self, selection) {
+            (_, Selection::Clipboard) | (Self::XClip | Self::XSel, _) => true,
+            #[cfg(feature = "x11-native")]
+            (Self::X11, _) => true,
+            (Self::Wayland, Selection::Primary) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this provider could be used here.
+    ///
+    /// This checks that its programs are installed, and that it looks like the right
+    /// environment for it (e.g. `WAYLAND_DISPLAY` is set).
+    #[must_use]
+    pub fn is_available(self) -> bool {
+        self.available(&System)
+    }
+
+    fn available(self, p: &impl Probe) -> bool {
+        match self {
+            Self::WindowsInterop => (is_wsl(p) && interop(p)) || (is_msys(p) && p.has("clip.exe")),
+            Self::Wayland => p.var("WAYLAND_DISPLAY").is_some() && p.has("wl-copy"),
+            Self::XSel => has_x11(p) && p.has("xsel"),
+            Self::XClip => has_x11(p) && p.has("xclip"),
+            Self::Klipper => p.has("klipper") && p.which(KLIPPER_TOOLS).is_some(),
+            Self::GPaste => p.has("gpaste-client"),
+            Self::CopyQ => p.answers("copyq", &["info"]),
+            Self::Termux => p.var("TERMUX_VERSION").is_some() || p.has("termux-clipboard-set"),
+            Self::CygwinDev => is_cygwin(p) && p.exists("/dev/clipboard"),
+            #[cfg(target_os = "macos")]
+            Self::PbCopy => true,
+            #[cfg(target_os = "haiku")]
+            Self::Haiku => p.has("clipboard"),
+            #[cfg(feature = "x11-native")]
+            Self::X11 => has_x11(p),
+            #[cfg(all(target_family = "windows", feature = "windows-native"))]
+            Self::Windows => true,
+            // only as a last resort, most terminals won't paste
+            #[cfg(feature = "osc52")]
+            Self::Osc52 => p.var("SSH_TTY").is_some(),
+            // after everything else, so that it is only used when nothing else is there
+            #[cfg(feature = "file")]
+            Self::File => crate::file::FileClipboard::path(p).is_some(),
+            // only ever asked for
+            Self::Plan9Snarf | Self::Lemonade | Self::Piknik => false,
+            Self::Kitty => {
+                (p.var("TERM").is_some_and(|t| t == "xterm-kitty")
+                    || p.var("KITTY_WINDOW_ID").is_some())
+                    && p.which(&["kitten", "kitty"]).is_some()
+            }
+            Self::Memory | Self::Custom => false,
+        }
+    }
+
+    /// Makes sure everything it needs is installed.
+    pub(crate) fn check(self, p: &impl Probe) -> Result<Self> {
+        if self == Self::Custom {
+            return Err(Error::UnknownProvider {
+                name: self.name().to_owned(),
+            });
+        }
+        match self.needs().iter().find(|any| p.which(any).is_none()) {
+            Some(any) => Err(Error::CommandNotFound {
+                command: any[0].to_owned(),
+            }),
+            None => Ok(self),
+        }
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        if name == "wsl" {
+            // what it was called before
+            return Ok(Self::WindowsInterop);
+        }
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| Error::UnknownProvider { name: name.into() })
+    }
+}
+
+/// A made up environment.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Fake {
+    pub vars: std::collections::HashMap<&'static str, OsString>,
+    pub files: std::collections::HashMap<&'static str, String>,
+}
+
+#[cfg(test)]
+impl Probe for Fake {
+    fn var(&self, key: &str) -> Option<OsString> {
+        self.vars.get(key).cloned()
+    }
+
+    fn read(&self, path: &str) -> Option<String> {
+        self.files.get(path).cloned()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+}
+
+/// Whether to test against real clipboards. They are shared with the rest of the
+/// machine, so this needs asking for with `CLIPP_INTEGRATION_TESTS=1`.
+#[cfg(test)]
+pub fn integration() -> bool {
+    std::env::var_os("CLIPP_INTEGRATION_TESTS").is_some_and(|v| v == "1")
+}
+
+/// Puts back whatever was on a real clipboard before the test, and keeps other tests off it meanwhile.
+#[cfg(test)]
+pub struct Restore<'a> {
+    clip: &'a dyn Backend,
+    was: String,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl<'a> Restore<'a> {
+    pub fn new(clip: &'a dyn Backend) -> Self {
+        static REAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let lock = REAL
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Self {
+            clip,
+            was: clip.paste().unwrap_or_default(),
+            _lock: lock,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        _ = match &*self.was {
+            "" => self.clip.clear(),
+            was => self.clip.copy(was),
+        };
+    }
+}
+
+/// Answers with made up output instead of running anything, and remembers what it was asked.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Canned {
+    answers: Vec<(String, i32, Vec<u8>, String)>,
+    /// Each command line run, with what it was given on stdin.
+    pub ran: std::sync::Mutex<Vec<(String, Option<Vec<u8>>)>>,
+}
+
+#[cfg(test)]
+impl Canned {
+    /// Answers `line` with this exit code, stdout and stderr. Anything else is not found.
+    pub fn answer(mut self, line: &str, code: i32, stdout: &[u8], stderr: &str) -> Self {
+        self.answers
+            .push((line.into(), code, stdout.into(), stderr.into()));
+        self
+    }
+
+    /// Has `line` never finish, as if it timed out.
+    pub fn hang(self, line: &str) -> Self {
+        self.answer(line, HANG, b"", "")
+    }
+
+    /// The command lines run so far.
+    pub fn lines(&self) -> Vec<String> {
+        self.ran
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.0.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Runner for Canned {
+    fn output(
+        &self,
+        c: &mut Command,
+        input: Option<&[u8]>,
+        read: bool,
+        _: Option<Duration>,
+    ) -> io::Result<Output> {
+        let line = std::iter::once(c.get_program())
+            .chain(c.get_args())
+            .map(OsStr::to_string_lossy)
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.ran
+            .lock()
+            .unwrap()
+            .push((line.clone(), input.map(<[u8]>::to_vec)));
+        let (_, code, stdout, stderr) = self
+            .answers
+            .iter()
+            .find(|a| a.0 == line)
+            .ok_or(io::ErrorKind::NotFound)?;
+        if *code == HANG {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        Ok(Output {
+            status: exit(*code),
+            stdout: if read { stdout.clone() } else { vec![] },
+            stderr: stderr.clone().into(),
+        })
+    }
+}
+
+#[cfg(test)]
+const HANG: i32 = -1;
+
+#[cfg(all(test, unix))]
+fn exit(code: i32) -> std::process::ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
+
+#[cfg(all(test, target_family = "windows"))]
+fn exit(code: i32) -> std::process::ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code.cast_unsigned())
+}
+
+/// A directory of fake helper programs, to put on a [`Fake`] PATH.
+#[cfg(all(test, unix))]
+pub struct Bin(pub PathBuf);
+
+#[cfg(all(test, unix))]
+impl Bin {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("clipp-{name}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// Adds a program that runs this shell script.
+    pub fn add(&self, program: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let p = self.0.join(program);
+        std::fs::write(&p, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&p, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+#[cfg(all(test, unix))]
+impl Drop for Bin {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs the helpers in a [`Bin`], instead of those on PATH.
+#[cfg(all(test, unix))]
+pub struct FromBin(pub PathBuf);
+
+#[cfg(all(test, unix))]
+impl FromBin {
+    fn there(&self, c: &Command) -> Command {
+        let mut there = Command::new(self.0.join(c.get_program()));
+        there.args(c.get_args());
+        there
+    }
+}
+
+#[cfg(all(test, unix))]
+impl Runner for FromBin {
+    fn output(
+        &self,
+        c: &mut Command,
+        input: Option<&[u8]>,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Real.output(&mut self.there(c), input, read, timeout)
+    }
+
+    fn output_from(
+        &self,
+        c: &mut Command,
+        input: &mut dyn Read,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Real.output_from(&mut self.there(c), input, read, timeout)
+    }
+}
+
+/// What [`available`] gives, less the native X11 provider, which comes first with any `DISPLAY`
+/// when it is built.
+#[cfg(test)]
+fn besides_x11(p: &impl Probe, prefer: &[Provider]) -> Vec<Provider> {
+    available(p, prefer).filter(|p| p.name() != "x11").collect()
+}
+
+#[test]
+fn test() {
+    if !integration() {
+        return;
+    }
+    macro_rules! test {
+        ($clipboard:expr) => {
+            let clipboard = $clipboard;
+            let _restore = Restore::new(&clipboard);
+            clipboard.copy("text").unwrap();
+            assert_eq!(clipboard.paste().unwrap(), "text");
+            clipboard.clear().unwrap();
+            assert_eq!(clipboard.paste().unwrap(), "");
+        };
+    }
+    #[cfg(target_os = "macos")]
+    test!(PbCopy::new(real(), crate::macos::Pasteboard::General));
+    #[cfg(target_os = "haiku")]
+    test!(Haiku(real()));
+    #[cfg(target_os = "linux")]
+    if System.has("xclip") {
+        test!(XClip::new(real()));
+    }
+    #[cfg(target_os = "linux")]
+    if System.has("xsel") {
+        test!(XSel::new(real()));
+    }
+    #[cfg(feature = "x11-native")]
+    if System.var("DISPLAY").is_some() {
+        test!(crate::x11::X11::new("CLIPBOARD"));
+    }
+    #[cfg(target_os = "linux")]
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        test!(Wayland::new(real()));
+    }
+    #[cfg(target_os = "linux")]
+    if System.has("klipper") && System.which(KLIPPER_TOOLS).is_some() {
+        test!(Klipper::new(real(), &System));
+    }
+    if System.has("termux-clipboard-get") {
+        test!(Termux(real()));
+    }
+    if Provider::CygwinDev.is_available() {
+        test!(CygwinDev::new());
+    }
+    #[cfg(all(target_family = "windows", feature = "windows-native"))]
+    test!(Windows);
+    if Provider::WindowsInterop.is_available() {
+        test!(WindowsInterop::new(real()));
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn primary() {
+    if !integration() {
+        return;
+    }
+    let independent = |all: &[&dyn Backend]| {
+        let _restore = Restore::new(all[0]);
+        let texts = ["clipboard", "primary", "secondary"];
+        for (b, text) in all.iter().zip(texts) {
+            b.copy(text).unwrap();
+        }
+        for (b, text) in all.iter().zip(texts) {
+            assert_eq!(b.paste().unwrap(), text);
+        }
+    };
+    if System.var("DISPLAY").is_some() && System.has("xclip") {
+        let on = |selection| XClip {
+            selection,
+            ..XClip::new(real())
+        };
+        let [c, p, s] = [
+            Selection::Clipboard,
+            Selection::Primary,
+            Selection::Secondary,
+        ]
+        .map(on);
+        independent(&[&c, &p, &s]);
+    }
+    if System.var("DISPLAY").is_some() && System.has("xsel") {
+        let on = |selection| XSel {
+            selection,
+            ..XSel::new(real())
+        };
+        let [c, p, s] = [
+            Selection::Clipboard,
+            Selection::Primary,
+            Selection::Secondary,
+        ]
+        .map(on);
+        independent(&[&c, &p, &s]);
+    }
+    if System.var("WAYLAND_DISPLAY").is_some() && System.has("wl-copy") {
+        let primary = Wayland {
+            selection: Selection::Primary,
+            ..Wayland::new(real())
+        };
+        independent(&[&Wayland::new(real()), &primary]);
+    }
+}
+
+/// Stands in for a helper that watches the clipboard: each time one is spawned, its command
+/// line is kept, and the next of `scripts` is run instead, the last one from then on.
+#[cfg(all(test, target_os = "linux"))]
+struct Watching {
+    scripts: Vec<String>,
+    ran: Mutex<Vec<String>>,
+}
+
+#[cfg(all(test, target_os = "linux"))]
+impl Watching {
+    fn new(scripts: &[&str]) -> Arc<Self> {
+        Arc::new(Self {
+            scripts: scripts.iter().map(|&s| s.to_owned()).collect(),
+            ran: Mutex::default(),
+        })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+impl Runner for Watching {
+    fn output(
+        &self,
+        _: &mut Command,
+        _: Option<&[u8]>,
+        _: bool,
+        _: Option<Duration>,
+    ) -> io::Result<Output> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    fn spawn(&self, c: &mut Command) -> io::Result<Child> {
+        let line = std::iter::once(c.get_program())
+            .chain(c.get_args())
+            .map(OsStr::to_string_lossy)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut ran = self.ran.lock().unwrap();
+        ran.push(line);
+        let script = &self.scripts[(ran.len() - 1).min(self.scripts.len() - 1)];
+        Command::new("sh")
+            .args(["-c", script])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+    }
+}
+
+/// Where a script can leave its pid, and whether it is still running once it has.
+#[cfg(all(test, target_os = "linux"))]
+pub fn pid_file(name: &str) -> (PathBuf, impl Fn() -> bool) {
+    let path = std::env::temp_dir().join(format!("clipp-{name}-{}", std::process::id()));
+    let read = path.clone();
+    let running = move || {
+        let pid = std::fs::read_to_string(&read).unwrap();
+        Path::new(&format!("/proc/{}", pid.trim())).exists()
+    };
+    (path, running)
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn watched() {
+    // wl-paste --watch runs it once to begin with, then twice for two changes
+    let (pid, running) = pid_file("watched");
+    let script = format!(
+        "echo $$ > {}; echo; echo; echo; exec sleep 60",
+        pid.display()
+    );
+    let r = Watching::new(&[&script]);
+    let w = Wayland {
+        selection: Selection::Primary,
+        ..Wayland::new(r.clone())
+    };
+    let changes = w.changes().unwrap().unwrap();
+    let wait = Duration::from_secs(10);
+    assert!(changes.next(wait).unwrap());
+    assert!(changes.next(wait).unwrap());
+    assert!(!changes.next(Duration::from_millis(100)).unwrap());
+    assert_eq!(
+        *r.ran.lock().unwrap(),
+        ["wl-paste --primary --watch sh -c cat >/dev/null; echo"]
+    );
+    // and it is gone, once they are done with
+    assert!(running());
+    drop(changes);
+    assert!(!running());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn notified() {
+    // not installed
+    struct Missing;
+    impl Runner for Missing {
+        fn output(
+            &self,
+            _: &mut Command,
+            _: Option<&[u8]>,
+            _: bool,
+            _: Option<Duration>,
+        ) -> io::Result<Output> {
+            Err(io::ErrorKind::NotFound.into())
+        }
+
+        fn spawn(&self, _: &mut Command) -> io::Result<Child> {
+            Err(io::ErrorKind::NotFound.into())
+        }
+    }
+    // clipnotify exits for two changes, then waits for a third
+    let (pid, running) = pid_file("notified");
+    let waiting = format!("echo $$ > {}; exec sleep 60", pid.display());
+    let r = Watching::new(&["exit 0", "exit 0", &waiting]);
+    let x = XClip {
+        selection: Selection::Primary,
+        ..XClip::new(r.clone())
+    };
+    let changes = x.changes().unwrap().unwrap();
+    let wait = Duration::from_secs(10);
+    assert!(changes.next(wait).unwrap());
+    assert!(changes.next(wait).unwrap());
+    assert!(!changes.next(Duration::from_millis(100)).unwrap());
+    assert_eq!(*r.ran.lock().unwrap(), ["clipnotify -s primary"; 3]);
+    assert!(running());
+    drop(changes);
+    assert!(!running());
+    // failing to watch, e.g. without a display, is the end of them, for polling to take over
+    let changes = XSel::new(Watching::new(&["exit 1"])).changes().unwrap();
+    assert!(changes.unwrap().next(wait).is_err());
+    // and without it there are none
+    assert!(XSel::new(Arc::new(Missing)).changes().unwrap().is_none());
+}
+
+#[test]
+fn stamped() {
+    let stamped = |stamp: &[u8]| {
+        let r =
+            Arc::new(Canned::default().answer("xclip -selection c -o -t TIMESTAMP", 0, stamp, ""));
+        XClip::new(r).change_token().unwrap()
+    };
+    let before = stamped(&7u32.to_le_bytes());
+    assert_eq!(before, stamped(&7u32.to_le_bytes()));
+    assert_ne!(before, stamped(&8u32.to_le_bytes()));
+    assert_eq!(before, ChangeToken::counted(7));
+    // nothing owns it, so what it pastes is hashed
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                "xclip -selection c -o -t TIMESTAMP",
+                1,
+                b"",
+                "Error: target TIMESTAMP not available",
+            )
+            .answer("xclip -selection c -o", 0, b"", ""),
+    );
+    let xclip = XClip::new(r.clone());
+    assert_eq!(xclip.change_token().unwrap(), hashed(&xclip).unwrap());
+    assert_eq!(r.lines().last().unwrap(), "xclip -selection c -o");
+}
+
+#[test]
+fn selections() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("wl-copy --primary", 0, b"", "")
+            .answer("wl-paste --primary -n", 0, b"selected", "")
+            .answer("xclip -selection primary", 0, b"", "")
+            .answer("xclip -selection primary -o", 0, b"selected", "")
+            .answer("xsel -p -i", 0, b"", "")
+            .answer("xsel -p -c", 0, b"", "")
+            .answer("xclip -selection c -o", 0, b"clipboard", "")
+            .answer("xclip -selection secondary", 0, b"", "")
+            .answer("xsel -s -o", 0, b"spare", ""),
+    );
+    let primary: [&dyn Backend; 3] = [
+        &Wayland {
+            selection: Selection::Primary,
+            ..Wayland::new(r.clone())
+        },
+        &XClip {
+            selection: Selection::Primary,
+            ..XClip::new(r.clone())
+        },
+        &XSel {
+            selection: Selection::Primary,
+            ..XSel::new(r.clone())
+        },
+    ];
+    for p in &primary[..2] {
+        p.copy("selected").unwrap();
+        assert_eq!(p.paste().unwrap(), "selected");
+    }
+    primary[2].copy("selected").unwrap();
+    primary[2].clear().unwrap();
+    // the clipboard is still the clipboard
+    assert_eq!(XClip::new(r.clone()).paste().unwrap(), "clipboard");
+    let secondary = XClip {
+        selection: Selection::Secondary,
+        ..XClip::new(r.clone())
+    };
+    secondary.copy("spare").unwrap();
+    let secondary = XSel {
+        selection: Selection::Secondary,
+        ..XSel::new(r.clone())
+    };
+    assert_eq!(secondary.paste().unwrap(), "spare");
+    assert_eq!(
+        r.lines(),
+        [
+            "wl-copy --primary",
+            "wl-paste --primary -n",
+            "xclip -selection primary",
+            "xclip -selection primary -o",
+            "xsel -p -i",
+            "xsel -p -c",
+            "xclip -selection c -o",
+            "xclip -selection secondary",
+            "xsel -s -o",
+        ]
+    );
+    // where there is no primary selection, it isn't the clipboard instead
+    let o = crate::Options::new().selection(Selection::Primary);
+    for provider in [
+        Provider::Klipper,
+        Provider::WindowsInterop,
+        Provider::CopyQ,
+        Provider::Memory,
+    ] {
+        assert!(provider.has_selection(Selection::Clipboard));
+        assert!(!provider.has_selection(Selection::Primary));
+        assert!(matches!(
+            crate::Clipboard::of(provider, &o),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+    assert!(crate::Clipboard::of(Provider::XSel, &o).is_ok());
+    // wayland has no secondary selection
+    let o = o.selection(Selection::Secondary);
+    assert!(!Provider::Wayland.has_selection(Selection::Secondary));
+    let e = crate::Clipboard::of(Provider::Wayland, &o).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "the secondary selection outside of X11 is not supported"
+    );
+    assert!(crate::Clipboard::of(Provider::XClip, &o).is_ok());
+}
+
+#[test]
+#[cfg(target_os = "macos")]
+fn pbcopy() {
+    if !integration() {
+        return;
+    }
+    let pb = PbCopy::new(real(), crate::macos::Pasteboard::General);
+    let _restore = Restore::new(&pb);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        pb.copy(text).unwrap();
+        assert_eq!(pb.paste().unwrap(), text);
+    }
+    assert!(pb.conceals());
+    pb.copy_concealed("hunter2").unwrap();
+    assert_eq!(pb.paste().unwrap(), "hunter2");
+    let mut c = Command::new("osascript");
+    c.args(["-l", "JavaScript", "-e", PASTE_TYPE, CONCEALED]);
+    assert!(output(&Real, &mut c, None, true, None).is_ok());
+}
+
+#[test]
+#[cfg(target_os = "haiku")]
+fn haiku() {
+    if !integration() {
+        return;
+    }
+    let h = Haiku(real());
+    let _restore = Restore::new(&h);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        h.copy(text).unwrap();
+        assert_eq!(h.paste().unwrap(), text);
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn run() {
+    assert!(c!("true").run_within(&Real, None).is_ok());
+    assert!(c!("false").run_within(&Real, None).is_err());
+    assert!(matches!(
+        c!("clipp-not-a-real-command").run_within(&Real, None),
+        Err(Error::CommandNotFound { command }) if command == "clipp-not-a-real-command"
+    ));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn xclip() {
+    if !integration() || !System.has("xclip") {
+        return;
+    }
+    let x = XClip::new(real());
+    let _restore = Restore::new(&x);
+    x.copy("clipp xclip test").unwrap();
+    assert_eq!(x.paste().unwrap(), "clipp xclip test");
+}
+
+#[test]
+fn chomping() {
+    assert_eq!(chomp(String::new()), "");
+    assert_eq!(chomp("a".into()), "a");
+    assert_eq!(chomp("\n".into()), "");
+    assert_eq!(chomp("\r\n".into()), "");
+    assert_eq!(chomp("text\n".into()), "text");
+    assert_eq!(chomp("text\r\n".into()), "text");
+    assert_eq!(chomp("two\n\n".into()), "two\n");
+    // what qdbus prints for klipper
+    assert_eq!(chomp("copied\n".into()), "copied");
+    assert_eq!(chomp("no newline".into()), "no newline");
+}
+
+#[test]
+fn chomped() {
+    for s in [
+        "",
+        "\n",
+        "\r",
+        "\r\n",
+        "\n\n",
+        "\r\r",
+        "a\r\n",
+        "a\r",
+        "a\n\r",
+        "two\r\n\r\n",
+        "in\r\nthe\rmiddle\r\n",
+    ] {
+        let chomped = chomp(s.into());
+        // however it is split up on the way
+        for n in 1..=s.len().max(1) {
+            let mut w = Chomped::new(vec![]);
+            s.as_bytes().chunks(n).for_each(|c| w.write_all(c).unwrap());
+            assert_eq!(w.finish().unwrap(), chomped.as_bytes(), "{s:?} by {n}");
+            let mut w = Chomped::new(Unix::new(vec![]));
+            s.as_bytes().chunks(n).for_each(|c| w.write_all(c).unwrap());
+            let unix = w.finish().unwrap().finish().unwrap();
+            assert_eq!(
+                unix,
+                chomped.replace("\r\n", "\n").as_bytes(),
+                "{s:?} by {n}"
+            );
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn eat() {
+    assert_eq!(c!("echo" "hi").eat_within(&Real, None).unwrap(), "hi\n");
+    assert!(c!("false").eat_within(&Real, None).is_err());
+    let e = c!("sh" "-c" "echo nothing is copied >&2; exit 1")
+        .eat_within(&Real, None)
+        .unwrap_err();
+    assert!(e.to_string().ends_with(": nothing is copied"));
+    assert!(said(&e, "nothing is copied"));
+    assert!(!said(&e, "something else"));
+    assert!(!said(
+        &Error::Io(io::Error::other("nothing is copied")),
+        "nothing is copied"
+    ));
+    assert!(matches!(
+        c!("printf" "\\377").eat_within(&Real, None),
+        Err(Error::InvalidUtf8)
+    ));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reaped() {
+    for _ in 0..300 {
+        assert_eq!(c!("printf" "x").eat_within(&Real, None).unwrap(), "x");
+    }
+    let me = std::process::id().to_string();
+    let zombies = std::fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|e| std::fs::read_to_string(e.ok()?.path().join("stat")).ok())
+        .filter(|stat| {
+            // pid (comm) state ppid ...
+            let mut rest = stat.rsplit_once(") ").map_or("", |(_, r)| r).split(' ');
+            stat.contains("(printf)") && rest.next() == Some("Z") && rest.next() == Some(&*me)
+        })
+        .count();
+    assert_eq!(zombies, 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn streamed() {
+    /// Gives some, then fails.
+    struct Failing(usize);
+    impl Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::other("disk on fire"));
+            }
+            let n = buf.len().min(self.0);
+            buf[..n].fill(b'x');
+            self.0 -= n;
+            Ok(n)
+        }
+    }
+    let bin = Bin::new("streamed");
+    let out = bin.0.join("out");
+    bin.add("xsel", &format!("cat > {}", out.display()));
+    let x = XSel::new(Arc::new(FromBin(bin.0.clone())));
+    // not text, and more than a pipe holds many times over
+    let bytes = (0..8 << 20)
+        .map(|i: u32| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    x.copy_from(&mut &bytes[..]).unwrap();
+    assert!(std::fs::read(&out).unwrap() == bytes);
+    x.copy_from(&mut io::empty()).unwrap();
+    assert_eq!(std::fs::read(&out).unwrap(), b"");
+    let e = x.copy_from(&mut Failing(1 << 20)).unwrap_err();
+    assert!(
+        matches!(&e, Error::Read(e) if e.to_string() == "disk on fire"),
+        "{e}"
+    );
+    // while the helper's own failures are still its own
+    bin.add("xsel", "echo no display >&2; exit 1");
+    let e = x.copy_from(&mut &b"x"[..]).unwrap_err();
+    assert!(matches!(e, Error::CommandFailed { .. }), "{e}");
+}
+
+#[test]
+#[cfg(unix)]
+fn pasted_to() {
+    /// Takes so much, then fails.
+    struct Full(usize);
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+            let n = buf.len().min(self.0);
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let bin = Bin::new("pasted-to");
+    let on = bin.0.join("on");
+    bin.add("xsel", &format!("cat {}", on.display()));
+    let bytes = (0..6 << 20)
+        .map(|i: u32| (i % 253) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(&on, &bytes).unwrap();
+    let clip = crate::Clipboard::new(XSel::new(Arc::new(FromBin(bin.0.clone()))));
+    let mut v = vec![];
+    assert_eq!(clip.paste_to(&mut v).unwrap(), bytes.len() as u64);
+    assert!(v == bytes);
+    let e = clip.paste_to(&mut Full(1 << 20)).unwrap_err();
+    assert!(
+        matches!(&e, Error::Write(e) if e.kind() == io::ErrorKind::StorageFull),
+        "{e}"
+    );
+    // one that starts but never finishes is still given up on
+    bin.add("xsel", "printf some; sleep 10");
+    let x = XSel {
+        timeout: Duration::from_millis(200),
+        ..XSel::new(Arc::new(FromBin(bin.0.clone())))
+    };
+    let started = Instant::now();
+    assert!(matches!(
+        x.paste_to(&mut vec![]),
+        Err(Error::TimedOut { .. })
+    ));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+#[cfg(unix)]
+fn bytes() {
+    let bin = Bin::new("bytes");
+    let on = bin.0.join("on");
+    let on = on.display();
+    bin.add(
+        "xsel",
+        &format!("case $2 in -i) cat > {on};; -o) cat {on};; esac"),
+    );
+    let clip = crate::Clipboard::new(XSel::new(Arc::new(FromBin(bin.0.clone()))));
+    for bytes in [
+        &b"nul\0in the middle"[..],
+        b"\xff\xfe\0\0",
+        b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+        "text too 👋".as_bytes(),
+    ] {
+        clip.copy_bytes(bytes).unwrap();
+        assert_eq!(clip.paste_bytes().unwrap(), bytes);
+    }
+    assert_eq!(clip.paste().unwrap(), "text too 👋");
+}
+
+#[test]
+fn mime() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("wl-copy --type text/html", 0, b"", "")
+            .answer("xclip -selection c -t image/png", 0, b"", "")
+            .answer("copyq copy text/html -", 0, b"", "")
+            .answer("wl-copy --type text/plain;charset=utf-8", 0, b"", ""),
+    );
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    Wayland::new(r.clone())
+        .copy_mime("text/html", b"<b>hi</b>")
+        .unwrap();
+    XClip::new(r.clone()).copy_mime("image/png", png).unwrap();
+    CopyQ(r.clone())
+        .copy_mime("text/html", b"<i>hi</i>")
+        .unwrap();
+    assert_eq!(
+        *r.ran.lock().unwrap(),
+        [
+            (
+                "wl-copy --type text/html".into(),
+                Some(b"<b>hi</b>".to_vec())
+            ),
+            ("xclip -selection c -t image/png".into(), Some(png.to_vec())),
+            ("copyq copy text/html -".into(), Some(b"<i>hi</i>".to_vec())),
+        ]
+    );
+    // those that only take text say so
+    for unsupported in [
+        &XSel::new(r.clone()) as &dyn Backend,
+        &WindowsInterop::new(r.clone()),
+        &Klipper::new(r.clone(), &Fake::default()),
+    ] {
+        assert!(matches!(
+            unsupported.copy_mime("text/html", b"<b>hi</b>"),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+    assert_eq!(r.lines().len(), 3);
+    // what goes into argv is checked first
+    let clip = crate::Clipboard::new(Wayland::new(r.clone()));
+    for bad in [
+        "",
+        "html",
+        "text/html ",
+        "text/\nhtml",
+        "--clear",
+        "-t/x",
+        "tëxt/html",
+    ] {
+        let e = clip.copy_mime(bad, b"x").unwrap_err();
+        assert!(
+            matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::InvalidInput),
+            "{bad:?}: {e}"
+        );
+    }
+    assert_eq!(r.lines().len(), 3);
+    clip.copy_mime("text/plain;charset=utf-8", b"x").unwrap();
+    assert_eq!(r.lines()[3], "wl-copy --type text/plain;charset=utf-8");
+}
+
+#[test]
+fn multi() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("wl-copy --type text/html", 0, b"", "")
+            .answer("wl-copy --type image/png", 0, b"", "")
+            .answer("xsel -b -i", 0, b"", ""),
+    );
+    let png = b"\x89PNG\r\n\x1a\n";
+    let items: [(&str, &[u8]); 3] = [
+        ("image/png", png),
+        ("text/html", b"<b>hi</b>"),
+        ("text/plain", b"hi"),
+    ];
+    // one at a time, it is the first that is text
+    let wl = crate::Clipboard::new(Wayland::new(r.clone()));
+    assert!(!wl.copies_multi());
+    wl.copy_multi(&items).unwrap();
+    wl.copy_multi(&items[..1]).unwrap();
+    // and text only takes text
+    crate::Clipboard::new(XSel::new(r.clone()))
+        .copy_multi(&[items[0], items[2], items[1]])
+        .unwrap();
+    assert!(matches!(
+        crate::Clipboard::new(Klipper::new(r.clone(), &Fake::default())).copy_multi(&items[..1]),
+        Err(Error::Unsupported { .. })
+    ));
+    assert_eq!(
+        *r.ran.lock().unwrap(),
+        [
+            (
+                "wl-copy --type text/html".into(),
+                Some(b"<b>hi</b>".to_vec())
+            ),
+            ("wl-copy --type image/png".into(), Some(png.to_vec())),
+            ("xsel -b -i".into(), Some(b"hi".to_vec())),
+        ]
+    );
+    for bad in [
+        &[][..],
+        &[("html", &b"x"[..])],
+        &[items[2], ("--clear", b"")],
+    ] {
+        let e = wl.copy_multi(bad).unwrap_err();
+        assert!(
+            matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::InvalidInput),
+            "{e}"
+        );
+    }
+    assert_eq!(r.lines().len(), 3);
+}
+
+#[test]
+#[cfg(unix)]
+fn mime_round_trip() {
+    let bin = Bin::new("mime-round-trip");
+    // each type in a file of its own, named for it
+    let dir = bin.0.display();
+    bin.add(
+        "wl-copy",
+        &format!("rm -f {dir}/on-*; cat > {dir}/on-$(echo $2 | tr / _)"),
+    );
+    bin.add(
+        "wl-paste",
+        &format!(
+            "f={dir}/on-$(echo $2 | tr / _)
+            [ -e $f ] || {{ echo No suitable type of content copied >&2; exit 1; }}
+            cat $f"
+        ),
+    );
+    let clip = crate::Clipboard::new(Wayland::new(Arc::new(FromBin(bin.0.clone()))));
+    let html = b"<p>a <b>bold</b> move</p>\n";
+    clip.copy_mime("text/html", html).unwrap();
+    assert_eq!(clip.paste_mime("text/html").unwrap(), html);
+    let e = clip.paste_mime("image/png").unwrap_err();
+    assert!(
+        matches!(&e, Error::FormatUnavailable { mime } if mime == "image/png"),
+        "{e}"
+    );
+    assert_eq!(e.to_string(), "the clipboard has nothing as image/png");
+    assert_eq!(clip.paste_image_png().unwrap(), None);
+    let image = png(3, 2);
+    clip.copy_image_png(&image).unwrap();
+    assert_eq!(clip.paste_image_png().unwrap().unwrap(), image);
+    let e = clip.copy_image_png(b"GIF89a").unwrap_err();
+    assert!(matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::InvalidInput));
+    // rtf can be pasted, but not copied without text alongside it
+    assert_eq!(clip.paste_rtf().unwrap(), None);
+    let rtf = r"{\rtf1\ansi {\b bold} move}";
+    clip.copy_mime("text/rtf", rtf.as_bytes()).unwrap();
+    assert_eq!(clip.paste_rtf().unwrap().unwrap(), rtf);
+    assert!(matches!(
+        clip.copy_rtf(rtf, "bold move"),
+        Err(Error::Unsupported { .. })
+    ));
+    assert_eq!(clip.paste_rtf().unwrap().unwrap(), rtf);
+    // files, relative ones from where we are
+    assert_eq!(clip.paste_files().unwrap(), None);
+    let files = [Path::new("/tmp/my notes.txt"), Path::new("日本")];
+    clip.copy_files(&files).unwrap();
+    assert_eq!(
+        clip.paste_files().unwrap().unwrap(),
+        [
+            files[0].into(),
+            std::env::current_dir().unwrap().join("日本")
+        ]
+    );
+    assert_eq!(
+        clip.paste_mime("text/uri-list").unwrap(),
+        crate::uri::list(&[files[0], &std::env::current_dir().unwrap().join("日本")])
+            .unwrap()
+            .as_bytes()
+    );
+    // links aren't files
+    clip.copy_mime("text/uri-list", b"https://example.com/\r\n")
+        .unwrap();
+    assert_eq!(clip.paste_files().unwrap(), None);
+    // what can't take images says so
+    let r = Arc::new(Canned::default());
+    for clip in [
+        crate::Clipboard::new(WindowsInterop::new(r.clone())),
+        crate::Clipboard::new(Klipper::new(r.clone(), &Fake::default())),
+    ] {
+        assert!(matches!(
+            clip.copy_image_png(&image),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(matches!(
+            clip.paste_image_png(),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+    assert!(r.lines().is_empty());
+}
+
+/// A `w` by `h` PNG, of RGB stripes, that needs nothing more than it has to be one.
+#[cfg(test)]
+pub fn png(w: u32, h: u32) -> Vec<u8> {
+    fn crc(bytes: &[u8]) -> u32 {
+        !bytes.iter().fold(!0, |crc, &b| {
+            (0..8).fold(crc ^ u32::from(b), |c, _| {
+                (c >> 1) ^ (0xedb8_8320 & (c & 1).wrapping_neg())
+            })
+        })
+    }
+    fn chunk(png: &mut Vec<u8>, ty: &[u8], data: &[u8]) {
+        png.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+        let at = png.len();
+        png.extend_from_slice(ty);
+        png.extend_from_slice(data);
+        let crc = crc(&png[at..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    // each row, after the filter type before it
+    let rows: Vec<u8> = (0..h)
+        .flat_map(|y| {
+            std::iter::once(0).chain((0..w).flat_map(move |x| {
+                let on = u8::try_from((x + y) % 2).unwrap() * 0xff;
+                [on, 0x80, !on]
+            }))
+        })
+        .collect();
+    // zlib, with the rows stored as they are
+    let len = u16::try_from(rows.len()).unwrap();
+    let (a, b) = rows.iter().fold((1u32, 0u32), |(a, b), &r| {
+        let a = (a + u32::from(r)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    let mut zlib = vec![0x78, 0x01, 0x01];
+    zlib.extend_from_slice(&len.to_le_bytes());
+    zlib.extend_from_slice(&(!len).to_le_bytes());
+    zlib.extend_from_slice(&rows);
+    zlib.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let ihdr = [&w.to_be_bytes()[..], &h.to_be_bytes(), &[8, 2, 0, 0, 0]].concat();
+    chunk(&mut png, b"IHDR", &ihdr);
+    chunk(&mut png, b"IDAT", &zlib);
+    chunk(&mut png, b"IEND", b"");
+    png
+}
+
+#[test]
+fn generated_png() {
+    // what `file` and every decoder check first
+    let image = png(1, 1);
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&image[12..16], b"IHDR");
+    assert!(image.ends_with(&[0xae, 0x42, 0x60, 0x82]));
+}
+
+#[test]
+fn mime_unavailable() {
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                "xclip -selection c -o -t text/html",
+                1,
+                b"",
+                "Error: target text/html not available\n",
+            )
+            .answer("xclip -selection c -o -t image/png", 0, b"\x89PNG\0", "")
+            .answer("copyq clipboard ?", 0, b"text/plain\ntext/html\n", "")
+            .answer("copyq clipboard text/html", 0, b"<b>hi</b>", ""),
+    );
+    let x = XClip::new(r.clone());
+    assert!(matches!(
+        x.paste_mime("text/html"),
+        Err(Error::FormatUnavailable { mime }) if mime == "text/html"
+    ));
+    assert_eq!(x.paste_mime("image/png").unwrap(), b"\x89PNG\0");
+    let q = CopyQ(r.clone());
+    assert_eq!(q.paste_mime("text/html").unwrap(), b"<b>hi</b>");
+    assert!(matches!(
+        q.paste_mime("image/png"),
+        Err(Error::FormatUnavailable { .. })
+    ));
+    assert!(matches!(
+        XSel::new(r).paste_mime("text/html"),
+        Err(Error::Unsupported { .. })
+    ));
+}
+
+/// What `xclip -selection c -o -t TARGETS` printed after copying part of a page in Chromium.
+#[cfg(test)]
+const XCLIP_TARGETS: &str = "TIMESTAMP
+TARGETS
+SAVE_TARGETS
+MULTIPLE
+STRING
+TEXT
+UTF8_STRING
+text/plain
+text/plain;charset=utf-8
+text/html
+chromium/x-source-url
+";
+
+/// What `wl-paste --list-types` printed after copying part of a page in Firefox.
+#[cfg(test)]
+const WL_PASTE_TYPES: &str = "text/html
+text/_moz_htmlcontext
+text/_moz_htmlinfo
+UTF8_STRING
+COMPOUND_TEXT
+TEXT
+STRING
+text/plain;charset=utf-8
+text/plain
+text/x-moz-url-priv
+";
+
+#[test]
+fn listed_targets() {
+    assert_eq!(
+        targets(XCLIP_TARGETS),
+        [
+            "text/plain",
+            "text/plain;charset=utf-8",
+            "text/html",
+            "chromium/x-source-url"
+        ]
+    );
+    assert_eq!(
+        targets(WL_PASTE_TYPES),
+        [
+            "text/html",
+            "text/_moz_htmlcontext",
+            "text/_moz_htmlinfo",
+            "text/plain;charset=utf-8",
+            "text/plain",
+            "text/x-moz-url-priv"
+        ]
+    );
+    assert!(targets("").is_empty());
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                "xclip -selection c -o -t TARGETS",
+                0,
+                XCLIP_TARGETS.as_bytes(),
+                "",
+            )
+            .answer("wl-paste --list-types", 1, b"", "Nothing is copied\n"),
+    );
+    assert_eq!(XClip::new(r.clone()).targets().unwrap()[2], "text/html");
+    // nothing there isn't the same as not being able to tell
+    assert_eq!(
+        Wayland::new(r.clone()).targets().unwrap(),
+        Vec::<String>::new()
+    );
+    assert!(matches!(
+        XSel::new(r).targets(),
+        Err(Error::Unsupported { .. })
+    ));
+    let r = Arc::new(Canned::default().answer(
+        "xclip -selection c -o -t TARGETS",
+        1,
+        b"",
+        "Error: target TARGETS not available\n",
+    ));
+    assert!(XClip::new(r).targets().unwrap().is_empty());
+}
+
+#[test]
+fn html_format() {
+    let v = cf_html(b"<b>hi</b>");
+    assert_eq!(fragment(&v).unwrap(), b"<b>hi</b>");
+    assert_eq!(fragment(b"Version:0.9\r\n"), None);
+    let s = std::str::from_utf8(&v).unwrap();
+    let at = |s: &str, key: &str| -> usize {
+        let (_, rest) = s.split_once(&format!("{key}:")).unwrap();
+        rest[..10].parse().unwrap()
+    };
+    // as windows has it, every offset ten digits, and the header before the html
+    assert!(s.starts_with("Version:0.9\r\nStartHTML:0000000105\r\nEndHTML:0000000"));
+    assert!(s[at(s, "StartHTML")..].starts_with("<html>"));
+    assert_eq!(at(s, "EndHTML"), s.len());
+    assert_eq!(
+        &s[at(s, "StartFragment")..at(s, "EndFragment")],
+        "<b>hi</b>"
+    );
+    assert!(s[..at(s, "StartFragment")].ends_with("<!--StartFragment-->"));
+    // the offsets count bytes, not characters
+    let wide = "<p>héllo wörld 👋</p>";
+    let v = cf_html(wide.as_bytes());
+    assert_eq!(fragment(&v).unwrap(), wide.as_bytes());
+    assert_eq!(at(std::str::from_utf8(&v).unwrap(), "EndHTML"), v.len());
+    // as does nothing at all
+    assert_eq!(fragment(&cf_html(b"")).unwrap(), b"");
+    // what chromium puts there
+    let captured = "Version:0.9\r\nStartHTML:0000000105\r\nEndHTML:0000000240\r\n\
+        StartFragment:0000000141\r\nEndFragment:0000000204\r\n<html>\r\n<body>\r\n\
+        <!--StartFragment--><a href=\"https://example.com/\">a link</a> and <em>emphasis</em>\
+        <!--EndFragment-->\r\n</body>\r\n</html>";
+    assert_eq!(
+        fragment(captured.as_bytes()).unwrap(),
+        br#"<a href="https://example.com/">a link</a> and <em>emphasis</em>"#
+    );
+    // offsets that point past the end are no fragment at all
+    assert_eq!(fragment(&captured.as_bytes()[..200]), None);
+}
+
+#[test]
+fn streamed_empty() {
+    let r = Arc::new(Canned::default().answer("wl-copy --clear", 0, b"", ""));
+    Wayland::new(r.clone()).copy_from(&mut io::empty()).unwrap();
+    assert_eq!(r.lines(), ["wl-copy --clear"]);
+    // and what can only take text still takes it
+    let clip = crate::Clipboard::new(crate::clipboard::Scratch::default());
+    clip.copy_from(&mut "streamed".as_bytes()).unwrap();
+    assert_eq!(clip.paste().unwrap(), "streamed");
+    assert!(matches!(
+        clip.copy_from(&mut &b"\xff"[..]),
+        Err(Error::Unsupported { .. })
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn chatty() {
+    let bin = Bin::new("chatty");
+    let out = bin.0.join("out");
+    // far more on stderr than a pipe holds, before reading any of stdin
+    bin.add(
+        "chatty",
+        &format!(
+            "head -c 1000000 /dev/zero | tr '\\0' e >&2; cat > {}; echo done >&2",
+            out.display()
+        ),
+    );
+    let big = "0123456789abcdef\n".repeat(50 << 20 >> 4);
+    // it would wait forever if it got stuck, but not this long
+    Command::new(bin.0.join("chatty"))
+        .put_within(&Real, &big, Some(Duration::from_secs(30)))
+        .unwrap();
+    assert!(std::fs::read(&out).unwrap() == big.as_bytes());
+    // and what a failing one says still comes through whole
+    bin.add(
+        "chatty",
+        "head -c 1000000 /dev/zero | tr '\\0' e >&2; exit 3",
+    );
+    let e = Command::new(bin.0.join("chatty"))
+        .eat_within(&Real, None)
+        .unwrap_err();
+    assert!(
+        matches!(e, Error::CommandFailed { status: Some(3), stderr, .. } if stderr.len() == 1_000_000)
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn given_up() {
+    let bin = Bin::new("given-up");
+    let out = bin.0.join("out");
+    bin.add("xsel", &format!("sleep 1; cat > {}", out.display()));
+    let clip = crate::Clipboard::new(XSel::new(Arc::new(FromBin(bin.0.clone()))));
+    let e = clip
+        .copy_with_timeout("late", Duration::from_millis(200))
+        .unwrap_err();
+    assert!(matches!(e, Error::TimedOut { .. }), "{e}");
+    // and it doesn't land after all
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(!out.exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn abandoned() {
+    let bin = Bin::new("abandoned");
+    let pid = bin.0.join("pid");
+    // never reads what it is given, nor finishes
+    bin.add(
+        "stuck",
+        &format!("echo $$ > {}; exec sleep 30 0<&-", pid.display()),
+    );
+    let stuck = || Command::new(bin.0.join("stuck"));
+    let gone = || {
+        let pid = std::fs::read_to_string(&pid).unwrap();
+        !Path::new("/proc").join(pid.trim()).exists()
+    };
+    // more than a pipe holds, so writing it fails
+    let e = stuck()
+        .put_within(&Real, vec![b'x'; 1 << 20], None)
+        .unwrap_err();
+    assert!(
+        matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::BrokenPipe),
+        "{e}"
+    );
+    assert!(gone());
+    assert!(stuck()
+        .eat_within(&Real, Some(Duration::from_millis(200)))
+        .is_err());
+    assert!(gone());
+    // or by a per-call timeout, before it is given up on
+    std::fs::rename(bin.0.join("stuck"), bin.0.join("xsel")).unwrap();
+    std::fs::remove_file(&pid).unwrap();
+    let clip = crate::Clipboard::new(XSel::new(Arc::new(FromBin(bin.0.clone()))));
+    let e = clip
+        .paste_with_timeout(Duration::from_millis(200))
+        .unwrap_err();
+    assert!(matches!(e, Error::TimedOut { .. }), "{e}");
+    assert!(gone());
+}
+
+#[test]
+#[cfg(all(target_family = "windows", feature = "windows-native"))]
+fn windows() {
+    if !integration() {
+        return;
+    }
+    let _restore = Restore::new(&Windows);
+    let big = "👋 ".repeat((1 << 20) / 5);
+    for text in ["line one\r\nline two", "héllo wörld 👋 日本語", &big] {
+        Windows.copy(text).unwrap();
+        assert_eq!(Windows.paste().unwrap(), text);
+    }
+    Windows.clear().unwrap();
+    assert_eq!(Windows.paste().unwrap(), "");
+    Windows.copy_concealed("hunter2").unwrap();
+    assert_eq!(Windows.paste().unwrap(), "hunter2");
+    for name in [
+        "ExcludeClipboardContentFromMonitorProcessing",
+        "CanIncludeInClipboardHistory",
+    ] {
+        let format = clipboard_win::register_format(name).unwrap().get();
+        assert!(clipboard_win::is_format_avail(format), "{name}");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn put() {
+    assert!(c!("sh" "-c" "cat >/dev/null")
+        .put_within(&Real, "text", None)
+        .is_ok());
+    let e = c!("sh" "-c" "cat >/dev/null; echo no selection for you >&2; exit 3")
+        .put_within(&Real, "text", None)
+        .unwrap_err();
+    assert!(e.to_string().starts_with("sh was not successful"));
+    assert!(e.to_string().ends_with(": no selection for you"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn detection() {
+    let bin = Bin::new("detection");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    assert!(matches!(detect(&env, &[]), Err(Error::NoDisplay)));
+    env.vars.insert("WAYLAND_DISPLAY", "wayland-0".into());
+    assert!(matches!(detect(&env, &[]), Err(Error::NoProvider)));
+    bin.add("xsel", "");
+    bin.add("xclip", "");
+    // wayland, without xwayland
+    assert!(matches!(detect(&env, &[]), Err(Error::NoProvider)));
+    env.vars.insert("DISPLAY", ":0".into());
+    assert_eq!(besides_x11(&env, &[]), [Provider::XSel, Provider::XClip]);
+    env.vars.insert("DISPLAY", "".into());
+    assert!(!Provider::XSel.available(&env));
+    // a text console, that DISPLAY was left set in
+    env.vars.insert("DISPLAY", ":0".into());
+    env.vars.insert("XDG_SESSION_TYPE", "tty".into());
+    assert!(!Provider::XSel.available(&env));
+    assert!(!Provider::XClip.available(&env));
+    env.vars.insert("XDG_SESSION_TYPE", "x11".into());
+    assert!(Provider::XSel.available(&env));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn resolved() {
+    /// Runs them without a PATH, as some hosts leave their plugins.
+    struct Cleared;
+    impl Runner for Cleared {
+        fn output(
+            &self,
+            c: &mut Command,
+            input: Option<&[u8]>,
+            read: bool,
+            timeout: Option<Duration>,
+        ) -> io::Result<Output> {
+            Real.output(c.env_remove("PATH"), input, read, timeout)
+        }
+    }
+    let bin = Bin::new("resolved");
+    let copied = bin.0.join("copied");
+    bin.add("xsel", &format!("cat > {}", copied.display()));
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("DISPLAY", ":0".into());
+    assert!(Provider::XSel.available(&env));
+    let x = Provider::XSel
+        .found(&crate::Options::default(), &env, Arc::new(Cleared))
+        .unwrap();
+    x.copy("found").unwrap();
+    assert_eq!(std::fs::read_to_string(&copied).unwrap(), "found");
+    // which going by its name alone can't
+    assert!(matches!(
+        XSel::new(Arc::new(Cleared)).copy("lost"),
+        Err(Error::CommandNotFound { command }) if command == "xsel"
+    ));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn sandboxed() {
+    let bin = Bin::new("sandboxed");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    // which is no reason to keep the clipboard in a file
+    env.vars.insert("HOME", "/home/me".into());
+    env.vars.insert("WAYLAND_DISPLAY", "wayland-0".into());
+    env.vars.insert("container", "podman".into());
+    let e = detect(&env, &[]).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "running inside a container (podman), where clipboard helpers are not visible"
+    );
+    env.vars.insert("SNAP_NAME", "editor".into());
+    assert!(matches!(
+        detect(&env, &[]),
+        Err(Error::Sandboxed { sandbox }) if sandbox == "Snap"
+    ));
+    env.files.insert("/.flatpak-info", "[Application]\n".into());
+    assert!(matches!(
+        detect(&env, &[]),
+        Err(Error::Sandboxed { sandbox }) if sandbox == "Flatpak"
+    ));
+    // it only matters when nothing else is there
+    bin.add("wl-copy", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Wayland);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn forced() {
+    let bin = Bin::new("forced");
+    bin.add("xsel", "");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("DISPLAY", ":0".into());
+    env.vars.insert("CLIPP_PROVIDER", "xsel".into());
+    assert!(detect(&env, &[]).is_ok());
+    env.vars.insert("CLIPP_PROVIDER", "xclip".into());
+    assert!(matches!(
+        detect(&env, &[]),
+        Err(Error::CommandNotFound { command }) if command == "xclip"
+    ));
+    env.vars.insert("CLIPP_PROVIDER", "xlcip".into());
+    let e = detect(&env, &[]).err().unwrap();
+    assert!(matches!(&e, Error::UnknownProvider { name } if name == "xlcip"));
+    let names = if cfg!(feature = "x11-native") {
+        "wl, interop, x11, xsel, xclip, klipper, gpaste, copyq"
+    } else {
+        "wl, interop, xsel, xclip, klipper, gpaste, copyq"
+    };
+    assert!(e.to_string().contains(names), "{e}");
+    for &p in Provider::ALL {
+        assert_eq!(p.name().parse::<Provider>().unwrap(), p);
+    }
+    // unset, or empty, is the same as not having it
+    env.vars.insert("CLIPP_PROVIDER", "".into());
+    assert!(detect(&env, &[]).is_ok());
+    env.vars.remove("CLIPP_PROVIDER");
+    assert!(detect(&env, &[]).is_ok());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn availability() {
+    let bin = Bin::new("availability");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("DISPLAY", ":0".into());
+    assert_eq!(besides_x11(&env, &[]).len(), 0);
+    bin.add("xclip", "");
+    bin.add("xsel", "");
+    assert_eq!(besides_x11(&env, &[]), [Provider::XSel, Provider::XClip]);
+    bin.add("wl-copy", "");
+    // not without the compositor
+    assert_eq!(besides_x11(&env, &[]).len(), 2);
+    env.vars.insert("WAYLAND_DISPLAY", "wayland-0".into());
+    assert_eq!(
+        besides_x11(&env, &[]),
+        [Provider::Wayland, Provider::XSel, Provider::XClip]
+    );
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Wayland);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn preferred() {
+    let bin = Bin::new("preferred");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("DISPLAY", ":0".into());
+    bin.add("xclip", "");
+    bin.add("xsel", "");
+    assert_eq!(besides_x11(&env, &[])[0], Provider::XSel);
+    assert_eq!(detect(&env, &[Provider::XClip]).unwrap(), Provider::XClip);
+    // unavailable preferences are skipped
+    assert_eq!(
+        detect(&env, &[Provider::Klipper, Provider::XClip]).unwrap(),
+        Provider::XClip
+    );
+    assert_eq!(
+        besides_x11(&env, &[Provider::XClip]),
+        [Provider::XClip, Provider::XSel]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn qdbus() {
+    for qdbus in &KLIPPER_TOOLS[..4] {
+        let bin = Bin::new(&format!("{qdbus}-only"));
+        let mut env = Fake::default();
+        env.vars.insert("PATH", bin.0.clone().into());
+        bin.add(qdbus, "");
+        bin.add("klipper", "");
+        assert_eq!(detect(&env, &[]).unwrap(), Provider::Klipper);
+        assert_eq!(Provider::Klipper.check(&env).unwrap(), Provider::Klipper);
+        let r = Arc::new(
+            Canned::default()
+                .answer(
+                    &format!("{qdbus} org.kde.klipper /klipper getClipboardContents"),
+                    0,
+                    b"mine\n",
+                    "",
+                )
+                .answer(
+                    &format!("{qdbus} org.kde.klipper /klipper setClipboardContents x"),
+                    0,
+                    b"",
+                    "",
+                ),
+        );
+        let k = Klipper::new(r.clone(), &env);
+        assert_eq!(k.paste().unwrap(), "mine");
+        k.copy("x").unwrap();
+        // it only takes text, and says so rather than mangling anything else
+        let clip = crate::Clipboard::new(k);
+        clip.copy_bytes(b"x").unwrap();
+        assert!(matches!(
+            clip.copy_bytes(b"\xff"),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+    assert!(matches!(
+        Provider::Klipper.check(&Fake::default()),
+        Err(Error::CommandNotFound { command }) if command == "qdbus"
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn dbus_send() {
+    let bin = Bin::new("dbus-send-only");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    bin.add("klipper", "");
+    assert!(detect(&env, &[]).is_err());
+    bin.add("dbus-send", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Klipper);
+    let send =
+        "dbus-send --session --print-reply --dest=org.kde.klipper /klipper org.kde.klipper.klipper";
+    let head = "method return time=1700000000.000 sender=:1.23 -> destination=:1.99 serial=45 reply_serial=2\n";
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                &format!("{send}.getClipboardContents"),
+                0,
+                format!("{head}   string \"say \"hi\",\n\\n  \"\n\"\n").as_bytes(),
+                "",
+            )
+            .answer(
+                &format!("{send}.setClipboardContents string:a,b \"c\""),
+                0,
+                b"",
+                "",
+            )
+            .answer(&format!("{send}.clearClipboardContents"), 0, b"", ""),
+    );
+    let k = Klipper {
+        tool: "dbus-send",
+        ..Klipper::new(r.clone(), &Fake::default())
+    };
+    assert_eq!(k.paste().unwrap(), "say \"hi\",\n\\n  \"\n");
+    k.copy("a,b \"c\"").unwrap();
+    k.clear().unwrap();
+    assert!(matches!(
+        k.copy(&"x".repeat(MAX_ARG + 1)),
+        Err(Error::TooLong { len, max: MAX_ARG }) if len == MAX_ARG + 1
+    ));
+    assert_eq!(r.lines().len(), 3);
+}
+
+#[test]
+fn dbus_send_replies() {
+    let head = "method return time=1.0 sender=:1.2 -> destination=:1.3 serial=4 reply_serial=2\n";
+    assert_eq!(unquote(&format!("{head}   string \"\"\n")).unwrap(), "");
+    assert_eq!(
+        unquote(&format!("{head}   string \"\"\"\"\n")).unwrap(),
+        "\"\""
+    );
+    assert_eq!(
+        unquote(&format!("{head}   string \"a\n\n\tb\\\"\n")).unwrap(),
+        "a\n\n\tb\\"
+    );
+    assert!(unquote(&format!("{head}   int32 3\n")).is_err());
+    assert!(unquote("").is_err());
+}
+
+#[test]
+fn klipper() {
+    let qdbus = "qdbus org.kde.klipper /klipper";
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                &format!("{qdbus} getClipboardContents"),
+                0,
+                b"two\nlines\n",
+                "",
+            )
+            .answer(&format!("{qdbus} setClipboardContents -n x"), 0, b"", "")
+            .answer(&format!("{qdbus} clearClipboardContents"), 0, b"", ""),
+    );
+    let k = Klipper {
+        tool: "qdbus",
+        ..Klipper::new(r.clone(), &Fake::default())
+    };
+    // qdbus adds a newline of its own, and only that one goes
+    assert_eq!(k.paste().unwrap(), "two\nlines");
+    // the text is an argument, not stdin, so it must not be taken for an option
+    k.copy("-n x").unwrap();
+    k.clear().unwrap();
+    assert_eq!(r.ran.lock().unwrap()[1].1, None);
+    assert!(k.copy("unanswered").is_err());
+}
+
+#[test]
+fn gpaste() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("gpaste-client get 0", 0, b"two\nlines\n\n", "")
+            .answer("gpaste-client add", 0, b"", "")
+            .answer("gpaste-client delete 0", 0, b"", ""),
+    );
+    let g = GPaste::new(r.clone());
+    // only the newline it adds goes
+    assert_eq!(g.paste().unwrap(), "two\nlines\n");
+    g.copy("-n x").unwrap();
+    assert_eq!(r.ran.lock().unwrap()[1].1.as_deref(), Some(&b"-n x"[..]));
+    g.clear().unwrap();
+}
+
+#[test]
+fn gpaste_empty() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("gpaste-client get 0", 1, b"", "Error: no such item")
+            .answer("gpaste-client delete 0", 1, b"", "Error: no such item")
+            .answer("gpaste-client history-size", 0, b"0\n", ""),
+    );
+    let g = GPaste::new(r.clone());
+    assert_eq!(g.paste().unwrap(), "");
+    g.clear().unwrap();
+    // but failing with something in it is still failing
+    let r = Arc::new(
+        Canned::default()
+            .answer("gpaste-client get 0", 1, b"", "Error: the daemon is gone")
+            .answer("gpaste-client history-size", 0, b"3\n", ""),
+    );
+    assert!(matches!(
+        GPaste::new(r).paste(),
+        Err(Error::CommandFailed { stderr, .. }) if stderr.contains("daemon")
+    ));
+}
+
+#[test]
+fn gpaste_round_trip() {
+    if !integration() || !System.has("gpaste-client") {
+        return;
+    }
+    let g = GPaste::new(real());
+    let _restore = Restore::new(&g);
+    // clearing only goes back through its history, so that isn't tried here
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        g.copy(text).unwrap();
+        assert_eq!(g.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn copyq() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("copyq clipboard", 0, b"two\nlines\n", "")
+            .answer("copyq copy -", 0, b"", ""),
+    );
+    let q = CopyQ(r.clone());
+    // nothing of its own is added
+    assert_eq!(q.paste().unwrap(), "two\nlines\n");
+    q.copy("-- secret").unwrap();
+    assert_eq!(
+        r.ran.lock().unwrap()[1].1.as_deref(),
+        Some(&b"-- secret"[..])
+    );
+    // without its server, it waits instead of failing
+    let q = CopyQ(Arc::new(Canned::default().hang("copyq clipboard")));
+    let e = q.paste().unwrap_err().to_string();
+    assert!(e.starts_with("timed out waiting for copyq"), "{e}");
+    assert!(e.contains("--start-server"));
+}
+
+#[test]
+fn concealed() {
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                "copyq copy text/plain - x-kde-passwordManagerHint secret",
+                0,
+                b"",
+                "",
+            )
+            .answer("xsel -b -i", 0, b"", ""),
+    );
+    let q = CopyQ(r.clone());
+    assert!(q.conceals());
+    q.copy_concealed("hunter2").unwrap();
+    // the ones that can't say so are copied to all the same
+    let x = XSel::new(r.clone());
+    assert!(!x.conceals());
+    x.copy_concealed("hunter2").unwrap();
+    assert_eq!(
+        *r.ran.lock().unwrap(),
+        [
+            (
+                "copyq copy text/plain - x-kde-passwordManagerHint secret".into(),
+                Some(b"hunter2".to_vec())
+            ),
+            ("xsel -b -i".into(), Some(b"hunter2".to_vec())),
+        ]
+    );
+    for plain in [
+        &Wayland::new(r.clone()) as &dyn Backend,
+        &WindowsInterop::new(r.clone()),
+        &GPaste::new(r.clone()),
+    ] {
+        assert!(!plain.conceals());
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn copyq_server() {
+    let bin = Bin::new("copyq-server");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    bin.add("copyq", "exit 1");
+    assert!(!Provider::CopyQ.available(&env));
+    // nor when the server never answers
+    bin.add("copyq", "sleep 5");
+    let started = Instant::now();
+    assert!(!Provider::CopyQ.available(&env));
+    assert!(started.elapsed() < Duration::from_secs(3));
+    bin.add("copyq", r#"[ "$1" = info ]"#);
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::CopyQ);
+    // but it is no more than a fallback
+    bin.add("gpaste-client", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+}
+
+#[test]
+fn copyq_round_trip() {
+    if !integration() || !System.answers("copyq", &["info"]) {
+        return;
+    }
+    let q = CopyQ(real());
+    let _restore = Restore::new(&q);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語", ""] {
+        q.copy(text).unwrap();
+        assert_eq!(q.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn wsl() {
+    let ps = "powershell.exe -noprofile -command";
+    let r = Arc::new(
+        Canned::default()
+            .answer(
+                &format!("{ps} {}", WindowsInterop::PASTE),
+                0,
+                b"line one\r\nline two\r\n",
+                "",
+            )
+            .answer(&format!("{ps} {}", WindowsInterop::COPY), 0, b"", "")
+            .answer(&format!("{ps} Set-Clipboard -Value $null"), 0, b"", ""),
+    );
+    let w = WindowsInterop::new(r.clone());
+    assert_eq!(w.paste().unwrap(), "line one\r\nline two");
+    w.copy("héllo").unwrap();
+    assert_eq!(
+        r.ran.lock().unwrap()[1].1.as_deref(),
+        Some("héllo".as_bytes())
+    );
+    w.copy("").unwrap();
+    assert!(r.lines()[2].ends_with("$null"));
+}
+
+#[test]
+fn interop_round_trip() {
+    if !integration() || !Provider::WindowsInterop.is_available() {
+        return;
+    }
+    let w = WindowsInterop::new(real());
+    let _restore = Restore::new(&w);
+    for text in [
+        "héllo wörld",
+        "👋 🦀",
+        "日本語のテキスト",
+        "one\r\ntwo",
+        "one\ntwo\n",
+        "ends with crlf\r\n",
+    ] {
+        w.copy(text).unwrap();
+        assert_eq!(w.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn wayland() {
+    for nothing in ["Nothing is copied\n", "No selection\n"] {
+        let r = Canned::default().answer("wl-paste -n", 1, b"", nothing);
+        assert_eq!(Wayland::new(Arc::new(r)).paste().unwrap(), "");
+    }
+    let r = Canned::default().answer(
+        "wl-paste -n",
+        1,
+        b"",
+        "Failed to connect to a Wayland server\n",
+    );
+    assert!(matches!(
+        Wayland::new(Arc::new(r)).paste(),
+        Err(Error::CommandFailed { status: Some(1), stderr, .. }) if stderr.contains("Failed to connect")
+    ));
+    let r = Arc::new(Canned::default().answer("wl-copy", 0, b"", "").answer(
+        "wl-copy --clear",
+        0,
+        b"",
+        "",
+    ));
+    let w = Wayland::new(r.clone());
+    w.copy("text").unwrap();
+    // wl-copy with nothing on stdin would copy an empty line instead
+    w.copy("").unwrap();
+    assert_eq!(r.lines(), ["wl-copy", "wl-copy --clear"]);
+}
+
+#[test]
+fn x11() {
+    let r = Canned::default().answer(
+        "xclip -selection c -o",
+        1,
+        b"",
+        "Error: target STRING not available\n",
+    );
+    assert_eq!(XClip::new(Arc::new(r)).paste().unwrap(), "");
+    let r = Arc::new(
+        Canned::default()
+            .answer("xsel -b -o", 0, b"\xff", "")
+            .answer("xsel -b -c", 0, b"", ""),
+    );
+    let x = XSel::new(r.clone());
+    assert!(matches!(x.paste(), Err(Error::InvalidUtf8)));
+    x.clear().unwrap();
+    assert!(matches!(
+        x.copy("nope"),
+        Err(Error::CommandNotFound { command }) if command == "xsel"
+    ));
+    assert_eq!(r.lines(), ["xsel -b -o", "xsel -b -c", "xsel -b -i"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn x11_gone() {
+    // what xsel does when DISPLAY is a forward whose other end has gone quiet
+    let bin = Bin::new("x11-gone");
+    bin.add(
+        "xsel",
+        r#"[ "$2" = -i ] && exec cat >/dev/null; exec sleep 30"#,
+    );
+    let x = XSel {
+        timeout: Duration::from_millis(200),
+        ..XSel::new(Arc::new(FromBin(bin.0.clone())))
+    };
+    let started = Instant::now();
+    let e = x.paste().unwrap_err();
+    assert_eq!(e.to_string(), "timed out waiting for xsel");
+    assert!(x.clear().is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // and it doesn't get in the way of those that answer
+    x.copy("quick").unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn hung() {
+    // not only xclip and xsel are given the timeout
+    let bin = Bin::new("hung");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    let o = crate::Options::new().timeout(Duration::from_millis(200));
+    for (provider, programs) in [
+        (Provider::Wayland, &["wl-copy", "wl-paste"][..]),
+        (Provider::Klipper, &["qdbus"]),
+        (Provider::GPaste, &["gpaste-client"]),
+        (Provider::Kitty, &["kitten"]),
+        (Provider::Plan9Snarf, &["9"]),
+        (Provider::WindowsInterop, &["powershell.exe"]),
+    ] {
+        for program in programs {
+            bin.add(program, "exec sleep 30");
+        }
+        let b = provider.found(&o, &env, real()).unwrap();
+        let started = Instant::now();
+        // more than a pipe holds, for those it goes to on stdin
+        assert!(
+            matches!(b.copy(&"x".repeat(100_000)), Err(Error::TimedOut { .. })),
+            "{provider:?}"
+        );
+        assert!(
+            matches!(b.paste(), Err(Error::TimedOut { .. })),
+            "{provider:?}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "x11-native"))]
+fn x11_native() {
+    let bin = Bin::new("x11-native");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    bin.add("xclip", "");
+    assert!(detect(&env, &[]).is_err());
+    env.vars.insert("DISPLAY", ":0".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::X11);
+    assert_eq!(
+        available(&env, &[]).collect::<Vec<_>>(),
+        [Provider::X11, Provider::XClip]
+    );
+    env.vars.insert("XDG_SESSION_TYPE", "tty".into());
+    assert!(detect(&env, &[]).is_err());
+    assert_eq!("x11".parse::<Provider>().unwrap(), Provider::X11);
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "osc52"))]
+fn over_ssh() {
+    let bin = Bin::new("over-ssh");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("SSH_TTY", "/dev/pts/3".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Osc52);
+    // anything that can paste too comes first
+    bin.add("gpaste-client", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+    env.vars.remove("SSH_TTY");
+    assert_eq!(available(&env, &[]).collect::<Vec<_>>(), [Provider::GPaste]);
+}
+
+#[test]
+#[cfg(unix)]
+fn timeout() {
+    let started = Instant::now();
+    let e = c!("sleep" "5")
+        .eat_within(&Real, Some(Duration::from_millis(100)))
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(e.to_string(), "timed out waiting for sleep");
+    assert_eq!(
+        c!("echo" "quick")
+            .eat_within(&Real, Some(Duration::from_secs(5)))
+            .unwrap(),
+        "quick\n"
+    );
+    assert!(c!("sh" "-c" "cat >/dev/null")
+        .put_within(&Real, "text", Some(Duration::from_secs(5)))
+        .is_ok());
+    // one that never reads what it is given can't keep the write waiting either
+    let started = Instant::now();
+    let e = c!("sleep" "5")
+        .put_within(&Real, vec![b'x'; 1 << 20], Some(Duration::from_millis(100)))
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(e.to_string(), "timed out waiting for sleep");
+}
+
+#[test]
+#[cfg(unix)]
+fn kitty() {
+    let bin = Bin::new("kitty");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("SSH_TTY", "/dev/pts/1".into());
+    env.vars.insert("TERM", "xterm-kitty".into());
+    assert!(!Provider::Kitty.available(&env));
+    bin.add("kitty", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Kitty);
+    let r = Arc::new(
+        Canned::default()
+            .answer("kitty +kitten clipboard --get-clipboard", 0, b"old", "")
+            .answer("kitten clipboard", 0, b"", "")
+            .answer(
+                "kitten clipboard --get-clipboard",
+                1,
+                b"",
+                "Failed to read clipboard: permission denied\n",
+            ),
+    );
+    assert_eq!(Kitty::new(r.clone(), &env).paste().unwrap(), "old");
+    bin.add("kitten", "");
+    let k = Kitty::new(r.clone(), &env);
+    k.copy("new").unwrap();
+    assert_eq!(r.ran.lock().unwrap()[1].1.as_deref(), Some(&b"new"[..]));
+    let e = k.paste().unwrap_err();
+    assert!(matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::PermissionDenied));
+    assert!(e.to_string().contains("clipboard_control"));
+    // in kitty, but somewhere TERM was changed
+    env.vars.remove("TERM");
+    assert!(!Provider::Kitty.available(&env));
+    env.vars.insert("KITTY_WINDOW_ID", "1".into());
+    assert!(Provider::Kitty.available(&env));
+}
+
+#[test]
+fn kitty_round_trip() {
+    if !integration() || !Provider::Kitty.is_available() {
+        return;
+    }
+    let k = Kitty::new(real(), &System);
+    let _restore = Restore::new(&k);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        k.copy(text).unwrap();
+        assert_eq!(k.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn networked() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("lemonade paste", 0, b"from afar", "")
+            .answer("lemonade copy", 0, b"", "")
+            .answer("piknik -paste", 0, b"from afar", "")
+            .answer("piknik -copy", 0, b"", ""),
+    );
+    assert_eq!(Lemonade(r.clone()).paste().unwrap(), "from afar");
+    Lemonade(r.clone()).copy("away").unwrap();
+    assert_eq!(Piknik(r.clone()).paste().unwrap(), "from afar");
+    Piknik(r.clone()).copy("away").unwrap();
+    let ran = r.ran.lock().unwrap();
+    assert_eq!(ran[1].1.as_deref(), Some(&b"away"[..]));
+    assert_eq!(ran[3].1.as_deref(), Some(&b"away"[..]));
+    drop(ran);
+    // a server that's gone doesn't keep anyone waiting
+    let r = Arc::new(Canned::default().hang("lemonade copy").answer(
+        "piknik -paste",
+        1,
+        b"",
+        "dial tcp 127.0.0.1:8075: connect: connection refused",
+    ));
+    let e = Lemonade(r.clone()).copy("lost").unwrap_err();
+    assert!(matches!(e, Error::TimedOut { .. }));
+    let e = Piknik(r).paste().unwrap_err().to_string();
+    assert!(e.ends_with("connection refused"), "{e}");
+    // and neither is ever picked by itself
+    let mut env = Fake::default();
+    env.vars
+        .insert("PATH", std::env::var_os("PATH").unwrap_or_default());
+    assert!(!Provider::Lemonade.available(&env));
+    assert!(!Provider::Piknik.available(&env));
+}
+
+#[test]
+#[cfg(unix)]
+fn networked_timeout() {
+    // what lemonade does against a port nothing is listening on, when it doesn't give up
+    let bin = Bin::new("lemonade-timeout");
+    bin.add("lemonade", "exec sleep 30");
+    let mut lemonade = Command::new(bin.0.join("lemonade"));
+    let started = Instant::now();
+    let e = lemonade
+        .arg("paste")
+        .eat_within(&Real, Some(Duration::from_millis(200)))
+        .unwrap_err();
+    assert!(matches!(e, Error::TimedOut { .. }));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    if !integration() || !System.has("lemonade") {
+        return;
+    }
+    // port 9 is discard, which should have nothing on it
+    let e = c!("lemonade" "--host=127.0.0.1" "--port=9" "paste")
+        .eat_within(&Real, Some(NETWORK_TIMEOUT))
+        .unwrap_err();
+    assert!(
+        matches!(e, Error::CommandFailed { .. } | Error::TimedOut { .. }),
+        "{e}"
+    );
+}
+
+#[test]
+fn snarf() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("9 9p read snarf", 0, b"two\nlines\n", "")
+            .answer("9 9p write snarf", 0, b"", ""),
+    );
+    let s = Snarf::new(r.clone());
+    assert_eq!(s.paste().unwrap(), "two\nlines\n");
+    s.copy("snarfed").unwrap();
+    assert_eq!(r.ran.lock().unwrap()[1].1.as_deref(), Some(&b"snarfed"[..]));
+    let s = Snarf::new(Arc::new(Canned::default().answer(
+        "9 9p read snarf",
+        1,
+        b"",
+        "9p: dial unix!/tmp/ns.me.:0/plumb: connection refused\n",
+    )));
+    let e = s.paste().unwrap_err();
+    assert!(matches!(&e, Error::Io(e) if e.kind() == io::ErrorKind::NotConnected));
+    assert!(e
+        .to_string()
+        .starts_with("snarf buffer unavailable: 9p: dial"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn snarf_asked_for() {
+    let bin = Bin::new("snarf");
+    bin.add("9", "");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.vars.insert("WAYLAND_DISPLAY", "wayland-0".into());
+    assert!(matches!(
+        detect(&env, &[Provider::Plan9Snarf]),
+        Err(Error::NoProvider)
+    ));
+    env.vars.insert("CLIPP_PROVIDER", "snarf".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::Plan9Snarf);
+}
+
+#[test]
+fn snarf_round_trip() {
+    if !integration() || !System.has("9") {
+        return;
+    }
+    let s = Snarf::new(real());
+    let _restore = Restore::new(&s);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        s.copy(text).unwrap();
+        assert_eq!(s.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn termux() {
+    let r = Arc::new(
+        Canned::default()
+            .answer("termux-clipboard-get", 0, "two\nlines 👋\n".as_bytes(), "")
+            .answer("termux-clipboard-set", 0, b"", ""),
+    );
+    let t = Termux(r.clone());
+    assert_eq!(t.paste().unwrap(), "two\nlines 👋\n");
+    t.copy("👋").unwrap();
+    assert_eq!(r.ran.lock().unwrap()[1].1.as_deref(), Some("👋".as_bytes()));
+    let t = Termux(Arc::new(Canned::default().hang("termux-clipboard-get")));
+    let e = t.paste().unwrap_err().to_string();
+    assert!(e.starts_with("timed out waiting for termux-clipboard-get"));
+    assert!(e.contains("Termux:API"));
+
+    let mut env = Fake::default();
+    assert!(!Provider::Termux.available(&env));
+    env.vars.insert("TERMUX_VERSION", "0.118.0".into());
+    assert!(Provider::Termux.available(&env));
+}
+
+#[test]
+fn termux_round_trip() {
+    if !integration() || !System.has("termux-clipboard-get") {
+        return;
+    }
+    let t = Termux(real());
+    let _restore = Restore::new(&t);
+    for text in ["line one\nline two\n", "héllo wörld 👋 日本語"] {
+        t.copy(text).unwrap();
+        assert_eq!(t.paste().unwrap(), text);
+    }
+}
+
+#[test]
+fn cygwin() {
+    let dev = std::env::temp_dir().join(format!("clipp-cygwin-{}", std::process::id()));
+    let c = CygwinDev(dev.clone());
+    for text in ["line one\r\nline two\r\n", "héllo wörld 👋 日本語", ""] {
+        c.copy(text).unwrap();
+        assert_eq!(c.paste().unwrap(), text);
+    }
+    c.copy("longer than what comes next").unwrap();
+    c.copy("short").unwrap();
+    assert_eq!(c.paste().unwrap(), "short");
+    std::fs::write(&dev, b"\xff").unwrap();
+    assert!(matches!(c.paste(), Err(Error::InvalidUtf8)));
+    std::fs::remove_file(&dev).unwrap();
+    assert!(matches!(c.paste(), Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound));
+
+    let mut env = Fake::default();
+    env.files.insert(
+        "/proc/version",
+        "CYGWIN_NT-10.0-19045 version 3.5.4-1.x86_64".into(),
+    );
+    assert!(!Provider::CygwinDev.available(&env));
+    env.files.insert("/dev/clipboard", String::new());
+    assert!(Provider::CygwinDev.available(&env));
+    env.files.remove("/proc/version");
+    assert!(!Provider::CygwinDev.available(&env));
+    env.vars.insert("OSTYPE", "cygwin".into());
+    assert!(Provider::CygwinDev.available(&env));
+    env.vars.remove("OSTYPE");
+    env.vars.insert("MSYSTEM", "MINGW64".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::CygwinDev);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn msys() {
+    let bin = Bin::new("msys");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    bin.add("clip.exe", "");
+    bin.add("powershell.exe", "");
+    // just having the programs around isn't enough
+    assert!(matches!(detect(&env, &[]), Err(Error::NoDisplay)));
+    env.vars.insert("MSYSTEM", "MINGW64".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    env.vars.remove("MSYSTEM");
+    env.files.insert(
+        "/proc/version",
+        "MINGW64_NT-10.0-22631 version 3.4.10-87d57229.x86_64".into(),
+    );
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    // /dev/clipboard does it without starting powershell
+    env.files.insert("/dev/clipboard", String::new());
+    env.vars.insert("MSYSTEM", "MSYS".into());
+    assert_eq!(
+        available(&env, &[]).collect::<Vec<_>>(),
+        [Provider::CygwinDev, Provider::WindowsInterop]
+    );
+    assert_eq!("wsl".parse::<Provider>().unwrap(), Provider::WindowsInterop);
+    #[allow(deprecated)]
+    let old = Provider::Wsl;
+    assert_eq!(old, Provider::WindowsInterop);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn wslg() {
+    let bin = Bin::new("wslg");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    env.files.insert(
+        "/proc/version",
+        "Linux version 5.15.153.1-microsoft-standard-WSL2".into(),
+    );
+    bin.add("clip.exe", "");
+    bin.add("powershell.exe", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    bin.add("wl-copy", "");
+    // no WSLg
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    env.vars.insert("WAYLAND_DISPLAY", "wayland-0".into());
+    assert_eq!(
+        available(&env, &[]).collect::<Vec<_>>(),
+        [Provider::Wayland, Provider::WindowsInterop]
+    );
+    // for when the bridge is broken
+    assert_eq!(
+        detect(&env, &[Provider::WindowsInterop]).unwrap(),
+        Provider::WindowsInterop
+    );
+    env.vars.insert("CLIPP_PROVIDER", "interop".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn wsl_detection() {
+    let bin = Bin::new("wsl-detection");
+    let mut env = Fake::default();
+    env.vars.insert("PATH", bin.0.clone().into());
+    bin.add("clip.exe", "");
+    bin.add("powershell.exe", "");
+    bin.add("gpaste-client", "");
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+    // a custom kernel, that doesn't say microsoft
+    env.files
+        .insert("/proc/version", "Linux version 6.6.36-custom".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+    env.vars.insert("WSL_DISTRO_NAME", "Ubuntu".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    env.vars.remove("WSL_DISTRO_NAME");
+    env.vars.insert("WSL_INTEROP", "/run/WSL/8_interop".into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    env.vars.remove("WSL_INTEROP");
+    env.files.insert(
+        "/proc/version",
+        "Linux version 5.15.153.1-microsoft-standard-WSL2".into(),
+    );
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+
+    // interop turned off: on to the next one
+    env.files.insert(
+        "/proc/sys/fs/binfmt_misc/WSLInterop",
+        "disabled\ninterpreter /init\n".into(),
+    );
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+    env.files.insert(
+        "/proc/sys/fs/binfmt_misc/WSLInterop",
+        "enabled\ninterpreter /init\n".into(),
+    );
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::WindowsInterop);
+    let bin = Bin::new("wsl-detection-no-interop");
+    bin.add("gpaste-client", "");
+    env.vars.insert("PATH", bin.0.clone().into());
+    assert_eq!(detect(&env, &[]).unwrap(), Provider::GPaste);
+}
+
+#[test]
+fn raw_paste() {
+    let ps = format!(
+        "powershell.exe -noprofile -command {}",
+        WindowsInterop::PASTE
+    );
+    // what's on the clipboard, and what powershell prints for it
+    for (clipboard, printed) in [
+        ("", ""),
+        ("one line", "one line\r\n"),
+        ("lf\nlines\n", "lf\nlines\n\r\n"),
+        ("crlf\r\nlines\r\n", "crlf\r\nlines\r\n\r\n"),
+        ("no\r\ntrailing newline", "no\r\ntrailing newline\r\n"),
+    ] {
+        let r = Canned::default().answer(&ps, 0, printed.as_bytes(), "");
+        let w = WindowsInterop::new(Arc::new(r));
+        assert_eq!(w.paste().unwrap(), clipboard, "{printed:?}");
+        let mut v = vec![];
+        w.paste_to(&mut v).unwrap();
+        assert_eq!(v, clipboard.as_bytes(), "{printed:?}");
+    }
+    let r = Canned::default().answer(&ps, 0, b"crlf\r\nlines\r\n\r\n", "");
+    let w = WindowsInterop {
+        unix_newlines: true,
+        ..WindowsInterop::new(Arc::new(r))
+    };
+    assert_eq!(w.paste().unwrap(), "crlf\nlines\n");
+    let mut v = vec![];
+    w.paste_to(&mut v).unwrap();
+    assert_eq!(v, b"crlf\nlines\n");
+}
+
+#[test]
+fn utf16() {
+    assert_eq!(utf16le(""), [0xff, 0xfe]);
+    assert_eq!(utf16le("hi"), [0xff, 0xfe, b'h', 0, b'i', 0]);
+    // the bmp
+    assert_eq!(utf16le("é日"), [0xff, 0xfe, 0xe9, 0x00, 0xe5, 0x65]);
+    // beyond it, as a surrogate pair
+    assert_eq!(utf16le("👋"), [0xff, 0xfe, 0x3d, 0xd8, 0x4b, 0xdc]);
+
+    let r = Arc::new(Canned::default().answer("clip.exe", 0, b"", "").answer(
+        "powershell.exe -noprofile -command Set-Clipboard -Value $null",
+        0,
+        b"",
+        "",
+    ));
+    let w = WindowsInterop {
+        clip_exe: true,
+        ..WindowsInterop::new(r.clone())
+    };
+    w.copy("👋").unwrap();
+    w.copy("").unwrap();
+    let ran = r.ran.lock().unwrap();
+    assert_eq!(ran[0], ("clip.exe".into(), Some(utf16le("👋"))));
+    assert!(ran[1].0.ends_with("$null"));
+}
+
+#[test]
+fn clip_exe_round_trip() {
+    if !integration() || !Provider::WindowsInterop.is_available() || !System.has("clip.exe") {
+        return;
+    }
+    let w = WindowsInterop {
+        clip_exe: true,
+        ..WindowsInterop::new(real())
+    };
+    let _restore = Restore::new(&w);
+    for text in ["ascii", "héllo wörld", "日本語", "👋 🦀", "one\r\ntwo"] {
+        w.copy(text).unwrap();
+        assert_eq!(w.paste().unwrap(), text);
     }
 }
@@ -0,0 +1,238 @@
+//! the X11 and Wayland providers, against display servers started just for the test: `Xvfb`,
+//! and sway with its headless backend
+//!
+//! They leave the desktop's clipboard alone, but are slow to start, so these need asking for
+//! with `CLIPP_HEADLESS_TESTS=1`. Whatever isn't installed is skipped.
+use super::{Backend, Probe, Real, Runner, System, Wayland, XClip, XSel};
+use std::{
+    ffi::OsString,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, Output, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+fn headless() -> bool {
+    std::env::var_os("CLIPP_HEADLESS_TESTS").is_some_and(|v| v == "1")
+}
+
+/// A display server of our own, killed when dropped, which a failed assertion does too.
+struct Server {
+    child: Child,
+    /// What points a client at it.
+    env: Vec<(&'static str, OsString)>,
+    /// Its runtime directory, if it has one.
+    dir: Option<PathBuf>,
+}
+
+impl Server {
+    /// `Xvfb`, on the first free display, which it says once it is ready.
+    fn xvfb() -> Option<Self> {
+        if !System.has("Xvfb") {
+            return None;
+        }
+        let child = Command::new("Xvfb")
+            .args(["-displayfd", "1", "-nolisten", "tcp"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut s = Self {
+            child,
+            env: vec![],
+            dir: None,
+        };
+        let mut display = String::new();
+        BufReader::new(s.child.stdout.take()?)
+            .read_line(&mut display)
+            .ok()?;
+        let display = display.trim();
+        if display.is_empty() {
+            return None;
+        }
+        s.env.push(("DISPLAY", format!(":{display}").into()));
+        Some(s)
+    }
+
+    /// sway, drawing nowhere, once its socket is there.
+    fn sway() -> Option<Self> {
+        use std::os::unix::fs::PermissionsExt;
+        if !System.has("sway") {
+            return None;
+        }
+        let dir = std::env::temp_dir().join(format!("clipp-sway-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).ok()?;
+        // it won't have a runtime directory anyone else can get into
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).ok()?;
+        std::fs::write(dir.join("config"), "xwayland disable\n").ok()?;
+        let child = Command::new("sway")
+            .arg("-c")
+            .arg(dir.join("config"))
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env("XDG_RUNTIME_DIR", &dir)
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut s = Self {
+            child,
+            env: vec![("XDG_RUNTIME_DIR", dir.clone().into())],
+            dir: Some(dir.clone()),
+        };
+        let until = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < until {
+            let socket = std::fs::read_dir(&dir)
+                .ok()?
+                .flatten()
+                .map(|e| e.file_name())
+                .find(|n| {
+                    let n = n.to_string_lossy();
+                    n.starts_with("wayland-") && !n.ends_with(".lock")
+                });
+            if let Some(socket) = socket {
+                s.env.push(("WAYLAND_DISPLAY", socket));
+                return Some(s);
+            }
+            if s.child.try_wait().ok()?.is_some() {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        None
+    }
+
+    /// Runs the helper programs for real, on this server rather than the desktop's.
+    fn runner(&self) -> Arc<dyn Runner> {
+        Arc::new(On(self.env.clone()))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        _ = self.child.kill();
+        _ = self.child.wait();
+        if let Some(dir) = &self.dir {
+            _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+struct On(Vec<(&'static str, OsString)>);
+
+impl On {
+    fn on<'a>(&self, c: &'a mut Command) -> &'a mut Command {
+        c.env_remove("DISPLAY")
+            .env_remove("WAYLAND_DISPLAY")
+            .envs(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+impl Runner for On {
+    fn output(
+        &self,
+        c: &mut Command,
+        input: Option<&[u8]>,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Real.output(self.on(c), input, read, timeout)
+    }
+
+    fn output_from(
+        &self,
+        c: &mut Command,
+        input: &mut dyn Read,
+        read: bool,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Real.output_from(self.on(c), input, read, timeout)
+    }
+
+    fn output_to(
+        &self,
+        c: &mut Command,
+        out: &mut dyn Write,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Real.output_to(self.on(c), out, timeout)
+    }
+
+    fn spawn(&self, c: &mut Command) -> io::Result<Child> {
+        Real.spawn(self.on(c))
+    }
+}
+
+/// Round trips on a clipboard nothing has been copied to yet.
+fn exercise(b: &dyn Backend) {
+    let short = |s: &str| s.chars().take(40).collect::<String>();
+    assert_eq!(b.paste().unwrap(), "");
+    for text in [
+        "text",
+        "héllo wörld 👋",
+        "ends with a newline\n",
+        "\n\n",
+        "one\r\ntwo",
+        &"multi\nmegabyte ".repeat(300_000),
+    ] {
+        b.copy(text).unwrap();
+        let pasted = b.paste().unwrap();
+        assert!(
+            pasted == text,
+            "{:?} ({} bytes) pasted as {:?} ({} bytes)",
+            short(text),
+            text.len(),
+            short(&pasted),
+            pasted.len()
+        );
+    }
+    b.clear().unwrap();
+    assert_eq!(b.paste().unwrap(), "");
+}
+
+#[test]
+fn xclip() {
+    if !headless() || !System.has("xclip") {
+        return;
+    }
+    let Some(x) = Server::xvfb() else {
+        return;
+    };
+    exercise(&XClip::new(x.runner()));
+}
+
+#[test]
+fn xsel() {
+    if !headless() || !System.has("xsel") {
+        return;
+    }
+    let Some(x) = Server::xvfb() else {
+        return;
+    };
+    exercise(&XSel::new(x.runner()));
+}
+
+#[test]
+fn wayland() {
+    if !headless() || !System.has("wl-copy") {
+        return;
+    }
+    let Some(sway) = Server::sway() else {
+        return;
+    };
+    exercise(&Wayland::new(sway.runner()));
+    // told by wl-paste --watch
+    let clip = crate::Clipboard::new(Wayland::new(sway.runner()));
+    let copying = clip.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(500));
+        copying.copy("watched").unwrap();
+    });
+    let copied = clip.wait_for_change(Some(Duration::from_secs(10)));
+    assert_eq!(copied.unwrap().as_deref(), Some("watched"));
+}
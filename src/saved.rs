@@ -0,0 +1,213 @@
+//! putting back what was on the clipboard
+use crate::{providers::is_text, Clipboard, Error, Result};
+
+/// What was on a clipboard when it was [saved](Self::save), put back when this is dropped,
+/// for the clipboard to be borrowed without the user losing what they had on it.
+///
+/// Where the clipboard can say what it offers and take several types at once, each of them
+/// is saved; otherwise what it pastes, as bytes. If it was empty, it is cleared again.
+///
+/// Failing to put it back when dropped is let go, as there is nothing to be done about it
+/// there, see [`restore`](Self::restore) to hear about it. Guards saved one inside another
+/// each put back what they saved, the outermost last.
+///
+/// ```no_run
+/// let saved = clipp::ClipboardGuard::save()?;
+/// clipp::copy("ctrl+v into the other window")?;
+/// // ... have it pasted
+/// saved.restore()?;
+/// # Ok::<_, clipp::Error>(())
+/// ```
+#[must_use = "dropping it puts back what was saved straight away"]
+pub struct ClipboardGuard {
+    clip: Clipboard,
+    saved: Option<Saved>,
+}
+
+#[derive(Debug)]
+enum Saved {
+    Bytes(Vec<u8>),
+    Types(Vec<(String, Vec<u8>)>),
+}
+
+impl ClipboardGuard {
+    /// Save what is on the clipboard [`copy`](crate::copy) and friends use.
+    ///
+    /// # Errors
+    ///
+    /// If no clipboard is available, or pasting fails.
+    pub fn save() -> Result<Self> {
+        crate::clip()?.save()
+    }
+
+    pub(crate) fn of(clip: &Clipboard) -> Result<Self> {
+        Ok(Self {
+            saved: Some(saved(clip)?),
+            clip: clip.clone(),
+        })
+    }
+
+    /// Put back what was saved now.
+    ///
+    /// # Errors
+    ///
+    /// If copying it back, or clearing an empty clipboard, fails.
+    pub fn restore(mut self) -> Result<()> {
+        self.restored()
+    }
+
+    /// Leave the clipboard as it is, without putting anything back.
+    pub fn forget(mut self) {
+        self.saved = None;
+    }
+
+    fn restored(&mut self) -> Result<()> {
+        match self.saved.take() {
+            None => Ok(()),
+            Some(Saved::Bytes(b)) if b.is_empty() => self.clip.clear(),
+            Some(Saved::Bytes(b)) => self.clip.copy_bytes(&b),
+            Some(Saved::Types(all)) => {
+                let items = all
+                    .iter()
+                    .map(|(mime, data)| (mime.as_str(), data.as_slice()))
+                    .collect::<Vec<_>>();
+                self.clip.backend.copy_multi(&items)
+            }
+        }
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        _ = self.restored();
+    }
+}
+
+impl std::fmt::Debug for ClipboardGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClipboardGuard")
+            .field("clip", &self.clip)
+            .field("saved", &self.saved.is_some())
+            .finish()
+    }
+}
+
+/// Everything `clip` has, as far as it will say.
+fn saved(clip: &Clipboard) -> Result<Saved> {
+    let text = clip.paste_bytes()?;
+    if !clip.copies_multi() {
+        return Ok(Saved::Bytes(text));
+    }
+    let Ok(targets) = clip.backend.targets() else {
+        return Ok(Saved::Bytes(text));
+    };
+    let mut all = vec![];
+    // what isn't a MIME type is another name for one of them, e.g. X11's UTF8_STRING
+    for mime in targets.into_iter().filter(|t| t.contains('/')) {
+        match clip.backend.paste_mime(&mime) {
+            Ok(data) => all.push((mime, data)),
+            Err(Error::FormatUnavailable { .. } | Error::Unsupported { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if all.is_empty() {
+        return Ok(Saved::Bytes(text));
+    }
+    if !text.is_empty() && !all.iter().any(|(mime, _)| is_text(mime)) {
+        all.push(("text/plain;charset=utf-8".into(), text));
+    }
+    Ok(Saved::Types(all))
+}
+
+#[test]
+fn text() {
+    let clip = Clipboard::memory();
+    clip.copy("theirs").unwrap();
+    let saved = clip.save().unwrap();
+    clip.copy("ours").unwrap();
+    drop(saved);
+    assert_eq!(clip.paste().unwrap(), "theirs");
+    let saved = clip.save().unwrap();
+    clip.copy("ours").unwrap();
+    saved.forget();
+    assert_eq!(clip.paste().unwrap(), "ours");
+    // and when there was nothing
+    clip.clear().unwrap();
+    let saved = clip.save().unwrap();
+    clip.copy("ours").unwrap();
+    saved.restore().unwrap();
+    assert_eq!(clip.paste().unwrap(), "");
+}
+
+#[test]
+fn nested() {
+    let clip = Clipboard::memory();
+    clip.copy("theirs").unwrap();
+    {
+        let _outer = clip.save().unwrap();
+        clip.copy("outer").unwrap();
+        {
+            let _inner = clip.save().unwrap();
+            clip.copy("inner").unwrap();
+        }
+        assert_eq!(clip.paste().unwrap(), "outer");
+    }
+    assert_eq!(clip.paste().unwrap(), "theirs");
+}
+
+#[test]
+fn types() {
+    let clip = Clipboard::new(crate::clipboard::Offers::default());
+    let png = crate::providers::png(1, 1);
+    let was = [
+        ("image/png", &png[..]),
+        ("text/html", b"<img src=a.png>"),
+        ("text/plain", b"a.png"),
+    ];
+    clip.copy_multi(&was).unwrap();
+    let saved = clip.save().unwrap();
+    clip.copy("ours").unwrap();
+    saved.restore().unwrap();
+    assert_eq!(
+        clip.targets().unwrap(),
+        ["image/png", "text/html", "text/plain"]
+    );
+    assert_eq!(clip.paste_image_png().unwrap().unwrap(), png);
+    assert_eq!(clip.paste().unwrap(), "a.png");
+}
+
+#[test]
+fn failed() {
+    struct Broken;
+    impl crate::Backend for Broken {
+        fn copy(&self, _: &str) -> Result<()> {
+            Err(Error::NoProvider)
+        }
+
+        fn paste(&self) -> Result<String> {
+            Ok("theirs".into())
+        }
+    }
+    let clip = Clipboard::new(Broken);
+    // not put back, quietly
+    drop(clip.save().unwrap());
+    assert!(clip.save().unwrap().restore().is_err());
+}
+
+#[test]
+fn temporary() {
+    let clip = Clipboard::memory();
+    clip.copy("theirs").unwrap();
+    let pasted = clip.with_temporary("ours", || clip.paste().unwrap());
+    assert_eq!(pasted.unwrap(), "ours");
+    assert_eq!(clip.paste().unwrap(), "theirs");
+    // put back on the way out of a panic too
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        clip.with_temporary("ours", || panic!("on the way out"))
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(clip.paste().unwrap(), "theirs");
+    clip.clear().unwrap();
+    clip.with_temporary("ours", || ()).unwrap();
+    assert_eq!(clip.paste().unwrap(), "");
+}
@@ -0,0 +1,318 @@
+//! copies that don't stay on the clipboard for longer than they need to
+use crate::{wipe::WipeOnDrop, Clipboard, Error, Result};
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Clears a secret copied with [`Clipboard::copy_secret`] once its time is up, unless
+/// something else has been copied since, which is left alone.
+///
+/// A thread waits out the time, so dropping the guard still has it cleared then. Or it can
+/// be [cancelled](Self::cancel), [cleared now](Self::clear), or
+/// [cleared when dropped](Self::clear_on_drop).
+///
+/// Nothing is cleared once this process has exited, as the thread goes with it. A clipboard
+/// that outlives it, e.g. one held by `xclip` or `wl-copy`, or the system's own, keeps the
+/// secret, so wait for it or [clear](Self::clear) it before exiting.
+pub struct SecretGuard {
+    clip: Clipboard,
+    text: Arc<WipeOnDrop<String>>,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    clear_on_drop: bool,
+}
+
+impl SecretGuard {
+    /// Waits `ttl` in the background, then clears `text` from `clip` if it is still there.
+    pub(crate) fn new(clip: Clipboard, text: &str, ttl: Duration) -> Self {
+        let guard = Self {
+            clip,
+            text: Arc::new(text.to_owned().into()),
+            stop: Arc::default(),
+            clear_on_drop: false,
+        };
+        let (clip, text, stop) = (guard.clip.clone(), guard.text.clone(), guard.stop.clone());
+        let until = Instant::now() + ttl;
+        std::thread::spawn(move || {
+            let (stopped, wake) = &*stop;
+            let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+            while !*stopped {
+                let left = until.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    // still holding the lock, so that it can't be cancelled halfway
+                    *stopped = true;
+                    _ = ours_cleared(&clip, &text);
+                    return;
+                }
+                stopped = wake
+                    .wait_timeout(stopped, left)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+        });
+        guard
+    }
+
+    /// Clear the secret when the guard is dropped too, if it is still there,
+    /// rather than waiting for its time to be up.
+    #[must_use]
+    pub fn clear_on_drop(mut self, clear_on_drop: bool) -> Self {
+        self.clear_on_drop = clear_on_drop;
+        self
+    }
+
+    /// Leave the secret on the clipboard, however long it stays there.
+    pub fn cancel(self) {
+        self.stopped();
+    }
+
+    /// Clear the secret now, if it is still there, returning whether it was.
+    ///
+    /// # Errors
+    ///
+    /// If pasting to compare, or clearing, fails.
+    pub fn clear(self) -> Result<bool> {
+        if self.stopped() {
+            return Ok(false);
+        }
+        ours_cleared(&self.clip, &self.text)
+    }
+
+    /// Stops the thread, returning whether it had already stopped, e.g. by clearing.
+    fn stopped(&self) -> bool {
+        let (stopped, wake) = &*self.stop;
+        let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        wake.notify_all();
+        std::mem::replace(&mut *stopped, true)
+    }
+}
+
+impl Drop for SecretGuard {
+    fn drop(&mut self) {
+        if self.clear_on_drop && !self.stopped() {
+            _ = ours_cleared(&self.clip, &self.text);
+        }
+    }
+}
+
+impl std::fmt::Debug for SecretGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // not the secret
+        f.debug_struct("SecretGuard")
+            .field("clip", &self.clip)
+            .field("clear_on_drop", &self.clear_on_drop)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clears what [`Clipboard::copy_scoped`] copied when it is dropped, unless something else
+/// has been copied since, which is left alone.
+///
+/// Failing to clear it when dropped is let go, as there is nothing to be done about it there,
+/// see [`clear_now`](Self::clear_now) to hear about it.
+///
+/// ```no_run
+/// let clip = clipp::Clipboard::detect()?;
+/// {
+///     let _code = clip.copy_scoped("492 113")?;
+///     println!("your code is on the clipboard, press enter when done");
+///     std::io::stdin().read_line(&mut String::new())?;
+/// }
+/// // and gone
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[must_use = "dropping it clears the clipboard straight away"]
+pub struct ClearGuard {
+    clip: Clipboard,
+    text: WipeOnDrop<String>,
+    armed: bool,
+}
+
+impl ClearGuard {
+    pub(crate) fn new(clip: Clipboard, text: &str) -> Self {
+        Self {
+            clip,
+            text: text.to_owned().into(),
+            armed: true,
+        }
+    }
+
+    /// Leave what was copied on the clipboard after all.
+    pub fn disarm(mut self) {
+        self.armed = false;
+    }
+
+    /// Clear it now, if it is still there, returning whether it was.
+    ///
+    /// # Errors
+    ///
+    /// If pasting to compare, or clearing, fails.
+    pub fn clear_now(mut self) -> Result<bool> {
+        self.armed = false;
+        ours_cleared(&self.clip, &self.text)
+    }
+}
+
+impl Drop for ClearGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            _ = ours_cleared(&self.clip, &self.text);
+        }
+    }
+}
+
+impl std::fmt::Debug for ClearGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClearGuard")
+            .field("clip", &self.clip)
+            .field("armed", &self.armed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clears `clip` if it still has `text`, and not whatever was copied over it.
+fn ours_cleared(clip: &Clipboard, text: &str) -> Result<bool> {
+    match clip.paste().map(WipeOnDrop::from) {
+        Ok(now) if *now == text => clip.clear().map(|()| true),
+        Ok(_) | Err(Error::InvalidUtf8) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+fn after(ms: u64) {
+    std::thread::sleep(Duration::from_millis(ms));
+}
+
+#[test]
+fn expired() {
+    let clip = Clipboard::memory();
+    let _guard = clip
+        .copy_secret("hunter2", Duration::from_millis(50))
+        .unwrap();
+    assert_eq!(clip.paste().unwrap(), "hunter2");
+    after(300);
+    assert_eq!(clip.paste().unwrap(), "");
+}
+
+#[test]
+fn theirs() {
+    // copied over it, before it was cleared
+    let clip = Clipboard::memory();
+    let guard = clip
+        .copy_secret("hunter2", Duration::from_millis(50))
+        .unwrap();
+    clip.copy("mine").unwrap();
+    after(300);
+    assert_eq!(clip.paste().unwrap(), "mine");
+    assert!(!guard.clear().unwrap());
+    // and when compared before clearing
+    clip.copy("hunter2").unwrap();
+    assert!(ours_cleared(&clip, "hunter2").unwrap());
+    clip.copy("mine").unwrap();
+    assert!(!ours_cleared(&clip, "hunter2").unwrap());
+    assert_eq!(clip.paste().unwrap(), "mine");
+}
+
+#[test]
+fn cancelled() {
+    let clip = Clipboard::memory();
+    let guard = clip
+        .copy_secret("hunter2", Duration::from_millis(50))
+        .unwrap();
+    guard.cancel();
+    after(300);
+    assert_eq!(clip.paste().unwrap(), "hunter2");
+    let guard = clip.copy_secret("hunter2", Duration::from_mins(1)).unwrap();
+    assert!(guard.clear().unwrap());
+    assert_eq!(clip.paste().unwrap(), "");
+}
+
+#[test]
+fn dropped() {
+    let clip = Clipboard::memory();
+    drop(
+        clip.copy_secret("hunter2", Duration::from_mins(1))
+            .unwrap()
+            .clear_on_drop(true),
+    );
+    assert_eq!(clip.paste().unwrap(), "");
+    // left to its time
+    drop(
+        clip.copy_secret("hunter2", Duration::from_millis(50))
+            .unwrap(),
+    );
+    assert_eq!(clip.paste().unwrap(), "hunter2");
+    after(300);
+    assert_eq!(clip.paste().unwrap(), "");
+}
+
+#[test]
+#[cfg(feature = "file")]
+fn exited() {
+    use std::process::Command;
+    // the limitation: a clipboard that outlives the process keeps the secret
+    if let Some(path) = std::env::var_os("CLIPP_SECRET_CHILD") {
+        let clip = Clipboard::new(crate::file::FileClipboard(path.into()));
+        let _guard = clip.copy_secret("hunter2", Duration::from_mins(1)).unwrap();
+        return;
+    }
+    let path = std::env::temp_dir().join(format!("clipp-secret-{}", std::process::id()));
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["secret::exited", "--exact", "--quiet"])
+        .env("CLIPP_SECRET_CHILD", &path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let clip = Clipboard::new(crate::file::FileClipboard(path));
+    assert_eq!(clip.paste().unwrap(), "hunter2");
+    clip.clear().unwrap();
+}
+
+#[test]
+#[cfg(feature = "zeroize")]
+fn pasted() {
+    let clip = Clipboard::memory();
+    clip.copy("hunter2").unwrap();
+    let pasted: crate::WipeOnDrop<String> = clip.paste_secret().unwrap();
+    assert_eq!(*pasted, "hunter2");
+}
+
+#[test]
+fn scoped() {
+    let clip = Clipboard::memory();
+    drop(clip.copy_scoped("492 113").unwrap());
+    assert_eq!(clip.paste().unwrap(), "");
+    clip.copy_scoped("492 113").unwrap().disarm();
+    assert_eq!(clip.paste().unwrap(), "492 113");
+    // what was copied over it stays
+    let guard = clip.copy_scoped("492 113").unwrap();
+    clip.copy("mine").unwrap();
+    drop(guard);
+    assert_eq!(clip.paste().unwrap(), "mine");
+    let guard = clip.copy_scoped("492 113").unwrap();
+    assert!(guard.clear_now().unwrap());
+    assert_eq!(clip.paste().unwrap(), "");
+}
+
+#[test]
+fn unwinding() {
+    struct Broken;
+    impl crate::Backend for Broken {
+        fn copy(&self, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            Err(Error::NoProvider)
+        }
+    }
+    // a provider failing as it is dropped, on the way out of a panic, doesn't abort
+    let clip = Clipboard::new(Broken);
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _guard = clip.copy_scoped("492 113").unwrap();
+        panic!("on the way out");
+    }));
+    assert!(panicked.is_err());
+    assert!(clip.copy_scoped("492 113").unwrap().clear_now().is_err());
+}
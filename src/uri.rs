@@ -0,0 +1,238 @@
+//! files on the clipboard, as the `file://` URIs of `text/uri-list`
+use crate::Result;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// `paths` as `text/uri-list` has them: a `file://` URI for each, each ending in `\r\n`.
+pub(crate) fn list(paths: &[&Path]) -> Result<String> {
+    paths.iter().map(|p| Ok(uri(p)? + "\r\n")).collect()
+}
+
+/// `paths` as nautilus copies them, to be pasted elsewhere rather than moved there.
+#[cfg(any(test, feature = "x11-native"))]
+pub(crate) fn gnome(paths: &[&Path]) -> Result<String> {
+    let mut v = String::from("copy");
+    for p in paths {
+        v.push('\n');
+        v.push_str(&uri(p)?);
+    }
+    Ok(v)
+}
+
+/// The files in a `text/uri-list`. Comments, and URIs for anything but a local file,
+/// e.g. a link or a file on an `sftp://` share, are left out.
+pub(crate) fn paths(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(path)
+        .collect()
+}
+
+/// The `file://` URI of an absolute path.
+fn uri(path: &Path) -> Result<String> {
+    let not = |what| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {what}", path.display()),
+        )
+    };
+    if !path.is_absolute() {
+        return Err(not("is not an absolute path").into());
+    }
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+    #[cfg(not(unix))]
+    let bytes = path
+        .to_str()
+        .map(|p| unc(p).unwrap_or_else(|| format!("/{}", p.replace('\\', "/"))))
+        .ok_or_else(|| not("is not unicode"))?
+        .into_bytes();
+    Ok(format!("file://{}", escaped(&bytes)))
+}
+
+/// The local file a URI is for.
+fn path(uri: &str) -> Option<PathBuf> {
+    let rest = uri
+        .get(..5)?
+        .eq_ignore_ascii_case("file:")
+        .then(|| &uri[5..])?;
+    // `file:/path` is still seen, from before the host was there
+    let (host, path) = match rest.strip_prefix("//") {
+        Some(rest) => rest.split_at(rest.find('/')?),
+        None => ("", rest),
+    };
+    if !path.starts_with('/') {
+        return None;
+    }
+    let host = (!host.eq_ignore_ascii_case("localhost")).then_some(host);
+    local(host.filter(|h| !h.is_empty()), unescaped(path)?)
+}
+
+#[cfg(unix)]
+fn local(host: Option<&str>, path: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    // on another machine
+    if host.is_some() {
+        return None;
+    }
+    Some(std::ffi::OsString::from_vec(path).into())
+}
+
+#[cfg(not(unix))]
+fn local(host: Option<&str>, path: Vec<u8>) -> Option<PathBuf> {
+    Some(dos(host, &String::from_utf8(path).ok()?).into())
+}
+
+/// A windows path from the path of a `file://` URI: `/C:/x` is `C:\x`,
+/// and with a host, it is on that machine's share.
+#[cfg(any(test, not(unix)))]
+fn dos(host: Option<&str>, path: &str) -> String {
+    let path = path.replace('/', "\\");
+    match host {
+        Some(host) => format!("\\\\{host}{path}"),
+        None if path.get(2..3) == Some(":") => path[1..].to_owned(),
+        None => path,
+    }
+}
+
+/// What follows `file://` for a windows UNC path, `\\host\share\x` being `host/share/x`.
+#[cfg(any(test, not(unix)))]
+fn unc(path: &str) -> Option<String> {
+    path.strip_prefix(r"\\")
+        .filter(|p| !p.starts_with(['?', '.']))
+        .map(|p| p.replace('\\', "/"))
+}
+
+/// Percent-encodes everything but what is left as it is in a URI's path.
+fn escaped(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut s = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-._~:".contains(&b) {
+            s.push(char::from(b));
+        } else {
+            _ = write!(s, "%{b:02X}");
+        }
+    }
+    s
+}
+
+fn unescaped(s: &str) -> Option<Vec<u8>> {
+    let mut v = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        v.push(if b == b'%' {
+            let mut hex = || char::from(bytes.next()?).to_digit(16);
+            u8::try_from(hex()? << 4 | hex()?).ok()?
+        } else {
+            b
+        });
+    }
+    Some(v)
+}
+
+#[test]
+fn escaping() {
+    assert_eq!(escaped(b"/home/me/a-b_c.d~"), "/home/me/a-b_c.d~");
+    assert_eq!(escaped(b"/my files/100%.txt"), "/my%20files/100%25.txt");
+    assert_eq!(
+        escaped("/ça/日本.txt".as_bytes()),
+        "/%C3%A7a/%E6%97%A5%E6%9C%AC.txt"
+    );
+    assert_eq!(escaped(b"/a#b?c&d+e"), "/a%23b%3Fc%26d%2Be");
+    assert_eq!(escaped(b"/not\xffutf8\n"), "/not%FFutf8%0A");
+    for bytes in [
+        &b"/my files/100%.txt"[..],
+        "/ça/日本.txt".as_bytes(),
+        b"/\xff\0",
+    ] {
+        assert_eq!(unescaped(&escaped(bytes)).unwrap(), bytes);
+    }
+    // either case
+    assert_eq!(unescaped("/%c3%a7").unwrap(), "/ç".as_bytes());
+    for bad in ["/100%", "/100%2", "/%zz", "/%+1"] {
+        assert_eq!(unescaped(bad), None, "{bad}");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn unix() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let paths = [
+        Path::new("/home/me/my notes.txt"),
+        Path::new("/srv/ünïcödé/日本語"),
+        Path::new("/"),
+    ];
+    let l = list(&paths).unwrap();
+    assert_eq!(
+        l,
+        "file:///home/me/my%20notes.txt\r\n\
+        file:///srv/%C3%BCn%C3%AFc%C3%B6d%C3%A9/%E6%97%A5%E6%9C%AC%E8%AA%9E\r\n\
+        file:///\r\n"
+    );
+    assert_eq!(self::paths(&l), paths);
+    assert_eq!(
+        gnome(&paths[..1]).unwrap(),
+        "copy\nfile:///home/me/my%20notes.txt"
+    );
+    // names needn't be unicode
+    let odd = Path::new(OsStr::from_bytes(b"/tmp/\xff\xfe"));
+    assert_eq!(self::paths(&list(&[odd]).unwrap()), [odd]);
+    let e = list(&[Path::new("relative")]).unwrap_err().to_string();
+    assert_eq!(e, "relative is not an absolute path");
+}
+
+#[test]
+#[cfg(unix)]
+fn pasted() {
+    // what file managers and browsers have been seen to copy
+    let l = "# from dolphin\r\n\
+        file:///home/me/a%20b\r\n\
+        file://localhost/home/me/c\r\n\
+        FILE:///home/me/d\n\
+        file:/home/me/e\n\
+        \n\
+        https://example.com/f\r\n\
+        sftp://host/home/me/g\r\n\
+        file://elsewhere/home/me/h\r\n\
+        file:///home/me/100%\r\n\
+        file://nopath\r\n\
+        file:relative\r\n";
+    assert_eq!(
+        paths(l),
+        ["/home/me/a b", "/home/me/c", "/home/me/d", "/home/me/e"].map(PathBuf::from)
+    );
+    assert!(paths("").is_empty());
+}
+
+#[test]
+fn windows() {
+    // the conversions happen everywhere, so they're tested everywhere
+    assert_eq!(
+        dos(None, "/C:/Users/me/my notes.txt"),
+        r"C:\Users\me\my notes.txt"
+    );
+    assert_eq!(dos(Some("server"), "/share/x"), r"\\server\share\x");
+    assert_eq!(dos(None, "/C:/"), r"C:\");
+    assert_eq!(unc(r"\\server\share\x").unwrap(), "server/share/x");
+    assert_eq!(unc(r"C:\x"), None);
+    assert_eq!(unc(r"\\?\C:\x"), None);
+    #[cfg(not(unix))]
+    {
+        let paths = [
+            Path::new(r"C:\Users\me\my notes.txt"),
+            Path::new(r"\\server\share\日本"),
+        ];
+        let l = list(&paths).unwrap();
+        assert_eq!(
+            l,
+            "file:///C:/Users/me/my%20notes.txt\r\nfile://server/share/%E6%97%A5%E6%9C%AC\r\n"
+        );
+        assert_eq!(self::paths(&l), paths);
+        assert_eq!(self::paths("file:///C:/x")[0], Path::new(r"C:\x"));
+    }
+}
@@ -0,0 +1,657 @@
+//! waiting for something to be copied
+use crate::{ChangeToken, Changes, Clipboard, Result};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How [`Clipboard::wait_for_change_with`] waits.
+///
+/// Where the clipboard can say when it changes, it is waited on: Wayland's through
+/// `wl-paste --watch`, X11's through the XFIXES extension, natively or with `clipnotify`
+/// where it is installed. Everywhere else, its [`change_token`](Clipboard::change_token) is asked
+/// for every [`interval`](Self::interval), sleeping in between.
+///
+/// ```no_run
+/// use std::sync::{atomic::AtomicBool, Arc};
+/// let stop = Arc::new(AtomicBool::new(false));
+/// let wait = clipp::Wait::new().stop(stop.clone());
+/// let clip = clipp::Clipboard::detect()?;
+/// // until another thread sets `stop`
+/// while let Some(copied) = clip.wait_for_change_with(&wait)? {
+///     println!("copied: {copied}");
+/// }
+/// # Ok::<_, clipp::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Wait {
+    timeout: Option<Duration>,
+    interval: Duration,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Wait {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wait {
+    /// How often the clipboard is looked at by default, where it can't say when it changes.
+    pub const INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Wait for as long as it takes, looking every [`INTERVAL`](Self::INTERVAL).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            timeout: None,
+            interval: Self::INTERVAL,
+            stop: None,
+        }
+    }
+
+    /// Give up after this long.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Look this often, where the clipboard can't say when it changes. Asking for its
+    /// change token can cost a whole paste, see [`ChangeToken`](crate::ChangeToken).
+    /// It is also how soon [`stop`](Self::stop) is noticed.
+    #[must_use]
+    pub const fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Give up once `stop` is set, e.g. from another thread.
+    #[must_use]
+    pub fn stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// What `clip` has once it changes, or [`None`] if it is given up on first.
+    pub(crate) fn changed(&self, clip: &Clipboard) -> Result<Option<String>> {
+        let until = self.timeout.map(|t| Instant::now() + t);
+        // how long to wait before looking again, if it is not time to give up
+        let next = || {
+            if self
+                .stop
+                .as_ref()
+                .is_some_and(|s| s.load(Ordering::Relaxed))
+            {
+                return None;
+            }
+            let left = until.map_or(Duration::MAX, |u| {
+                u.saturating_duration_since(Instant::now())
+            });
+            // never spinning, however short it is asked to be
+            let interval = self.interval.max(Duration::from_millis(1));
+            (!left.is_zero()).then(|| left.min(interval))
+        };
+        if let Some(changes) = clip.backend.changes()? {
+            loop {
+                let Some(wait) = next() else {
+                    return Ok(None);
+                };
+                match changes.next(wait) {
+                    Ok(true) => return clip.paste().map(Some),
+                    Ok(false) => {}
+                    // no longer told, e.g. by a wl-paste too old to watch, so it is looked at
+                    Err(_) => break,
+                }
+            }
+        }
+        let seen = clip.change_token()?;
+        while let Some(wait) = next() {
+            std::thread::sleep(wait);
+            if clip.change_token()? != seen {
+                return clip.paste().map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Each new thing copied to a clipboard, from [`Clipboard::watch`], as it is copied.
+///
+/// What was there to begin with isn't one, unless [asked for](Self::initial), and nor is
+/// the same thing copied again. Where the clipboard can say when it changes, it is waited on;
+/// everywhere else, it is looked at every interval. A failure to look is yielded as it is,
+/// and looking goes on after it. It ends once [stopped](Self::stop).
+///
+/// ```no_run
+/// use std::time::Duration;
+/// let watch = clipp::watch(Duration::from_millis(500))?;
+/// let stop = watch.stop_handle();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_secs(60));
+///     stop.stop();
+/// });
+/// for copied in watch {
+///     println!("copied: {}", copied?);
+/// }
+/// # Ok::<_, clipp::Error>(())
+/// ```
+pub struct Watch {
+    clip: Clipboard,
+    interval: Duration,
+    coalesce: Duration,
+    initial: bool,
+    stop: StopHandle,
+    started: bool,
+    changes: Option<Changes>,
+    seen: Option<ChangeToken>,
+    last: Option<String>,
+}
+
+/// Stops a [`Watch`], from any thread, waking it if it is waiting.
+#[derive(Clone, Debug, Default)]
+pub struct StopHandle(Arc<(Mutex<bool>, Condvar)>);
+
+impl StopHandle {
+    /// Have the watch end.
+    pub fn stop(&self) {
+        let (stopped, wake) = &*self.0;
+        *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wake.notify_all();
+    }
+
+    fn stopped(&self) -> bool {
+        *self.0 .0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits `d`, returning early, and whether it was, if stopped.
+    fn sleep(&self, d: Duration) -> bool {
+        let (stopped, wake) = &*self.0;
+        let stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        *wake
+            .wait_timeout_while(stopped, d, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+    }
+}
+
+impl Watch {
+    /// How long [`Clipboard::spawn_watcher`] waits for copying to settle down.
+    pub const COALESCE: Duration = Duration::from_millis(100);
+
+    /// How often a stop is looked for, while waiting to be told of a change, as that can't
+    /// be woken.
+    const TOLD: Duration = Duration::from_millis(50);
+
+    pub(crate) fn new(clip: Clipboard, interval: Duration) -> Self {
+        Self {
+            clip,
+            // never spinning, however short it is asked to be
+            interval: interval.max(Duration::from_millis(1)),
+            coalesce: Duration::ZERO,
+            initial: false,
+            stop: StopHandle::default(),
+            started: false,
+            changes: None,
+            seen: None,
+            last: None,
+        }
+    }
+
+    /// Yield what was there to begin with first.
+    #[must_use]
+    pub fn initial(mut self, initial: bool) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Once it changes, wait this long for it to stop changing, and only yield what it has
+    /// then, so that several copies in quick succession are one.
+    #[must_use]
+    pub fn coalesce(mut self, coalesce: Duration) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    /// Something to stop it with from another thread, e.g. the one it is handed to.
+    #[must_use]
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Have it end.
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+
+    /// Call `on_change` with each new thing copied from now on, on a thread of its own, until
+    /// the handle is stopped or dropped.
+    ///
+    /// A failure to look is tried again after a while, longer each time it fails in a row,
+    /// up to a minute.
+    pub fn spawn(mut self, on_change: impl Fn(String) + Send + 'static) -> WatcherHandle {
+        const MAX_BACKOFF: Duration = Duration::from_mins(1);
+        let stop = self.stop_handle();
+        let watching = stop.clone();
+        let interval = self.interval;
+        // here, for what is copied once this returns not to be taken for what was there
+        let first = self.begun();
+        let thread = std::thread::spawn(move || {
+            let mut backoff = interval;
+            for copied in first.into_iter().chain(self) {
+                if let Ok(text) = copied {
+                    backoff = interval;
+                    on_change(text);
+                    continue;
+                }
+                if watching.sleep(backoff) {
+                    return;
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+        WatcherHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Starts, unless it has, with what that yields: a failure, or what there was to begin
+    /// with if that is asked for.
+    fn begun(&mut self) -> Option<Result<String>> {
+        if std::mem::replace(&mut self.started, true) {
+            return None;
+        }
+        if let Err(e) = self.start() {
+            return Some(Err(e));
+        }
+        self.initial
+            .then(|| Ok(self.last.clone().unwrap_or_default()))
+    }
+
+    /// Notes what there is to begin with, to only yield what comes after.
+    fn start(&mut self) -> Result<()> {
+        self.changes = self.clip.backend.changes()?;
+        if self.changes.is_none() {
+            self.seen = Some(self.clip.change_token()?);
+        }
+        self.last = Some(self.clip.paste()?);
+        Ok(())
+    }
+
+    /// Whether it changed, waiting up to an interval for it to.
+    fn changed(&mut self) -> Result<bool> {
+        if let Some(changes) = &self.changes {
+            match changes.next(Self::TOLD) {
+                // no longer told, so it is looked at instead
+                Err(_) => self.changes = None,
+                told => return told,
+            }
+        }
+        if self.stop.sleep(self.interval) {
+            return Ok(false);
+        }
+        let token = self.clip.change_token()?;
+        Ok(self.seen.replace(token) != Some(token))
+    }
+}
+
+impl Iterator for Watch {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Result<String>> {
+        if let Some(first) = self.begun() {
+            return Some(first);
+        }
+        while !self.stop.stopped() {
+            match self.changed() {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+            if !self.coalesce.is_zero() && self.stop.sleep(self.coalesce) {
+                break;
+            }
+            match self.clip.paste() {
+                Ok(text) if self.last.as_ref() != Some(&text) => {
+                    self.last = Some(text.clone());
+                    return Some(Ok(text));
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+impl std::fmt::Debug for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watch")
+            .field("clip", &self.clip)
+            .field("interval", &self.interval)
+            .field("coalesce", &self.coalesce)
+            .field("initial", &self.initial)
+            .field("stop", &self.stop)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The thread a [`Watch`] was [spawned](Watch::spawn) on, stopped and waited for when this
+/// is dropped.
+#[derive(Debug)]
+#[must_use = "dropping it stops the watcher straight away"]
+pub struct WatcherHandle {
+    stop: StopHandle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatcherHandle {
+    /// Stop the watcher, and wait for its thread to finish, which it does as soon as it is
+    /// done with any call to `on_change` it is in the middle of.
+    pub fn stop(mut self) {
+        self.stopped();
+    }
+
+    fn stopped(&mut self) {
+        self.stop.stop();
+        if let Some(thread) = self.thread.take() {
+            // on_change panicking is its own business
+            _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.stopped();
+    }
+}
+
+#[cfg(test)]
+fn later(clip: &Clipboard, f: impl FnOnce(&Clipboard) + Send + 'static) {
+    let clip = clip.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        f(&clip);
+    });
+}
+
+#[test]
+fn changed() {
+    let clip = Clipboard::memory();
+    clip.copy("before").unwrap();
+    later(&clip, |c| c.copy("after").unwrap());
+    let copied = clip.wait_for_change(Some(Duration::from_secs(10)));
+    assert_eq!(copied.unwrap().as_deref(), Some("after"));
+    // and to nothing at all
+    later(&clip, |c| c.clear().unwrap());
+    let copied = clip.wait_for_change(Some(Duration::from_secs(10)));
+    assert_eq!(copied.unwrap().as_deref(), Some(""));
+}
+
+#[test]
+fn timed_out() {
+    let clip = Clipboard::memory();
+    let start = Instant::now();
+    let wait = Wait::new()
+        .timeout(Duration::from_millis(200))
+        .interval(Duration::from_millis(20));
+    assert_eq!(clip.wait_for_change_with(&wait).unwrap(), None);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn stopped() {
+    let clip = Clipboard::memory();
+    let stop = Arc::new(AtomicBool::new(false));
+    let wait = Wait::new()
+        .timeout(Duration::from_mins(1))
+        .interval(Duration::from_millis(20))
+        .stop(stop.clone());
+    let start = Instant::now();
+    later(&clip, move |_| stop.store(true, Ordering::Relaxed));
+    assert_eq!(clip.wait_for_change_with(&wait).unwrap(), None);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn told() {
+    use crate::{Backend, Changes};
+    use std::sync::{mpsc, Mutex};
+    // a clipboard that says when it changes, so that it is never looked at in between
+    struct Told {
+        text: Mutex<String>,
+        tell: Mutex<Option<mpsc::Sender<()>>>,
+    }
+    impl Backend for Told {
+        fn copy(&self, text: &str) -> Result<()> {
+            text.clone_into(&mut self.text.lock().unwrap());
+            if let Some(tell) = &*self.tell.lock().unwrap() {
+                _ = tell.send(());
+            }
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            Ok(self.text.lock().unwrap().clone())
+        }
+
+        fn change_token(&self) -> Result<crate::ChangeToken> {
+            panic!("asked instead of told")
+        }
+
+        fn changes(&self) -> Result<Option<Changes>> {
+            let (tx, rx) = mpsc::channel();
+            *self.tell.lock().unwrap() = Some(tx);
+            Ok(Some(Changes::new(rx, || {})))
+        }
+    }
+    let clip = Clipboard::new(Told {
+        text: Mutex::default(),
+        tell: Mutex::default(),
+    });
+    later(&clip, |c| c.copy("told").unwrap());
+    let wait = Wait::new().interval(Duration::from_mins(1));
+    assert_eq!(
+        clip.wait_for_change_with(&wait).unwrap().as_deref(),
+        Some("told")
+    );
+    let wait = wait.timeout(Duration::from_millis(100));
+    assert_eq!(clip.wait_for_change_with(&wait).unwrap(), None);
+}
+
+#[test]
+fn untold() {
+    use crate::{clipboard::Scratch, Backend, Changes};
+    // says it will tell, then stops straight away, as a wl-paste without --watch would
+    struct Untold(Scratch);
+    impl Backend for Untold {
+        fn copy(&self, text: &str) -> Result<()> {
+            self.0.copy(text)
+        }
+
+        fn paste(&self) -> Result<String> {
+            self.0.paste()
+        }
+
+        fn changes(&self) -> Result<Option<Changes>> {
+            let (_, rx) = std::sync::mpsc::channel();
+            Ok(Some(Changes::new(rx, || {})))
+        }
+    }
+    let clip = Clipboard::new(Untold(Scratch::default()));
+    later(&clip, |c| c.copy("looked at").unwrap());
+    let wait = Wait::new()
+        .timeout(Duration::from_secs(10))
+        .interval(Duration::from_millis(10));
+    assert_eq!(
+        clip.wait_for_change_with(&wait).unwrap().as_deref(),
+        Some("looked at")
+    );
+    let mut watch = clip.watch(Duration::from_millis(10));
+    later(&clip, |c| c.copy("watched").unwrap());
+    assert_eq!(watch.next().unwrap().unwrap(), "watched");
+}
+
+#[test]
+fn watched() {
+    let _global = crate::GLOBAL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    crate::mock::install();
+    crate::mock::set("before");
+    let watch = crate::watch(Duration::from_millis(10)).unwrap();
+    let stop = watch.stop_handle();
+    std::thread::spawn(|| {
+        for text in ["one", "one", "two", "two", "three"] {
+            std::thread::sleep(Duration::from_millis(100));
+            crate::mock::set(text);
+        }
+    });
+    let mut watch = watch.map(Result::unwrap);
+    assert_eq!(
+        watch.by_ref().take(3).collect::<Vec<_>>(),
+        ["one", "two", "three"]
+    );
+    stop.stop();
+    assert_eq!(watch.next(), None);
+}
+
+#[test]
+fn failing() {
+    use crate::{Backend, Error};
+    use std::sync::Mutex;
+    // what it has, or None while it fails
+    struct Flaky(Arc<Mutex<Option<String>>>);
+    impl Backend for Flaky {
+        fn copy(&self, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            self.0.lock().unwrap().clone().ok_or(Error::NoProvider)
+        }
+    }
+    let has = Arc::new(Mutex::new(Some("before".to_owned())));
+    let watch = Clipboard::new(Flaky(has.clone())).watch(Duration::from_millis(10));
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        *has.lock().unwrap() = None;
+        std::thread::sleep(Duration::from_millis(100));
+        *has.lock().unwrap() = Some("after".into());
+    });
+    let mut failed = 0;
+    for r in watch {
+        match r {
+            Err(Error::NoProvider) => failed += 1,
+            r => {
+                assert_eq!(r.unwrap(), "after");
+                break;
+            }
+        }
+    }
+    assert!(failed > 0);
+}
+
+#[cfg(test)]
+fn collected() -> (
+    impl Fn(String) + Send + 'static,
+    std::sync::mpsc::Receiver<String>,
+) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = Mutex::new(tx);
+    (move |text| _ = tx.lock().unwrap().send(text), rx)
+}
+
+#[test]
+fn spawned() {
+    let clip = Clipboard::memory();
+    clip.copy("before").unwrap();
+    let (on_change, changed) = collected();
+    let watcher = clip.watch(Duration::from_millis(10)).spawn(on_change);
+    clip.copy("after").unwrap();
+    let wait = Duration::from_secs(10);
+    assert_eq!(changed.recv_timeout(wait).unwrap(), "after");
+    // and once it has stopped, no more
+    watcher.stop();
+    assert!(changed.recv().is_err());
+    // what there was to begin with, when asked for
+    let (on_change, changed) = collected();
+    let _watcher = clip
+        .watch(Duration::from_millis(10))
+        .initial(true)
+        .spawn(on_change);
+    assert_eq!(changed.recv_timeout(wait).unwrap(), "after");
+}
+
+#[test]
+fn coalesced() {
+    let clip = Clipboard::memory();
+    let (on_change, changed) = collected();
+    let _watcher = clip
+        .watch(Duration::from_millis(10))
+        .coalesce(Duration::from_millis(300))
+        .spawn(on_change);
+    std::thread::sleep(Duration::from_millis(100));
+    for text in ["one", "two", "three"] {
+        clip.copy(text).unwrap();
+    }
+    assert_eq!(
+        changed.recv_timeout(Duration::from_secs(10)).unwrap(),
+        "three"
+    );
+    assert!(changed.recv_timeout(Duration::from_millis(500)).is_err());
+}
+
+#[test]
+fn joined() {
+    // stopping it wakes it, however long its interval, and drops on_change with the thread
+    let clip = Clipboard::memory();
+    let held = Arc::new(());
+    for drop_it in [false, true] {
+        let ours = held.clone();
+        let watcher = clip.watch(Duration::from_mins(1)).spawn(move |_| {
+            let _ = &ours;
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        if drop_it {
+            drop(watcher);
+        } else {
+            watcher.stop();
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(Arc::strong_count(&held), 1);
+    }
+}
+
+#[test]
+fn retried() {
+    use crate::{Backend, Error};
+    // fails to begin with
+    struct Flaky(Mutex<Option<String>>);
+    impl Backend for Flaky {
+        fn copy(&self, text: &str) -> Result<()> {
+            *self.0.lock().unwrap() = Some(text.into());
+            Ok(())
+        }
+
+        fn paste(&self) -> Result<String> {
+            self.0.lock().unwrap().clone().ok_or(Error::NoProvider)
+        }
+    }
+    let clip = Clipboard::new(Flaky(Mutex::default()));
+    let (on_change, changed) = collected();
+    let _watcher = clip.watch(Duration::from_millis(10)).spawn(on_change);
+    std::thread::sleep(Duration::from_millis(200));
+    clip.copy("recovered").unwrap();
+    assert_eq!(
+        changed.recv_timeout(Duration::from_secs(10)).unwrap(),
+        "recovered"
+    );
+}
@@ -0,0 +1,134 @@
+//! wiping what was copied and pasted from memory once it is done with, as best as can be
+//! done without `unsafe`
+//!
+//! Only with the `zeroize` feature; without it, these are left as they are. This is not the
+//! zeroize crate, and makes none of its guarantees: copies the allocator or the compiler made
+//! along the way, e.g. when a buffer grew or was moved, are out of its reach.
+use std::{
+    fmt,
+    io::{self, Read},
+    ops::{Deref, DerefMut},
+};
+
+/// What can be overwritten with zeros, allocation and all, leaving it empty.
+///
+/// This is best-effort. Without `unsafe`, there are no volatile writes to ask for, so the zeros
+/// are passed through [`black_box`](std::hint::black_box), which hints but doesn't promise that
+/// they won't be optimized away.
+pub trait Wipe {
+    /// Overwrite all of it, spare capacity included, and leave it empty.
+    fn wipe(&mut self);
+}
+
+impl Wipe for Vec<u8> {
+    fn wipe(&mut self) {
+        if cfg!(feature = "zeroize") {
+            self.clear();
+            self.resize(self.capacity(), 0);
+            std::hint::black_box(&mut *self);
+        }
+        self.clear();
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        let mut v = std::mem::take(self).into_bytes();
+        v.wipe();
+        *self = String::from_utf8(v).expect("empty");
+    }
+}
+
+/// Holds `T`, and [wipes](Wipe) it, as best it can, when dropped, e.g. the text
+/// [`paste_secret`](crate::paste_secret) pasted.
+///
+/// ```
+/// # #[cfg(feature = "zeroize")] {
+/// let pasted = clipp::WipeOnDrop::from(String::from("hunter2"));
+/// assert_eq!(*pasted, "hunter2");
+/// // and gone, when it is dropped
+/// # }
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct WipeOnDrop<T: Wipe>(T);
+
+impl<T: Wipe> From<T> for WipeOnDrop<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Wipe> Deref for WipeOnDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for WipeOnDrop<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for WipeOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<T: Wipe> fmt::Debug for WipeOnDrop<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // not what it holds, which is bound to be a secret
+        f.write_str("WipeOnDrop(..)")
+    }
+}
+
+/// All of `r`, like [`Read::read_to_end`], but wiping what is left behind each time it
+/// needs more room, and whatever was read if reading fails.
+pub(crate) fn read_to_end(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut v = WipeOnDrop(vec![]);
+    if !cfg!(feature = "zeroize") {
+        r.read_to_end(&mut v)?;
+        return Ok(std::mem::take(&mut v));
+    }
+    let mut buf = WipeOnDrop(vec![0; 8 * 1024]);
+    loop {
+        let n = match r.read(&mut buf) {
+            Ok(0) => return Ok(std::mem::take(&mut v)),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if v.len() + n > v.capacity() {
+            let mut bigger = Vec::with_capacity((v.len() + n).max(v.capacity() * 2));
+            bigger.extend_from_slice(&v);
+            v = WipeOnDrop(bigger);
+        }
+        v.extend_from_slice(&buf[..n]);
+    }
+}
+
+#[test]
+fn read() {
+    let text = "0123456789".repeat(10_000);
+    let v = read_to_end(&mut text.as_bytes()).unwrap();
+    assert_eq!(v, text.as_bytes());
+    assert!(read_to_end(&mut io::empty()).unwrap().is_empty());
+}
+
+#[test]
+fn wiped() {
+    let mut v = b"hunter2".to_vec();
+    let capacity = v.capacity();
+    v.wipe();
+    assert!(v.is_empty());
+    assert_eq!(v.capacity(), capacity);
+    let mut s = String::from("hunter2");
+    s.wipe();
+    assert!(s.is_empty());
+    let z = WipeOnDrop::from(String::from("hunter2"));
+    assert_eq!(z.len(), 7);
+    assert_eq!(format!("{z:?}"), "WipeOnDrop(..)");
+}
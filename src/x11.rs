@@ -0,0 +1,1284 @@
+//! talking to the X server ourselves, without xclip or xsel
+use crate::{
+    providers::{hashed, is_text, ChangeToken, Changes},
+    Backend, Error, Result,
+};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex, PoisonError},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+#[cfg(test)]
+mod server;
+
+/// How long to wait for whoever has the clipboard to hand it over.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+// predefined atoms
+const ATOM: u32 = 4;
+const INTEGER: u32 = 19;
+const STRING: u32 = 31;
+
+// events
+const PROPERTY_NOTIFY: u8 = 28;
+const SELECTION_CLEAR: u8 = 29;
+const SELECTION_REQUEST: u8 = 30;
+const SELECTION_NOTIFY: u8 = 31;
+
+const PROPERTY_CHANGE_MASK: u32 = 0x0040_0000;
+
+/// XFIXES' `SelectSelectionInput` for a new owner, its window going, or its client.
+const SELECTION_CHANGES: u32 = 0b111;
+
+/// The X11 clipboard, speaking the X protocol.
+///
+/// Copying takes ownership of the selection, and a thread hands it to anyone who pastes until
+/// something else is copied. Like any X11 program's, what was copied goes away when the process exits,
+/// unless it is [persisted](Backend::persist) first.
+pub struct X11 {
+    selection: &'static str,
+    /// Instead of `DISPLAY`.
+    display: Option<String>,
+    /// The window that owns the selection, the thread serving it, and its connection to end
+    /// it with, since the last copy.
+    serving: Mutex<Option<(u32, JoinHandle<()>, Stream)>>,
+}
+
+impl X11 {
+    pub fn new(selection: &'static str) -> Self {
+        Self {
+            selection,
+            display: None,
+            serving: Mutex::default(),
+        }
+    }
+
+    fn connect(&self) -> Result<Conn> {
+        match &self.display {
+            Some(d) => Conn::open(d),
+            None => match std::env::var("DISPLAY") {
+                Ok(d) if !d.is_empty() => Conn::open(&d),
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "DISPLAY is not set").into()),
+            },
+        }
+    }
+
+    /// Takes the selection, offering each of `items` as the target it is named for, in order.
+    /// Plain text is offered as every target text goes by.
+    fn offer(&self, items: Vec<(String, Vec<u8>)>) -> Result<()> {
+        // held throughout, so that copies at once take turns
+        let mut last = self.serving.lock().unwrap_or_else(PoisonError::into_inner);
+        // the owner before goes first, so that they never pile up
+        if let Some((_, serving, stream)) = last.take() {
+            _ = stream.shutdown();
+            _ = serving.join();
+        }
+        let mut c = self.connect()?;
+        let stream = c.stream.try_clone()?;
+        let (ready, taken) = mpsc::channel();
+        let selection = self.selection;
+        let serving = std::thread::spawn(move || {
+            let taking = items
+                .into_iter()
+                .map(|(mime, data)| {
+                    let target = if is_text(&mime) { "UTF8_STRING" } else { &mime };
+                    Ok((c.atom(target)?, data))
+                })
+                .collect::<Result<Vec<_>>>()
+                .and_then(|items| Ok((Owner::new(&mut c, selection)?, items)));
+            match taking {
+                Ok((mut owner, items)) => {
+                    _ = ready.send(Ok(owner.w));
+                    // whatever goes wrong from here, nobody is listening
+                    _ = owner.serve(&mut c, &items);
+                    // ended for the server too, though a handle to stop it with is still kept
+                    _ = c.stream.shutdown();
+                }
+                Err(e) => _ = ready.send(Err(e)),
+            }
+        });
+        let w = taken
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("the X11 clipboard thread died").into()))?;
+        *last = Some((w, serving, stream));
+        Ok(())
+    }
+}
+
+impl Backend for X11 {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.offer(vec![("text/plain".into(), text.into())])
+    }
+
+    fn copy_mime(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.offer(vec![(mime.into(), data.to_vec())])
+    }
+
+    fn copy_multi(&self, items: &[(&str, &[u8])]) -> Result<()> {
+        self.offer(
+            items
+                .iter()
+                .map(|&(mime, data)| (mime.into(), data.to_vec()))
+                .collect(),
+        )
+    }
+
+    fn copies_multi(&self) -> bool {
+        true
+    }
+
+    /// As nautilus does, with the paths as text for whatever pastes them somewhere else.
+    fn copy_files(&self, paths: &[&Path]) -> Result<()> {
+        let text = paths
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.offer(vec![
+            (
+                "text/uri-list".into(),
+                crate::uri::list(paths)?.into_bytes(),
+            ),
+            (
+                "x-special/gnome-copied-files".into(),
+                crate::uri::gnome(paths)?.into_bytes(),
+            ),
+            ("text/plain".into(), text.into_bytes()),
+        ])
+    }
+
+    fn paste(&self) -> Result<String> {
+        let mut c = self.connect()?;
+        c.stream.set_read_timeout(Some(TIMEOUT))?;
+        let sel = c.atom(self.selection)?;
+        if c.owner(sel)? == 0 {
+            return Ok(String::new());
+        }
+        let a = Atoms::new(&mut c)?;
+        let w = c.window()?;
+        let time = c.now(w, a.prop)?;
+        if let Some(utf8) = c.convert(w, sel, a.utf8, time, &a)? {
+            return String::from_utf8(utf8).map_err(|_| Error::InvalidUtf8);
+        }
+        // latin-1
+        Ok(c.convert(w, sel, STRING, time, &a)?
+            .map(|s| s.into_iter().map(char::from).collect())
+            .unwrap_or_default())
+    }
+
+    fn paste_mime(&self, mime: &str) -> Result<Vec<u8>> {
+        let mut c = self.connect()?;
+        c.stream.set_read_timeout(Some(TIMEOUT))?;
+        let sel = c.atom(self.selection)?;
+        let unavailable = || Error::FormatUnavailable { mime: mime.into() };
+        if c.owner(sel)? == 0 {
+            return Err(unavailable());
+        }
+        let (a, target) = (Atoms::new(&mut c)?, c.atom(mime)?);
+        let w = c.window()?;
+        let time = c.now(w, a.prop)?;
+        c.convert(w, sel, target, time, &a)?.ok_or_else(unavailable)
+    }
+
+    fn targets(&self) -> Result<Vec<String>> {
+        let mut c = self.connect()?;
+        c.stream.set_read_timeout(Some(TIMEOUT))?;
+        let sel = c.atom(self.selection)?;
+        if c.owner(sel)? == 0 {
+            return Ok(vec![]);
+        }
+        let a = Atoms::new(&mut c)?;
+        let w = c.window()?;
+        let time = c.now(w, a.prop)?;
+        let Some(targets) = c.convert(w, sel, a.targets, time, &a)? else {
+            return Ok(vec![]);
+        };
+        let names = targets
+            .chunks_exact(4)
+            .map(|t| c.name(u32_at(t, 0)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(crate::providers::targets(&names.join("\n")))
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut c = self.connect()?;
+        let sel = c.atom(self.selection)?;
+        // nobody owns it (and whoever did hears about it)
+        c.send(&request(22, 0, &[&le(0), &le(sel), &le(0)]))?;
+        c.sync()
+    }
+
+    /// Which window took the selection, and when, which its `TIMESTAMP` says. Nobody
+    /// having it is a change too.
+    fn change_token(&self) -> Result<ChangeToken> {
+        let mut c = self.connect()?;
+        c.stream.set_read_timeout(Some(TIMEOUT))?;
+        let sel = c.atom(self.selection)?;
+        let owner = c.owner(sel)?;
+        if owner == 0 {
+            return Ok(ChangeToken::counted(0));
+        }
+        let a = Atoms::new(&mut c)?;
+        let w = c.window()?;
+        let time = c.now(w, a.prop)?;
+        match c.convert(w, sel, a.timestamp, time, &a)? {
+            Some(stamp) if stamp.len() == 4 => Ok(ChangeToken::counted(
+                u64::from(owner) << 32 | u64::from(u32_at(&stamp, 0)),
+            )),
+            // what has it won't say
+            _ => hashed(self),
+        }
+    }
+
+    /// XFIXES' `SelectionNotify`, for every new owner of the selection, or none. Without
+    /// XFIXES, there are none.
+    fn changes(&self) -> Result<Option<Changes>> {
+        let mut c = self.connect()?;
+        let Some((xfixes, first_event)) = c.extension("XFIXES")? else {
+            return Ok(None);
+        };
+        // it has to hear which version we speak before anything else
+        c.call(&request(xfixes, 0, &[&le(5), &le(0)]))?;
+        let sel = c.atom(self.selection)?;
+        let w = c.window()?;
+        c.send(&request(
+            xfixes,
+            2,
+            &[&le(w), &le(sel), &le(SELECTION_CHANGES)],
+        ))?;
+        c.sync()?;
+        let stream = c.stream.try_clone()?;
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            // until the connection is shut down
+            while let Ok(e) = c.event() {
+                if e[0] & 0x7f == first_event && u32_at(&e, 12) == sel && tx.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Some(Changes::new(rx, move || {
+            _ = stream.shutdown();
+        })))
+    }
+
+    /// Hands the clipboard to the clipboard manager, if there is one, as the freedesktop
+    /// clipboard manager spec has it. Either way, this waits until something else owns it.
+    fn persist(&self) -> Result<()> {
+        let serving = self
+            .serving
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let Some((w, serving, _)) = serving.filter(|(_, s, _)| !s.is_finished()) else {
+            return Ok(());
+        };
+        // managers only look after the clipboard
+        if self.selection == "CLIPBOARD" {
+            let mut c = self.connect()?;
+            let manager = c.atom("CLIPBOARD_MANAGER")?;
+            if c.owner(manager)? != 0 {
+                let (save, prop) = (c.atom("SAVE_TARGETS")?, c.atom("CLIPP_SELECTION")?);
+                // on behalf of the owner, which hears how it went
+                c.send(&request(
+                    24,
+                    0,
+                    &[&le(w), &le(manager), &le(save), &le(prop), &le(0)],
+                ))?;
+                c.sync()?;
+            }
+        }
+        serving
+            .join()
+            .map_err(|_| io::Error::other("the X11 clipboard thread panicked").into())
+    }
+}
+
+/// The atoms the clipboard needs, besides the selection itself.
+struct Atoms {
+    /// Where things are put for us.
+    prop: u32,
+    utf8: u32,
+    incr: u32,
+    targets: u32,
+    timestamp: u32,
+    text: u32,
+    plain: u32,
+    save: u32,
+}
+
+impl Atoms {
+    fn new(c: &mut Conn) -> Result<Self> {
+        Ok(Self {
+            prop: c.atom("CLIPP_SELECTION")?,
+            utf8: c.atom("UTF8_STRING")?,
+            incr: c.atom("INCR")?,
+            targets: c.atom("TARGETS")?,
+            timestamp: c.atom("TIMESTAMP")?,
+            text: c.atom("TEXT")?,
+            plain: c.atom("text/plain;charset=utf-8")?,
+            save: c.atom("SAVE_TARGETS")?,
+        })
+    }
+}
+
+/// Holds a selection, and hands it out.
+struct Owner {
+    w: u32,
+    sel: u32,
+    time: u32,
+    a: Atoms,
+    /// Transfers too big for one go, by requestor and property: what's left to send, and its type.
+    incr: HashMap<(u32, u32), (Vec<u8>, u32)>,
+}
+
+impl Owner {
+    fn new(c: &mut Conn, selection: &str) -> Result<Self> {
+        let sel = c.atom(selection)?;
+        let a = Atoms::new(c)?;
+        let w = c.window()?;
+        let time = c.now(w, a.prop)?;
+        c.send(&request(22, 0, &[&le(w), &le(sel), &le(time)]))?;
+        if c.owner(sel)? != w {
+            return Err(
+                io::Error::other(format!("could not take the {selection} selection")).into(),
+            );
+        }
+        Ok(Self {
+            w,
+            sel,
+            time,
+            a,
+            incr: HashMap::new(),
+        })
+    }
+
+    /// Hands out each of `items` as its target, with `UTF8_STRING` standing for
+    /// all of text's, until someone else takes the selection.
+    fn serve(&mut self, c: &mut Conn, items: &[(u32, Vec<u8>)]) -> Result<()> {
+        loop {
+            let e = c.event()?;
+            match e[0] & 0x7f {
+                SELECTION_CLEAR if u32_at(&e, 8) == self.w && u32_at(&e, 12) == self.sel => {
+                    return Ok(())
+                }
+                SELECTION_REQUEST if u32_at(&e, 8) == self.w => {
+                    self.answer(c, &e, items)?;
+                }
+                // the clipboard manager has it now, unless it couldn't take it
+                SELECTION_NOTIFY
+                    if u32_at(&e, 8) == self.w
+                        && u32_at(&e, 16) == self.a.save
+                        && u32_at(&e, 20) != 0 =>
+                {
+                    return Ok(())
+                }
+                // the requestor took the last piece, so on with the next
+                PROPERTY_NOTIFY if e[16] == 1 => {
+                    let key = (u32_at(&e, 4), u32_at(&e, 8));
+                    if let Some((rest, ty)) = self.incr.get_mut(&key) {
+                        let piece: Vec<u8> = rest.drain(..rest.len().min(c.chunk())).collect();
+                        c.send(&change(key.0, key.1, *ty, 8, &piece))?;
+                        // an empty piece says that was all
+                        if piece.is_empty() {
+                            self.incr.remove(&key);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn answer(&mut self, c: &mut Conn, e: &[u8; 32], items: &[(u32, Vec<u8>)]) -> Result<()> {
+        let (time, requestor, target) = (u32_at(e, 4), u32_at(e, 12), u32_at(e, 20));
+        // obsolete clients leave it to us
+        let prop = match u32_at(e, 24) {
+            0 => target,
+            p => p,
+        };
+        let a = &self.a;
+        let text = [a.utf8, STRING, a.text, a.plain];
+        let offered = |t: &u32| {
+            if *t == a.utf8 {
+                text.to_vec()
+            } else {
+                vec![*t]
+            }
+        };
+        let ok = if target == a.targets {
+            let targets: Vec<u8> = [a.targets, a.timestamp]
+                .into_iter()
+                .chain(items.iter().flat_map(|(t, _)| offered(t)))
+                .flat_map(u32::to_le_bytes)
+                .collect();
+            c.send(&change(requestor, prop, ATOM, 32, &targets))?;
+            true
+        } else if target == a.timestamp {
+            c.send(&change(requestor, prop, INTEGER, 32, &le(self.time)))?;
+            true
+        } else if let Some((_, data)) = items.iter().find(|(t, _)| offered(t).contains(&target)) {
+            let (data, ty) = match target {
+                STRING => (latin1(data), STRING),
+                t if text.contains(&t) => (data.clone(), a.utf8),
+                t => (data.clone(), t),
+            };
+            self.give(c, requestor, prop, data, ty)?;
+            true
+        } else {
+            false
+        };
+        let prop = if ok { prop } else { 0 };
+        c.send(&notify(time, requestor, self.sel, target, prop))?;
+        Ok(())
+    }
+
+    /// Puts `data` in `requestor`'s `prop`, as `ty`.
+    fn give(
+        &mut self,
+        c: &mut Conn,
+        requestor: u32,
+        prop: u32,
+        data: Vec<u8>,
+        ty: u32,
+    ) -> Result<()> {
+        if data.len() > c.chunk() {
+            // too big for one request, so it goes in pieces, each when the last has been taken
+            c.send(&request(
+                2,
+                0,
+                &[&le(requestor), &le(0x800), &le(PROPERTY_CHANGE_MASK)],
+            ))?;
+            let len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+            c.send(&change(requestor, prop, self.a.incr, 32, &le(len)))?;
+            self.incr.insert((requestor, prop), (data, ty));
+        } else {
+            c.send(&change(requestor, prop, ty, 8, &data))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tells `requestor` its conversion is done, leaving `prop` 0 if it was refused.
+fn notify(time: u32, requestor: u32, sel: u32, target: u32, prop: u32) -> Vec<u8> {
+    let mut e = [0; 32];
+    e[0] = SELECTION_NOTIFY;
+    for (at, v) in [
+        (4, time),
+        (8, requestor),
+        (12, sel),
+        (16, target),
+        (20, prop),
+    ] {
+        e[at..at + 4].copy_from_slice(&le(v));
+    }
+    request(25, 0, &[&le(requestor), &le(0), &e])
+}
+
+/// UTF-8 as latin-1, for clients that only take STRING.
+fn latin1(data: &[u8]) -> Vec<u8> {
+    String::from_utf8_lossy(data)
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// A connection to the X server.
+struct Conn {
+    stream: Stream,
+    seq: u16,
+    /// Events that came while waiting for a reply.
+    events: VecDeque<[u8; 32]>,
+    root: u32,
+    id: (u32, u32, u32),
+    /// The longest request it takes, in bytes.
+    max_request: usize,
+}
+
+impl Conn {
+    fn open(display: &str) -> Result<Self> {
+        let (addr, number) = parse(display).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't make sense of DISPLAY={display:?}"),
+            )
+        })?;
+        let mut stream = connect(&addr)?;
+        let cookie = xauthority().and_then(|f| cookie(&f, number));
+        let (name, data): (&[u8], &[u8]) = match &cookie {
+            Some(data) => (b"MIT-MAGIC-COOKIE-1", data),
+            None => (b"", b""),
+        };
+        let mut setup = vec![b'l', 0, 11, 0, 0, 0];
+        setup.extend_from_slice(&len16(name.len()).to_le_bytes());
+        setup.extend_from_slice(&len16(data.len()).to_le_bytes());
+        setup.extend_from_slice(&[0, 0]);
+        for s in [name, data] {
+            setup.extend_from_slice(s);
+            setup.resize(setup.len().next_multiple_of(4), 0);
+        }
+        stream.write_all(&setup)?;
+        let mut head = [0; 8];
+        stream.read_exact(&mut head)?;
+        let mut more = vec![0; usize::from(u16::from_le_bytes([head[6], head[7]])) * 4];
+        stream.read_exact(&mut more)?;
+        if head[0] != 1 {
+            // failed says why in so many bytes, authenticate just says why
+            let why = match head[0] {
+                0 => &more[..usize::from(head[1]).min(more.len())],
+                _ => &more,
+            };
+            let why = String::from_utf8_lossy(why);
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the X server would not have us: {}",
+                    why.trim_end_matches('\0').trim()
+                ),
+            )
+            .into());
+        }
+        let vendor = usize::from(u16::from_le_bytes([more[16], more[17]]));
+        let formats = usize::from(more[21]);
+        let screens = 32 + vendor.next_multiple_of(4) + 8 * formats;
+        Ok(Self {
+            stream,
+            seq: 0,
+            events: VecDeque::new(),
+            root: u32_at(&more, screens),
+            id: (u32_at(&more, 4), u32_at(&more, 8), 0),
+            max_request: usize::from(u16::from_le_bytes([more[18], more[19]])) * 4,
+        })
+    }
+
+    fn send(&mut self, request: &[u8]) -> Result<u16> {
+        self.stream.write_all(request)?;
+        self.seq = self.seq.wrapping_add(1);
+        Ok(self.seq)
+    }
+
+    /// Reads whatever comes next: a reply, an error, or an event.
+    fn read(&mut self) -> Result<Vec<u8>> {
+        let mut m = vec![0; 32];
+        self.stream.read_exact(&mut m).map_err(late)?;
+        if m[0] == 1 {
+            let more = u32_at(&m, 4) as usize * 4;
+            m.resize(32 + more, 0);
+            self.stream.read_exact(&mut m[32..]).map_err(late)?;
+        }
+        Ok(m)
+    }
+
+    fn reply(&mut self, seq: u16) -> Result<Vec<u8>> {
+        loop {
+            let m = self.read()?;
+            let of = u16::from_le_bytes([m[2], m[3]]);
+            match m[0] {
+                1 if of == seq => return Ok(m),
+                0 if of == seq => {
+                    return Err(io::Error::other(format!(
+                        "X11 request {} failed with error {}",
+                        m[10], m[1]
+                    ))
+                    .into())
+                }
+                // for some earlier request
+                0 | 1 => {}
+                _ => self.events.push_back(event(&m)),
+            }
+        }
+    }
+
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>> {
+        let seq = self.send(request)?;
+        self.reply(seq)
+    }
+
+    /// Waits until the server has done everything asked of it so far.
+    fn sync(&mut self) -> Result<()> {
+        // GetInputFocus, which does nothing much
+        self.call(&request(43, 0, &[])).map(drop)
+    }
+
+    fn event(&mut self) -> Result<[u8; 32]> {
+        if let Some(e) = self.events.pop_front() {
+            return Ok(e);
+        }
+        loop {
+            let m = self.read()?;
+            // errors for requests without replies, e.g. for a requestor that went away, are no concern
+            if m[0] > 1 {
+                return Ok(event(&m));
+            }
+        }
+    }
+
+    /// How much of a property can go in one request.
+    fn chunk(&self) -> usize {
+        self.max_request - 64
+    }
+
+    fn atom(&mut self, name: &str) -> Result<u32> {
+        let r = self.call(&request(
+            16,
+            0,
+            &[&len16(name.len()).to_le_bytes(), &[0, 0], name.as_bytes()],
+        ))?;
+        Ok(u32_at(&r, 8))
+    }
+
+    fn name(&mut self, atom: u32) -> Result<String> {
+        let r = self.call(&request(17, 0, &[&le(atom)]))?;
+        let len = usize::from(u16::from_le_bytes([r[8], r[9]]));
+        let name = r.get(32..32 + len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the X server's atom name was cut short",
+            )
+        })?;
+        Ok(String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// The opcode of the extension called `name`, and its first event's, if the server has it.
+    fn extension(&mut self, name: &str) -> Result<Option<(u8, u8)>> {
+        let r = self.call(&request(
+            98,
+            0,
+            &[&len16(name.len()).to_le_bytes(), &[0, 0], name.as_bytes()],
+        ))?;
+        Ok((r[8] != 0).then_some((r[9], r[10])))
+    }
+
+    fn owner(&mut self, sel: u32) -> Result<u32> {
+        let r = self.call(&request(23, 0, &[&le(sel)]))?;
+        Ok(u32_at(&r, 8))
+    }
+
+    /// Makes a window to own selections and receive them with, hearing about its properties.
+    fn window(&mut self) -> Result<u32> {
+        self.id.2 += 1;
+        let w = self.id.0 | (self.id.2 & self.id.1);
+        let (x_y, w_h, border_class) = ([0; 4], [1, 0, 1, 0], [0, 0, 2, 0]);
+        self.send(&request(
+            1,
+            0,
+            &[
+                &le(w),
+                &le(self.root),
+                &x_y,
+                &w_h,
+                &border_class,
+                // copy the visual from the parent, and set the event mask
+                &le(0),
+                &le(0x800),
+                &le(PROPERTY_CHANGE_MASK),
+            ],
+        ))?;
+        Ok(w)
+    }
+
+    /// The server's time, which ICCCM would have us use rather than `CurrentTime`:
+    /// append nothing to a property, and see when that happened.
+    fn now(&mut self, w: u32, prop: u32) -> Result<u32> {
+        let mut append = change(w, prop, STRING, 8, &[]);
+        append[1] = 2;
+        self.send(&append)?;
+        loop {
+            let e = self.event()?;
+            if e[0] & 0x7f == PROPERTY_NOTIFY && u32_at(&e, 4) == w && u32_at(&e, 8) == prop {
+                return Ok(u32_at(&e, 12));
+            }
+        }
+    }
+
+    /// Asks for the selection as `target`, or [`None`] if its owner won't.
+    fn convert(
+        &mut self,
+        w: u32,
+        sel: u32,
+        target: u32,
+        time: u32,
+        a: &Atoms,
+    ) -> Result<Option<Vec<u8>>> {
+        self.send(&request(
+            24,
+            0,
+            &[&le(w), &le(sel), &le(target), &le(a.prop), &le(time)],
+        ))?;
+        loop {
+            let e = self.event()?;
+            if e[0] & 0x7f == SELECTION_NOTIFY && u32_at(&e, 8) == w {
+                if u32_at(&e, 20) == 0 {
+                    return Ok(None);
+                }
+                break;
+            }
+        }
+        let (ty, data) = self.take(w, a.prop)?;
+        if ty != a.incr {
+            return Ok(Some(data));
+        }
+        // it comes in pieces, each put there once the last one has been taken
+        let mut all = vec![];
+        loop {
+            let e = self.event()?;
+            if e[0] & 0x7f != PROPERTY_NOTIFY || u32_at(&e, 4) != w || e[16] != 0 {
+                continue;
+            }
+            match self.take(w, a.prop)? {
+                (_, piece) if piece.is_empty() => return Ok(Some(all)),
+                (_, piece) => all.extend(piece),
+            }
+        }
+    }
+
+    /// Gets a property, all of it, and deletes it.
+    fn take(&mut self, w: u32, prop: u32) -> Result<(u32, Vec<u8>)> {
+        let r = self.call(&request(
+            20,
+            1,
+            &[&le(w), &le(prop), &le(0), &le(0), &le(u32::MAX / 4)],
+        ))?;
+        let len = u32_at(&r, 16) as usize * usize::from(r[1] / 8);
+        Ok((u32_at(&r, 8), r[32..32 + len].to_vec()))
+    }
+}
+
+/// A timed out read is the other side not answering.
+fn late(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::TimedOut {
+            what: "waiting for the X11 clipboard".into(),
+        },
+        _ => Error::Io(e),
+    }
+}
+
+fn event(m: &[u8]) -> [u8; 32] {
+    m[..32].try_into().expect("32 bytes")
+}
+
+fn le(v: u32) -> [u8; 4] {
+    v.to_le_bytes()
+}
+
+fn u32_at(m: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(m[at..at + 4].try_into().expect("4 bytes"))
+}
+
+fn len16(n: usize) -> u16 {
+    u16::try_from(n).expect("too long for X11")
+}
+
+/// Builds a request: its opcode, the byte that goes after it, and the rest.
+fn request(op: u8, data: u8, rest: &[&[u8]]) -> Vec<u8> {
+    let mut r = vec![op, data, 0, 0];
+    for part in rest {
+        r.extend_from_slice(part);
+    }
+    r.resize(r.len().next_multiple_of(4), 0);
+    let len = len16(r.len() / 4);
+    r[2..4].copy_from_slice(&len.to_le_bytes());
+    r
+}
+
+/// `ChangeProperty`, replacing what was there.
+fn change(w: u32, prop: u32, ty: u32, format: u8, data: &[u8]) -> Vec<u8> {
+    let units = u32::try_from(data.len() / usize::from(format / 8)).expect("too long for X11");
+    request(
+        18,
+        0,
+        &[
+            &le(w),
+            &le(prop),
+            &le(ty),
+            &[format, 0, 0, 0],
+            &le(units),
+            data,
+        ],
+    )
+}
+
+/// Where the X server is.
+#[derive(Debug, PartialEq, Eq)]
+enum Addr {
+    Unix(PathBuf),
+    Tcp(String, u16),
+}
+
+/// Makes sense of `DISPLAY`, giving where to connect to and the display number.
+fn parse(display: &str) -> Option<(Addr, u16)> {
+    let (host, rest) = display.rsplit_once(':')?;
+    let number = rest.split('.').next()?.parse().ok()?;
+    let addr = match host {
+        "" | "unix" => Addr::Unix(format!("/tmp/.X11-unix/X{number}").into()),
+        // a socket of its own, as with XQuartz
+        host if host.starts_with('/') => Addr::Unix(display.into()),
+        host => Addr::Tcp(host.to_owned(), 6000 + number),
+    };
+    Some((addr, number))
+}
+
+enum Stream {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_read_timeout(timeout),
+            Self::Tcp(s) => s.set_read_timeout(timeout),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.try_clone().map(Self::Unix),
+            Self::Tcp(s) => s.try_clone().map(Self::Tcp),
+        }
+    }
+
+    /// Ends the connection, for whoever is reading it too.
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.shutdown(Shutdown::Both),
+            Self::Tcp(s) => s.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.read(buf),
+            Self::Tcp(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.write(buf),
+            Self::Tcp(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(s) => s.flush(),
+            Self::Tcp(s) => s.flush(),
+        }
+    }
+}
+
+fn connect(addr: &Addr) -> io::Result<Stream> {
+    match addr {
+        #[cfg(unix)]
+        Addr::Unix(path) => match UnixStream::connect(path) {
+            Ok(s) => Ok(Stream::Unix(s)),
+            // servers on linux tend to listen on an abstract socket too, which a chroot or container can still reach
+            #[cfg(target_os = "linux")]
+            Err(e) => {
+                use std::os::{linux::net::SocketAddrExt, unix::ffi::OsStrExt};
+                let abs = std::os::unix::net::SocketAddr::from_abstract_name(
+                    path.as_os_str().as_bytes(),
+                )?;
+                UnixStream::connect_addr(&abs)
+                    .map(Stream::Unix)
+                    .map_err(|_| e)
+            }
+            #[cfg(not(target_os = "linux"))]
+            Err(e) => Err(e),
+        },
+        #[cfg(not(unix))]
+        Addr::Unix(_) => Err(io::ErrorKind::Unsupported.into()),
+        Addr::Tcp(host, port) => {
+            let s = TcpStream::connect((host.as_str(), *port))?;
+            s.set_nodelay(true)?;
+            Ok(Stream::Tcp(s))
+        }
+    }
+}
+
+fn xauthority() -> Option<Vec<u8>> {
+    let path = std::env::var_os("XAUTHORITY")
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".Xauthority")))?;
+    std::fs::read(path).ok()
+}
+
+/// Finds the MIT-MAGIC-COOKIE-1 for display `number` in an Xauthority file.
+fn cookie(file: &[u8], number: u16) -> Option<Vec<u8>> {
+    // each entry is a family, then an address, a display number, a name and the data,
+    // all counted with big endian lengths
+    fn field<'a>(rest: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let len = usize::from(u16::from_be_bytes([*rest.first()?, *rest.get(1)?]));
+        let field = rest.get(2..2 + len)?;
+        *rest = &rest[2 + len..];
+        Some(field)
+    }
+    let number = number.to_string();
+    let mut rest = file;
+    let mut elsewhere = None;
+    while let [a, b, more @ ..] = rest {
+        let family = u16::from_be_bytes([*a, *b]);
+        rest = more;
+        // a truncated entry ends it
+        let (Some(_), Some(n), Some(name), Some(data)) = (
+            field(&mut rest),
+            field(&mut rest),
+            field(&mut rest),
+            field(&mut rest),
+        ) else {
+            break;
+        };
+        if name != b"MIT-MAGIC-COOKIE-1" || !(n.is_empty() || n == number.as_bytes()) {
+            continue;
+        }
+        // local and wild entries are for this machine, others could be for any host
+        if family == 256 || family == 0xffff {
+            return Some(data.to_vec());
+        }
+        elsewhere.get_or_insert_with(|| data.to_vec());
+    }
+    elsewhere
+}
+
+#[test]
+fn displays() {
+    let unix = |p: &str| Addr::Unix(p.into());
+    assert_eq!(parse(":0"), Some((unix("/tmp/.X11-unix/X0"), 0)));
+    assert_eq!(parse(":1.0"), Some((unix("/tmp/.X11-unix/X1"), 1)));
+    assert_eq!(parse("unix:2"), Some((unix("/tmp/.X11-unix/X2"), 2)));
+    // ssh's forwarding
+    assert_eq!(
+        parse("localhost:10.0"),
+        Some((Addr::Tcp("localhost".into(), 6010), 10))
+    );
+    assert_eq!(
+        parse("/private/tmp/com.apple.launchd.abc/org.xquartz:0"),
+        Some((unix("/private/tmp/com.apple.launchd.abc/org.xquartz:0"), 0))
+    );
+    assert_eq!(parse(""), None);
+    assert_eq!(parse("nonsense"), None);
+    assert_eq!(parse(":x"), None);
+}
+
+#[test]
+fn cookies() {
+    fn entry(family: u16, address: &str, number: &str, name: &str, data: &[u8]) -> Vec<u8> {
+        let mut e = family.to_be_bytes().to_vec();
+        for f in [address.as_bytes(), number.as_bytes(), name.as_bytes(), data] {
+            e.extend_from_slice(&len16(f.len()).to_be_bytes());
+            e.extend_from_slice(f);
+        }
+        e
+    }
+    let file = [
+        entry(256, "box", "1", "MIT-MAGIC-COOKIE-1", b"one"),
+        entry(0, "10.0.0.2", "0", "MIT-MAGIC-COOKIE-1", b"remote"),
+        entry(256, "box", "0", "XDM-AUTHORIZATION-1", b"other kind"),
+        entry(256, "box", "0", "MIT-MAGIC-COOKIE-1", b"zero"),
+    ]
+    .concat();
+    assert_eq!(cookie(&file, 0).unwrap(), b"zero");
+    assert_eq!(cookie(&file, 1).unwrap(), b"one");
+    assert_eq!(cookie(&file, 2), None);
+    assert_eq!(
+        cookie(&file[..file.len() - 3], 0).as_deref(),
+        Some(&b"remote"[..])
+    );
+    let wild = entry(0xffff, "", "", "MIT-MAGIC-COOKIE-1", b"any");
+    assert_eq!(cookie(&wild, 7).unwrap(), b"any");
+    assert_eq!(cookie(b"", 0), None);
+}
+
+#[test]
+fn requests() {
+    // InternAtom "TEXT": opcode, only-if-exists, length in words, name length, the name
+    assert_eq!(
+        request(16, 0, &[&4u16.to_le_bytes(), &[0, 0], b"TEXT"]),
+        [16, 0, 3, 0, 4, 0, 0, 0, b'T', b'E', b'X', b'T']
+    );
+    // padded to a word
+    assert_eq!(request(16, 1, &[b"a"]), [16, 1, 2, 0, b'a', 0, 0, 0]);
+    let c = change(1, 2, STRING, 8, b"hi");
+    assert_eq!(c.len(), 28);
+    assert_eq!(u32_at(&c, 20), 2);
+    assert_eq!(&c[24..], b"hi\0\0");
+    assert_eq!(u32_at(&change(1, 2, ATOM, 32, &[0; 12]), 20), 3);
+    assert_eq!(latin1("héllo 👋".as_bytes()), b"h\xe9llo ?");
+}
+
+#[test]
+#[cfg(unix)]
+fn round_trip() {
+    let server = server::Server::start("round-trip");
+    let x = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    };
+    // nobody has it yet
+    assert_eq!(x.paste().unwrap(), "");
+    x.copy("héllo wörld 👋").unwrap();
+    assert_eq!(x.paste().unwrap(), "héllo wörld 👋");
+    // more than the server takes in one request, both ways
+    let big = "0123456789".repeat(10_000);
+    x.copy(&big).unwrap();
+    assert_eq!(x.paste().unwrap(), big);
+    x.copy("again").unwrap();
+    assert_eq!(x.paste().unwrap(), "again");
+    // copying the same again is a change too
+    let again = x.change_token().unwrap();
+    assert_eq!(x.change_token().unwrap(), again);
+    x.copy("again").unwrap();
+    assert_ne!(x.change_token().unwrap(), again);
+    // and the owners before it have gone
+    assert_eq!(server.clients(), 1);
+    // however quickly, and from however many threads, they come
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for n in 0..20 {
+                    x.copy(&n.to_string()).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(x.paste().unwrap(), "19");
+    assert_eq!(server.clients(), 1);
+    x.clear().unwrap();
+    assert_eq!(x.paste().unwrap(), "");
+    let primary = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("PRIMARY")
+    };
+    let secondary = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("SECONDARY")
+    };
+    primary.copy("primary").unwrap();
+    secondary.copy("secondary").unwrap();
+    x.copy("clipboard").unwrap();
+    assert_eq!(primary.paste().unwrap(), "primary");
+    assert_eq!(secondary.paste().unwrap(), "secondary");
+    assert_eq!(x.paste().unwrap(), "clipboard");
+}
+
+#[test]
+#[cfg(unix)]
+fn watched() {
+    let server = server::Server::start("watched");
+    let x = |selection| X11 {
+        display: Some(server.display.clone()),
+        ..X11::new(selection)
+    };
+    let (ours, theirs) = (x("CLIPBOARD"), x("CLIPBOARD"));
+    let changes = ours.changes().unwrap().unwrap();
+    let wait = Duration::from_secs(2);
+    theirs.copy("theirs").unwrap();
+    assert!(changes.next(wait).unwrap());
+    // nobody having it is a change too
+    theirs.clear().unwrap();
+    assert!(changes.next(wait).unwrap());
+    // not the other selections
+    x("PRIMARY").copy("primary").unwrap();
+    assert!(!changes.next(Duration::from_millis(200)).unwrap());
+    // and its connection goes once they are done with, leaving primary's owner
+    drop(changes);
+    assert_eq!(server.clients(), 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn concealed() {
+    let server = server::Server::start("concealed");
+    let x = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    };
+    assert!(x.conceals());
+    x.copy_concealed("hunter2").unwrap();
+    assert_eq!(x.paste().unwrap(), "hunter2");
+    assert!(x
+        .targets()
+        .unwrap()
+        .contains(&"x-kde-passwordManagerHint".to_owned()));
+    assert_eq!(
+        x.paste_mime("x-kde-passwordManagerHint").unwrap(),
+        b"secret"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn html() {
+    let server = server::Server::start("html");
+    let x = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    };
+    let html = "<p>a <b>bold</b> move</p>".repeat(10_000);
+    x.copy_html(&html, "a bold move").unwrap();
+    // what only takes text gets the text
+    assert_eq!(x.paste().unwrap(), "a bold move");
+    assert_eq!(x.paste_mime("text/html").unwrap(), html.as_bytes());
+    let theirs = crate::Clipboard::new(X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    });
+    assert_eq!(theirs.paste_html().unwrap().unwrap(), html);
+    let mut c = x.connect().unwrap();
+    let (sel, a) = (c.atom("CLIPBOARD").unwrap(), Atoms::new(&mut c).unwrap());
+    let (w, ty) = (c.window().unwrap(), c.atom("text/html").unwrap());
+    let time = c.now(w, a.prop).unwrap();
+    assert_eq!(
+        c.convert(w, sel, ty, time, &a).unwrap().unwrap(),
+        html.as_bytes()
+    );
+    let targets = c.convert(w, sel, a.targets, time, &a).unwrap().unwrap();
+    assert!(targets.chunks(4).any(|t| u32_at(t, 0) == ty));
+    // and a plain copy offers it no more
+    x.copy("plain").unwrap();
+    assert_eq!(c.convert(w, sel, ty, time, &a).unwrap(), None);
+    assert!(matches!(
+        x.paste_mime("text/html"),
+        Err(Error::FormatUnavailable { .. })
+    ));
+    assert_eq!(theirs.paste_html().unwrap(), None);
+    // several at once, listed in the order given
+    let md = c.atom("text/markdown").unwrap();
+    assert!(x.copies_multi());
+    x.copy_multi(&[("text/markdown", b"**hi**"), ("text/plain", b"hi")])
+        .unwrap();
+    let time = c.now(w, a.prop).unwrap();
+    let targets = c.convert(w, sel, a.targets, time, &a).unwrap().unwrap();
+    let targets: Vec<u32> = targets.chunks(4).map(|t| u32_at(t, 0)).collect();
+    assert_eq!(
+        targets,
+        [a.targets, a.timestamp, md, a.utf8, STRING, a.text, a.plain]
+    );
+    assert_eq!(theirs.paste().unwrap(), "hi");
+    assert_eq!(theirs.paste_mime("text/markdown").unwrap(), b"**hi**");
+    assert_eq!(
+        theirs.targets().unwrap(),
+        ["text/markdown", "text/plain;charset=utf-8", "text/plain"]
+    );
+    assert_eq!(
+        theirs.paste_any().unwrap(),
+        crate::ClipboardContent::Text("hi".into())
+    );
+    // rtf, with text for what can't take it
+    let rtf = r"{\rtf1\ansi {\b bold} move}";
+    x.copy_rtf(rtf, "bold move").unwrap();
+    assert_eq!(theirs.paste().unwrap(), "bold move");
+    assert_eq!(theirs.paste_rtf().unwrap().unwrap(), rtf);
+    assert_eq!(theirs.paste_html().unwrap(), None);
+    // and an image, on its own
+    let image = crate::providers::png(64, 64);
+    crate::Clipboard::new(x).copy_image_png(&image).unwrap();
+    assert_eq!(theirs.paste_image_png().unwrap().unwrap(), image);
+    assert_eq!(theirs.paste().unwrap(), "");
+    // files, as nautilus would have them, and as text
+    let files = [Path::new("/home/me/my notes.txt"), Path::new("/tmp/ü")];
+    crate::Clipboard::new(X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    })
+    .copy_files(&files)
+    .unwrap();
+    assert_eq!(theirs.paste_files().unwrap().unwrap(), files);
+    assert_eq!(
+        theirs.paste_mime("x-special/gnome-copied-files").unwrap(),
+        b"copy\nfile:///home/me/my%20notes.txt\nfile:///tmp/%C3%BC"
+    );
+    assert_eq!(theirs.paste().unwrap(), "/home/me/my notes.txt\n/tmp/ü");
+}
+
+#[test]
+#[cfg(unix)]
+fn persisted() {
+    let server = server::Server::start("persisted");
+    let x = || X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    };
+    let (ours, theirs) = (x(), x());
+    // with nothing copied, there's nothing to wait for
+    ours.persist().unwrap();
+    ours.copy("keep me").unwrap();
+    // without a clipboard manager, until someone else copies
+    let waiting = std::thread::scope(|s| {
+        let persisting = s.spawn(|| ours.persist());
+        std::thread::sleep(Duration::from_millis(200));
+        let waiting = !persisting.is_finished();
+        theirs.copy("theirs").unwrap();
+        persisting.join().unwrap().unwrap();
+        waiting
+    });
+    assert!(waiting);
+
+    // a clipboard manager takes a copy, then the clipboard
+    let (display, (ready, running)) = (server.display.clone(), mpsc::channel());
+    let manager = std::thread::spawn(move || {
+        let mut c = Conn::open(&display).unwrap();
+        let owner = Owner::new(&mut c, "CLIPBOARD_MANAGER").unwrap();
+        ready.send(()).unwrap();
+        let e = loop {
+            let e = c.event().unwrap();
+            if e[0] & 0x7f == SELECTION_REQUEST {
+                break e;
+            }
+        };
+        let sel = c.atom("CLIPBOARD").unwrap();
+        let text = c.convert(owner.w, sel, owner.a.utf8, 0, &owner.a).unwrap();
+        let keeper = X11 {
+            display: Some(display),
+            ..X11::new("CLIPBOARD")
+        };
+        keeper
+            .copy(std::str::from_utf8(&text.unwrap()).unwrap())
+            .unwrap();
+        let (requestor, target, prop) = (u32_at(&e, 12), u32_at(&e, 20), u32_at(&e, 24));
+        c.send(&notify(0, requestor, owner.sel, target, prop))
+            .unwrap();
+        c.sync().unwrap();
+        // still holding it
+        keeper
+    });
+    running.recv().unwrap();
+    ours.copy("manage me").unwrap();
+    ours.persist().unwrap();
+    drop(ours);
+    let _keeper = manager.join().unwrap();
+    assert_eq!(theirs.paste().unwrap(), "manage me");
+}
+
+#[test]
+#[cfg(unix)]
+fn refused() {
+    let server = server::Server::start("refused");
+    server.refuse();
+    let x = X11 {
+        display: Some(server.display.clone()),
+        ..X11::new("CLIPBOARD")
+    };
+    let e = x.paste().unwrap_err();
+    assert!(e.to_string().contains("No protocol specified"), "{e}");
+}
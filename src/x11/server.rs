@@ -0,0 +1,363 @@
+//! just enough of an X server for the clipboard, to test against
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// What every connection shares.
+#[derive(Default)]
+struct State {
+    /// By number, less one; the predefined ones have placeholders, bar those the clipboard uses.
+    atoms: Vec<String>,
+    /// By window and atom: type, format and data.
+    props: HashMap<(u32, u32), (u32, u8, Vec<u8>)>,
+    /// Selection owners' windows.
+    owners: HashMap<u32, u32>,
+    /// Which client made each window.
+    windows: HashMap<u32, u32>,
+    /// Event masks, by client and window.
+    masks: HashMap<(u32, u32), u32>,
+    /// For sending events to.
+    clients: HashMap<u32, UnixStream>,
+    /// Windows hearing about new selection owners through XFIXES, by selection.
+    fixes: Vec<(u32, u32)>,
+    time: u32,
+}
+
+impl State {
+    fn intern(&mut self, name: String) -> u32 {
+        let n = self
+            .atoms
+            .iter()
+            .position(|a| *a == name)
+            .unwrap_or_else(|| {
+                self.atoms.push(name);
+                self.atoms.len() - 1
+            });
+        u32::try_from(n + 1).unwrap()
+    }
+
+    fn send(&mut self, window: u32, event: &[u8; 32]) {
+        if let Some(s) = self.windows.get(&window).and_then(|c| self.clients.get(c)) {
+            _ = (&*s).write_all(event);
+        }
+    }
+
+    /// Gives `sel` to `owner`, telling the one before, and whoever asked XFIXES to hear of it.
+    fn own(&mut self, sel: u32, owner: u32) {
+        let before = match owner {
+            0 => self.owners.remove(&sel),
+            w => self.owners.insert(sel, w),
+        };
+        if let Some(before) = before.filter(|&b| b != owner) {
+            let mut e = [0; 32];
+            e[0] = 29;
+            e[8..12].copy_from_slice(&before.to_le_bytes());
+            e[12..16].copy_from_slice(&sel.to_le_bytes());
+            self.send(before, &e);
+        }
+        let mut e = [0; 32];
+        e[0] = XFIXES_EVENT;
+        e[8..12].copy_from_slice(&owner.to_le_bytes());
+        e[12..16].copy_from_slice(&sel.to_le_bytes());
+        for (w, _) in self.fixes.clone().into_iter().filter(|&(_, s)| s == sel) {
+            e[4..8].copy_from_slice(&w.to_le_bytes());
+            self.send(w, &e);
+        }
+    }
+
+    fn notify(&mut self, window: u32, atom: u32, deleted: bool) {
+        self.time += 1;
+        let mut e = [0; 32];
+        e[0] = 28;
+        e[4..8].copy_from_slice(&window.to_le_bytes());
+        e[8..12].copy_from_slice(&atom.to_le_bytes());
+        e[12..16].copy_from_slice(&self.time.to_le_bytes());
+        e[16] = u8::from(deleted);
+        for (&(client, w), mask) in &self.masks {
+            if w == window && mask & 0x0040_0000 != 0 {
+                if let Some(s) = self.clients.get(&client) {
+                    _ = (&*s).write_all(&e);
+                }
+            }
+        }
+    }
+}
+
+pub struct Server {
+    pub display: String,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+    refuse: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub fn start(name: &str) -> Self {
+        let display = format!(
+            "{}/clipp-x11-{}-{name}:0",
+            std::env::temp_dir().display(),
+            std::process::id()
+        );
+        let path = PathBuf::from(&display);
+        _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let mut atoms: Vec<String> = (1..=68).map(|n| format!("predefined {n}")).collect();
+        for (n, name) in [(4, "ATOM"), (19, "INTEGER"), (31, "STRING")] {
+            atoms[n - 1] = name.to_owned();
+        }
+        let state = Arc::new(Mutex::new(State {
+            atoms,
+            ..State::default()
+        }));
+        let refuse = Arc::default();
+        let (s, r) = (Arc::clone(&state), Arc::clone(&refuse));
+        thread::spawn(move || {
+            for (client, stream) in (1..).zip(listener.incoming()) {
+                let (s, r) = (Arc::clone(&s), Arc::clone(&r));
+                thread::spawn(move || serve(client, stream.unwrap(), &s, &r));
+            }
+        });
+        Self {
+            display,
+            path,
+            state,
+            refuse,
+        }
+    }
+
+    /// Turns everyone away from now on.
+    pub fn refuse(&self) {
+        self.refuse.store(true, Ordering::Relaxed);
+    }
+
+    /// How many are connected, once those on their way out have gone.
+    pub fn clients(&self) -> usize {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut last = usize::MAX;
+        while Instant::now() < deadline {
+            let now = self.state.lock().unwrap().clients.len();
+            if now == last {
+                break;
+            }
+            last = now;
+            thread::sleep(Duration::from_millis(100));
+        }
+        last
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+const XFIXES: u8 = 138;
+const XFIXES_EVENT: u8 = 87;
+
+fn u32_at(m: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(m[at..at + 4].try_into().unwrap())
+}
+
+fn serve(client: u32, mut s: UnixStream, state: &Mutex<State>, refuse: &AtomicBool) {
+    let mut setup = [0; 12];
+    s.read_exact(&mut setup).unwrap();
+    assert_eq!(&setup[..4], b"l\0\x0b\0");
+    let auth = usize::from(u16::from_le_bytes([setup[6], setup[7]])).next_multiple_of(4)
+        + usize::from(u16::from_le_bytes([setup[8], setup[9]])).next_multiple_of(4);
+    s.read_exact(&mut vec![0; auth]).unwrap();
+    if refuse.load(Ordering::Relaxed) {
+        let why = b"No protocol specified\n\0\0";
+        let mut r = vec![0, 22, 11, 0, 0, 0, 6, 0];
+        r.extend_from_slice(why);
+        s.write_all(&r).unwrap();
+        return;
+    }
+    let mut more = vec![];
+    for v in [0, client << 21, 0x001f_ffff, 0] {
+        more.extend_from_slice(&u32::to_le_bytes(v));
+    }
+    // vendor length, a small max request length to see things go in pieces, one screen, no formats
+    more.extend_from_slice(&[4, 0, 0, 4, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    more.extend_from_slice(b"fake");
+    // the screen, which is its root window and things the clipboard doesn't care about
+    more.extend_from_slice(&[1, 0, 0, 0]);
+    more.resize(more.len() + 36, 0);
+    let mut r = vec![1, 0, 11, 0, 0, 0];
+    r.extend_from_slice(&u16::try_from(more.len() / 4).unwrap().to_le_bytes());
+    r.extend_from_slice(&more);
+    s.write_all(&r).unwrap();
+    state
+        .lock()
+        .unwrap()
+        .clients
+        .insert(client, s.try_clone().unwrap());
+    let mut seq: u16 = 0;
+    loop {
+        let mut head = [0; 4];
+        if s.read_exact(&mut head).is_err() {
+            break;
+        }
+        let mut m = head.to_vec();
+        m.resize(usize::from(u16::from_le_bytes([head[2], head[3]])) * 4, 0);
+        if s.read_exact(&mut m[4..]).is_err() {
+            break;
+        }
+        seq = seq.wrapping_add(1);
+        let mut st = state.lock().unwrap();
+        let reply = request(client, &m, &mut st);
+        if let Some(mut reply) = reply {
+            reply[0] = 1;
+            reply[2..4].copy_from_slice(&seq.to_le_bytes());
+            reply.resize(reply.len().max(32), 0);
+            let more = u32::try_from((reply.len() - 32).div_ceil(4)).unwrap();
+            reply.resize(32 + more as usize * 4, 0);
+            reply[4..8].copy_from_slice(&more.to_le_bytes());
+            _ = s.write_all(&reply);
+        }
+    }
+    state.lock().unwrap().clients.remove(&client);
+}
+
+/// Does what `m` asks, giving the reply if there is one, less its header.
+fn request(client: u32, m: &[u8], st: &mut State) -> Option<Vec<u8>> {
+    let reply = |at8: u32| {
+        let mut r = vec![0; 32];
+        r[8..12].copy_from_slice(&at8.to_le_bytes());
+        Some(r)
+    };
+    match m[0] {
+        // CreateWindow, with only an event mask
+        1 => {
+            let w = u32_at(m, 4);
+            st.windows.insert(w, client);
+            if u32_at(m, 28) == 0x800 {
+                st.masks.insert((client, w), u32_at(m, 32));
+            }
+            None
+        }
+        // ChangeWindowAttributes, likewise
+        2 => {
+            if u32_at(m, 8) == 0x800 {
+                st.masks.insert((client, u32_at(m, 4)), u32_at(m, 12));
+            }
+            None
+        }
+        // InternAtom
+        16 => {
+            let len = usize::from(u16::from_le_bytes([m[4], m[5]]));
+            let name = String::from_utf8(m[8..8 + len].to_vec()).unwrap();
+            reply(st.intern(name))
+        }
+        // GetAtomName
+        17 => {
+            let name = &st.atoms[u32_at(m, 4) as usize - 1];
+            let mut r = vec![0; 32];
+            r[8..10].copy_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+            Some([&r, name.as_bytes()].concat())
+        }
+        // ChangeProperty
+        18 => {
+            let (w, prop, ty, format) = (u32_at(m, 4), u32_at(m, 8), u32_at(m, 12), m[16]);
+            let len = u32_at(m, 20) as usize * usize::from(format / 8);
+            let data = &m[24..24 + len];
+            let p = st.props.entry((w, prop)).or_insert((ty, format, vec![]));
+            match m[1] {
+                0 => *p = (ty, format, data.to_vec()),
+                _ => p.2.extend_from_slice(data),
+            }
+            st.notify(w, prop, false);
+            None
+        }
+        // GetProperty, always for any type, all of it
+        20 => {
+            let (w, prop) = (u32_at(m, 4), u32_at(m, 8));
+            let mut r = vec![0; 32];
+            if let Some((ty, format, data)) = st.props.get(&(w, prop)).cloned() {
+                r[1] = format;
+                r[8..12].copy_from_slice(&ty.to_le_bytes());
+                let units = u32::try_from(data.len() / usize::from(format / 8)).unwrap();
+                r[16..20].copy_from_slice(&units.to_le_bytes());
+                r.extend_from_slice(&data);
+                if m[1] == 1 {
+                    st.props.remove(&(w, prop));
+                    st.notify(w, prop, true);
+                }
+            }
+            Some(r)
+        }
+        // SetSelectionOwner
+        22 => {
+            st.own(u32_at(m, 8), u32_at(m, 4));
+            None
+        }
+        // GetSelectionOwner
+        23 => reply(st.owners.get(&u32_at(m, 4)).copied().unwrap_or(0)),
+        // ConvertSelection
+        24 => {
+            let (requestor, sel) = (u32_at(m, 4), u32_at(m, 8));
+            let mut e = [0; 32];
+            if let Some(owner) = st.owners.get(&sel).copied() {
+                e[0] = 30;
+                e[4..8].copy_from_slice(&m[20..24]);
+                e[8..12].copy_from_slice(&owner.to_le_bytes());
+                e[12..28].copy_from_slice(&m[4..20]);
+                st.send(owner, &e);
+            } else {
+                e[0] = 31;
+                e[8..20].copy_from_slice(&m[4..16]);
+                st.send(requestor, &e);
+            }
+            None
+        }
+        // SendEvent
+        25 => {
+            let mut e: [u8; 32] = m[12..44].try_into().unwrap();
+            e[0] |= 0x80;
+            st.send(u32_at(m, 4), &e);
+            None
+        }
+        // GetInputFocus
+        43 => reply(0),
+        98 | XFIXES => extension(m, st),
+        op => panic!("the fake X server doesn't do request {op}"),
+    }
+}
+
+/// Requests to do with extensions, of which there is only XFIXES.
+fn extension(m: &[u8], st: &mut State) -> Option<Vec<u8>> {
+    match m[0] {
+        // QueryExtension
+        98 => {
+            let len = usize::from(u16::from_le_bytes([m[4], m[5]]));
+            let mut r = vec![0; 32];
+            if &m[8..8 + len] == b"XFIXES" {
+                r[8..11].copy_from_slice(&[1, XFIXES, XFIXES_EVENT]);
+            }
+            Some(r)
+        }
+        // QueryVersion, which is 5, and SelectSelectionInput for any change at all
+        _ if m[1] == 0 => {
+            let mut r = vec![0; 32];
+            r[8] = 5;
+            Some(r)
+        }
+        _ if m[1] == 2 => {
+            let (w, sel) = (u32_at(m, 4), u32_at(m, 8));
+            st.fixes.retain(|&f| f != (w, sel));
+            if u32_at(m, 12) != 0 {
+                st.fixes.push((w, sel));
+            }
+            None
+        }
+        _ => panic!("the fake X server doesn't do XFIXES request {}", m[1]),
+    }
+}
@@ -0,0 +1,114 @@
+//! `copy_fmt` never holds all of what it copies at once, counted by an allocator of its own,
+//! which needs a test binary to itself.
+use clipp::{Backend, Clipboard, Result};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    hash::{DefaultHasher, Hasher},
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Bytes allocated now, and the most there have been since last reset.
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let p = unsafe { System.alloc(layout) };
+        if !p.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        p
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+/// Megabytes, a line at a time.
+struct Lines(u32);
+
+impl fmt::Display for Lines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (0..self.0).try_for_each(|n| writeln!(f, "line {n} of many"))
+    }
+}
+
+/// How much was copied, and a hash of it, rather than all of it.
+#[derive(Default)]
+struct Digest(usize, DefaultHasher);
+
+impl Digest {
+    fn add(&mut self, bytes: &[u8]) {
+        self.0 += bytes.len();
+        self.1.write(bytes);
+    }
+}
+
+impl fmt::Write for Digest {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.add(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Keeps only the [`Digest`] of what it is given.
+#[derive(Default)]
+struct Digesting(Mutex<Digest>);
+
+impl Backend for Digesting {
+    fn copy(&self, text: &str) -> Result<()> {
+        self.copy_from(&mut text.as_bytes())
+    }
+
+    fn copy_from(&self, r: &mut dyn Read) -> Result<()> {
+        let (mut d, mut buf) = (Digest::default(), vec![0; 64 * 1024]);
+        loop {
+            match r.read(&mut buf).map_err(clipp::Error::Read)? {
+                0 => break,
+                n => d.add(&buf[..n]),
+            }
+        }
+        *self.0.lock().unwrap() = d;
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        Err(clipp::Error::Unsupported { what: "pasting" })
+    }
+}
+
+#[test]
+fn streamed() {
+    let lines = Lines(600_000);
+    let mut expected = Digest::default();
+    fmt::write(&mut expected, format_args!("{lines}")).unwrap();
+    let digesting = Arc::new(Digesting::default());
+    let clip = Clipboard::from(digesting.clone() as Arc<dyn Backend>);
+    let before = LIVE.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    clip.copy_fmt(format_args!("{lines}")).unwrap();
+    let most = PEAK.load(Ordering::Relaxed) - before;
+    let copied = digesting.0.lock().unwrap();
+    assert_eq!(copied.0, expected.0);
+    assert_eq!(copied.1.finish(), expected.1.finish());
+    // a few chunks of it, of megabytes
+    assert!(expected.0 > 10 << 20);
+    assert!(
+        most < 1 << 20,
+        "{most} bytes at once, copying {}",
+        expected.0
+    );
+}